@@ -0,0 +1,62 @@
+//! Feeds arbitrary bytes to the multiboot2 info parser, which must reject anything malformed
+//! rather than read outside the info.
+//!
+//! The kernel is a `no_std` staticlib, so the parser is built on its own here, with stand-ins for
+//! the few kernel types it uses. Run with `cargo fuzz run multiboot` from the repository root.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[allow(dead_code)]
+#[path = "../../src/multiboot/mod.rs"]
+mod multiboot;
+
+mod acpi {
+    /// Only the size matters to the parser
+    #[derive(Debug)]
+    #[repr(C, packed)]
+    pub struct Rsdp([u8; 20]);
+
+    impl Rsdp {
+        pub fn is_valid(&self) -> bool {
+            true
+        }
+    }
+
+    #[derive(Debug)]
+    #[repr(C, packed)]
+    pub struct Xsdp([u8; 36]);
+
+    impl Xsdp {
+        pub fn is_valid(&self) -> bool {
+            true
+        }
+    }
+}
+
+mod memory {
+    pub struct MemoryRange;
+
+    impl MemoryRange {
+        pub fn new(_start_addr: usize, _end_addr: usize) -> MemoryRange {
+            MemoryRange
+        }
+    }
+}
+
+use multiboot::Multiboot2Info;
+
+fuzz_target!(|data: &[u8]| {
+    // the bootloader always passes the info 8 byte aligned
+    let mut buffer = vec![0u64; (data.len() + 7) / 8];
+    // SAFETY: The buffer is plain memory, at least as long as `data`.
+    let bytes =
+        unsafe { std::slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, data.len()) };
+    bytes.copy_from_slice(data);
+
+    if let Ok(info) = Multiboot2Info::from_bytes(bytes) {
+        multiboot::read_tags(&info);
+        multiboot::header::missing_tags(&info).count();
+    }
+});
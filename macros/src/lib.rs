@@ -0,0 +1,62 @@
+//! Procedural macros used by the kernel. These run on the host, so unlike the kernel they can
+//! use std.
+
+use proc_macro::{TokenStream, TokenTree};
+
+/// Registers a function as a test that is run inside the kernel (in QEMU), by the test harness
+/// in `ktest`. The function must take no arguments and return nothing.
+///
+/// Use `#[kernel_test(should_panic)]` for tests that are expected to panic. This includes
+/// tests that cause a CPU exception, as the exception handlers panic.
+#[proc_macro_attribute]
+pub fn kernel_test(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let should_panic = match attr.to_string().as_str() {
+        "" => false,
+        "should_panic" => true,
+        _ => return compile_error("expected `#[kernel_test]` or `#[kernel_test(should_panic)]`"),
+    };
+
+    let name = match function_name(item.clone()) {
+        Some(name) => name,
+        None => return compile_error("`#[kernel_test]` can only be used on functions"),
+    };
+
+    // the linker gathers every test into the .kernel_tests section for the harness
+    let registration = format!(
+        "#[used]
+        #[link_section = \".kernel_tests\"]
+        #[allow(non_upper_case_globals)]
+        static __KERNEL_TEST_{name}: crate::ktest::KernelTest = crate::ktest::KernelTest {{
+            name: concat!(module_path!(), \"::{name}\"),
+            func: {name},
+            should_panic: {should_panic},
+        }};",
+        name = name,
+        should_panic = should_panic
+    );
+
+    let mut output = item;
+    output.extend(registration.parse::<TokenStream>().unwrap());
+    output
+}
+
+/// Returns the name of the function `item` defines
+fn function_name(item: TokenStream) -> Option<String> {
+    let mut tokens = item.into_iter();
+    while let Some(token) = tokens.next() {
+        if let TokenTree::Ident(ident) = token {
+            if ident.to_string() == "fn" {
+                return match tokens.next() {
+                    Some(TokenTree::Ident(name)) => Some(name.to_string()),
+                    _ => None,
+                };
+            }
+        }
+    }
+
+    None
+}
+
+fn compile_error(message: &str) -> TokenStream {
+    format!("compile_error!({:?});", message).parse().unwrap()
+}
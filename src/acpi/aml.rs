@@ -0,0 +1,138 @@
+//! Just enough AML to find the sleep states, without an interpreter.
+//!
+//! The `\_Sx_` objects are (nearly always) a package defined directly in the DSDT, i.e.
+//! `Name (_S5, Package () { 0x05, 0x05, Zero, Zero })`, so we search the bytecode for the name
+//! and decode the package that follows it.
+
+/// AML opcodes
+const NAME_OP: u8 = 0x08;
+const PACKAGE_OP: u8 = 0x12;
+const ZERO_OP: u8 = 0x00;
+const ONE_OP: u8 = 0x01;
+const BYTE_PREFIX: u8 = 0x0A;
+const WORD_PREFIX: u8 = 0x0B;
+const DWORD_PREFIX: u8 = 0x0C;
+const ROOT_CHAR: u8 = b'\\';
+
+/// SLP_TYP is 3 bits wide in the PM1 control registers
+const SLEEP_TYPE_MASK: u64 = 0b111;
+
+/// The values to write to the PM1a and PM1b control registers' SLP_TYP field to enter a sleep
+/// state
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SleepType {
+    pub pm1a: u8,
+    pub pm1b: u8,
+}
+
+/// Finds the `\_Sx_` package for sleep state `state` (i.e. 5 for soft off) in `aml`, usually the
+/// DSDT's bytecode
+pub fn find_sleep_type(aml: &[u8], state: u8) -> Option<SleepType> {
+    let name = [b'_', b'S', b'0' + state, b'_'];
+    (0..aml.len().saturating_sub(name.len() - 1))
+        .filter(|index| aml[*index..*index + name.len()] == name)
+        .filter(|index| is_name_definition(aml, *index))
+        .find_map(|index| parse_sleep_package(&aml[index + name.len()..]))
+}
+
+/// Returns true if the name at `index` is being defined with `Name`, rather than referenced
+fn is_name_definition(aml: &[u8], index: usize) -> bool {
+    match index.checked_sub(1).map(|before| aml[before]) {
+        Some(NAME_OP) => true,
+        Some(ROOT_CHAR) => index >= 2 && aml[index - 2] == NAME_OP,
+        _ => false,
+    }
+}
+
+/// Decodes a package of at least two integers, from just after its name
+fn parse_sleep_package(aml: &[u8]) -> Option<SleepType> {
+    let (&opcode, rest) = aml.split_first()?;
+    if opcode != PACKAGE_OP {
+        return None;
+    }
+
+    // the PkgLength's first byte holds the number of bytes that follow it in its top 2 bits
+    let length_bytes = (*rest.first()? >> 6) as usize + 1;
+    let rest = rest.get(length_bytes..)?;
+    let (&num_elements, rest) = rest.split_first()?;
+    if num_elements < 2 {
+        return None;
+    }
+
+    let (pm1a, rest) = parse_integer(rest)?;
+    let (pm1b, _) = parse_integer(rest)?;
+    Some(SleepType {
+        pm1a: (pm1a & SLEEP_TYPE_MASK) as u8,
+        pm1b: (pm1b & SLEEP_TYPE_MASK) as u8,
+    })
+}
+
+/// Decodes an integer constant, returning it and the bytes after it
+fn parse_integer(aml: &[u8]) -> Option<(u64, &[u8])> {
+    let (&opcode, rest) = aml.split_first()?;
+    let size = match opcode {
+        ZERO_OP => return Some((0, rest)),
+        ONE_OP => return Some((1, rest)),
+        BYTE_PREFIX => 1,
+        WORD_PREFIX => 2,
+        DWORD_PREFIX => 4,
+        _ => return None,
+    };
+
+    let bytes = rest.get(..size)?;
+    let value = bytes
+        .iter()
+        .rev()
+        .fold(0, |value, byte| value << 8 | *byte as u64);
+    Some((value, &rest[size..]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn find_s5() {
+        #[rustfmt::skip]
+        let aml = [
+            // a reference to _S5_, which isn't a definition
+            0x70, b'_', b'S', b'5', b'_', 0x60,
+            // Name (\_S5, Package (0x04) { 0x05, Zero, Zero, Zero })
+            NAME_OP, ROOT_CHAR, b'_', b'S', b'5', b'_', PACKAGE_OP, 0x08, 0x04,
+            BYTE_PREFIX, 0x05, ZERO_OP, ZERO_OP, ZERO_OP,
+        ];
+
+        assert_eq!(
+            find_sleep_type(&aml, 5),
+            Some(SleepType { pm1a: 5, pm1b: 0 })
+        );
+        assert_eq!(find_sleep_type(&aml, 3), None);
+    }
+
+    #[test]
+    fn find_s5_with_constants() {
+        #[rustfmt::skip]
+        let aml = [
+            // Name (_S5, Package (0x02) { Zero, One }), where Zero and One are opcodes on their own
+            NAME_OP, b'_', b'S', b'5', b'_', PACKAGE_OP, 0x06, 0x02,
+            ZERO_OP, ONE_OP,
+        ];
+
+        assert_eq!(
+            find_sleep_type(&aml, 5),
+            Some(SleepType { pm1a: 0, pm1b: 1 })
+        );
+    }
+
+    #[test]
+    fn find_truncated_s5() {
+        #[rustfmt::skip]
+        let aml = [
+            // a package that ends before its elements
+            NAME_OP, b'_', b'S', b'5', b'_', PACKAGE_OP, 0x06, 0x02,
+            BYTE_PREFIX,
+        ];
+
+        assert_eq!(find_sleep_type(&aml, 5), None);
+    }
+}
@@ -0,0 +1,166 @@
+//! The Fixed ACPI Description Table, describing the fixed power management hardware.
+
+use bitflags::bitflags;
+use core::mem::size_of;
+
+use super::{find_table_as, read_le, GenericAddress, SdtHeader};
+use crate::arch::paging::PhysicalAddress;
+
+/// Offsets of the fields added after ACPI 1.0, which older tables are too short to have
+const RESET_REGISTER: usize = 116;
+const RESET_VALUE: usize = 128;
+const X_DSDT: usize = 140;
+
+bitflags! {
+    /// Fixed feature flags
+    pub struct FadtFlags: u32 {
+        const WBINVD = 1 << 0;
+        const POWER_BUTTON_IS_CONTROL_METHOD = 1 << 4;
+        const SLEEP_BUTTON_IS_CONTROL_METHOD = 1 << 5;
+        const RTC_WAKES_FROM_S4 = 1 << 7;
+        const TIMER_IS_32_BIT = 1 << 8;
+        const RESET_REGISTER_SUPPORTED = 1 << 10;
+        const HEADLESS = 1 << 12;
+        const HARDWARE_REDUCED = 1 << 20;
+        const LOW_POWER_S0_IDLE = 1 << 21;
+    }
+}
+
+bitflags! {
+    /// The IA-PC boot architecture flags, describing the legacy devices that are present
+    pub struct BootArchitecture: u16 {
+        const LEGACY_DEVICES = 1 << 0;
+        const I8042 = 1 << 1;
+        const VGA_NOT_PRESENT = 1 << 2;
+        const MSI_NOT_SUPPORTED = 1 << 3;
+        const PCIE_ASPM_CONTROLS = 1 << 4;
+        const CMOS_RTC_NOT_PRESENT = 1 << 5;
+    }
+}
+
+/// The ACPI 1.0 part of the FADT. Later fields are read by offset, as the table may not have them.
+#[derive(Debug)]
+#[repr(C, packed)]
+pub struct Fadt {
+    header: SdtHeader,
+    firmware_control: u32,
+    dsdt: u32,
+    _reserved: u8,
+    preferred_pm_profile: u8,
+    sci_interrupt: u16,
+    smi_command_port: u32,
+    acpi_enable: u8,
+    acpi_disable: u8,
+    s4bios_request: u8,
+    pstate_control: u8,
+    pm1a_event_block: u32,
+    pm1b_event_block: u32,
+    pm1a_control_block: u32,
+    pm1b_control_block: u32,
+    pm2_control_block: u32,
+    pm_timer_block: u32,
+    gpe0_block: u32,
+    gpe1_block: u32,
+    pm1_event_length: u8,
+    pm1_control_length: u8,
+    pm2_control_length: u8,
+    pm_timer_length: u8,
+    gpe0_length: u8,
+    gpe1_length: u8,
+    gpe1_base: u8,
+    cstate_control: u8,
+    worst_c2_latency: u16,
+    worst_c3_latency: u16,
+    flush_size: u16,
+    flush_stride: u16,
+    duty_offset: u8,
+    duty_width: u8,
+    day_alarm: u8,
+    month_alarm: u8,
+    century: u8,
+    boot_architecture: u16,
+    _reserved2: u8,
+    flags: u32,
+}
+
+impl Fadt {
+    pub fn get() -> Option<&'static Fadt> {
+        // SAFETY: A table with the FACP signature is always a Fadt.
+        unsafe { find_table_as(b"FACP") }
+    }
+
+    /// The physical address of the DSDT, which holds the AML for the system's devices
+    pub fn dsdt(&self) -> Option<PhysicalAddress> {
+        let dsdt = match read_le(self.header.as_bytes(), X_DSDT, size_of::<u64>()) {
+            0 => self.dsdt as u64,
+            x_dsdt => x_dsdt,
+        };
+        Some(PhysicalAddress::new(dsdt)).filter(|addr| addr.as_u64() != 0)
+    }
+
+    /// The IRQ (in PIC mode) that ACPI events are raised on
+    pub fn sci_interrupt(&self) -> u16 {
+        self.sci_interrupt
+    }
+
+    /// The port that `acpi_enable` and `acpi_disable` are written to, to switch between legacy
+    /// and ACPI mode. `None` if the system is always in ACPI mode.
+    pub fn smi_command_port(&self) -> Option<u16> {
+        Some(self.smi_command_port as u16).filter(|port| *port != 0)
+    }
+
+    pub fn acpi_enable(&self) -> u8 {
+        self.acpi_enable
+    }
+
+    pub fn acpi_disable(&self) -> u8 {
+        self.acpi_disable
+    }
+
+    /// The port of the PM1a control register, which holds SCI_EN and the sleep controls
+    pub fn pm1a_control_block(&self) -> Option<u16> {
+        Some(self.pm1a_control_block as u16).filter(|port| *port != 0)
+    }
+
+    /// The port of the optional second half of the PM1 control register
+    pub fn pm1b_control_block(&self) -> Option<u16> {
+        Some(self.pm1b_control_block as u16).filter(|port| *port != 0)
+    }
+
+    /// The port of the power management timer, which counts at 3.579545 MHz
+    pub fn pm_timer_block(&self) -> Option<u16> {
+        Some(self.pm_timer_block as u16).filter(|port| *port != 0)
+    }
+
+    /// The index of the RTC's century register in CMOS, if it has one
+    pub fn century_register(&self) -> Option<u8> {
+        Some(self.century).filter(|index| *index != 0)
+    }
+
+    pub fn boot_architecture(&self) -> BootArchitecture {
+        BootArchitecture::from_bits_truncate(self.boot_architecture)
+    }
+
+    pub fn flags(&self) -> FadtFlags {
+        FadtFlags::from_bits_truncate(self.flags)
+    }
+
+    /// The register to write to reset the system, and the value to write
+    pub fn reset_register(&self) -> Option<(GenericAddress, u8)> {
+        let bytes = self.header.as_bytes();
+        if !self.flags().contains(FadtFlags::RESET_REGISTER_SUPPORTED) || bytes.len() <= RESET_VALUE
+        {
+            return None;
+        }
+
+        let field = |offset, size| read_le(bytes, RESET_REGISTER + offset, size);
+        let register = GenericAddress {
+            address_space: field(0, 1) as u8,
+            bit_width: field(1, 1) as u8,
+            bit_offset: field(2, 1) as u8,
+            access_size: field(3, 1) as u8,
+            address: field(4, 8),
+        };
+        Some((register, bytes[RESET_VALUE]))
+    }
+}
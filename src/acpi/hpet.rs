@@ -0,0 +1,56 @@
+use super::{find_table_as, GenericAddress, SdtHeader};
+use crate::arch::paging::PhysicalAddress;
+
+/// The HPET description table
+#[derive(Debug)]
+#[repr(C, packed)]
+pub struct HpetTable {
+    header: SdtHeader,
+    event_timer_block_id: u32,
+    base_address: GenericAddress,
+    hpet_number: u8,
+    minimum_tick: u16,
+    page_protection: u8,
+}
+
+impl HpetTable {
+    pub fn get() -> Option<&'static HpetTable> {
+        // SAFETY: A table with the HPET signature is always an HpetTable.
+        unsafe { find_table_as(b"HPET") }
+    }
+
+    /// The physical address of the HPET's registers
+    pub fn base_address(&self) -> PhysicalAddress {
+        PhysicalAddress::new(self.base_address.address)
+    }
+
+    pub fn hpet_number(&self) -> u8 {
+        self.hpet_number
+    }
+
+    /// The PCI vendor ID of the HPET's manufacturer
+    pub fn vendor_id(&self) -> u16 {
+        (self.event_timer_block_id >> 16) as u16
+    }
+
+    /// The number of timers (comparators) in the first timer block
+    pub fn comparators(&self) -> u8 {
+        ((self.event_timer_block_id >> 8) & 0x1F) as u8 + 1
+    }
+
+    /// Returns true if the main counter is 64 bits wide
+    pub fn has_64_bit_counter(&self) -> bool {
+        self.event_timer_block_id & (1 << 13) != 0
+    }
+
+    /// Returns true if the HPET can replace the PIT and RTC interrupts
+    pub fn is_legacy_replacement_capable(&self) -> bool {
+        self.event_timer_block_id & (1 << 15) != 0
+    }
+
+    /// The smallest periodic tick the HPET supports without losing interrupts, in main counter
+    /// ticks
+    pub fn minimum_tick(&self) -> u16 {
+        self.minimum_tick
+    }
+}
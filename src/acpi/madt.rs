@@ -0,0 +1,272 @@
+//! The Multiple APIC Description Table, listing the interrupt controllers and CPUs.
+
+use super::{find_table_as, read_le, SdtHeader};
+use crate::arch::paging::PhysicalAddress;
+
+/// The MADT flag set when the system also has dual 8259 PICs
+const PCAT_COMPAT: u32 = 1 << 0;
+
+/// Entry types
+const LOCAL_APIC: u8 = 0;
+const IO_APIC: u8 = 1;
+const INTERRUPT_OVERRIDE: u8 = 2;
+const LOCAL_APIC_NMI: u8 = 4;
+const LOCAL_APIC_ADDRESS: u8 = 5;
+const LOCAL_X2APIC: u8 = 9;
+
+/// Bits in a local APIC's flags
+const PROCESSOR_ENABLED: u32 = 1 << 0;
+const PROCESSOR_ONLINE_CAPABLE: u32 = 1 << 1;
+
+/// Bits in the MPS INTI flags of overrides and NMIs
+const POLARITY_MASK: u16 = 0b11;
+const POLARITY_ACTIVE_LOW: u16 = 0b11;
+const TRIGGER_MASK: u16 = 0b11 << 2;
+const TRIGGER_LEVEL: u16 = 0b11 << 2;
+
+#[derive(Debug)]
+#[repr(C, packed)]
+pub struct Madt {
+    header: SdtHeader,
+    local_apic_address: u32,
+    flags: u32,
+    // the entries are after here
+}
+
+impl Madt {
+    pub fn get() -> Option<&'static Madt> {
+        // SAFETY: A table with the APIC signature is always a Madt.
+        unsafe { find_table_as(b"APIC") }
+    }
+
+    /// The physical address of every CPU's local APIC, which an entry can override with a 64
+    /// bit address
+    pub fn local_apic_address(&self) -> PhysicalAddress {
+        self.entries()
+            .find_map(|entry| match entry {
+                MadtEntry::LocalApicAddress(addr) => Some(addr),
+                _ => None,
+            })
+            .unwrap_or_else(|| PhysicalAddress::new(self.local_apic_address as u64))
+    }
+
+    /// Returns true if the legacy 8259 PICs are present, and must be masked to use the APICs
+    pub fn has_legacy_pics(&self) -> bool {
+        self.flags & PCAT_COMPAT != 0
+    }
+
+    pub fn entries(&self) -> MadtEntries<'_> {
+        // skip the local APIC address and flags
+        MadtEntries::new(&self.header.data()[8..])
+    }
+
+    /// Returns every CPU that is enabled, or can be brought online
+    pub fn processors(&self) -> impl Iterator<Item = LocalApic> + '_ {
+        self.entries()
+            .filter_map(|entry| match entry {
+                MadtEntry::LocalApic(apic) => Some(apic),
+                _ => None,
+            })
+            .filter(LocalApic::is_usable)
+    }
+
+    pub fn io_apics(&self) -> impl Iterator<Item = IoApic> + '_ {
+        self.entries().filter_map(|entry| match entry {
+            MadtEntry::IoApic(io_apic) => Some(io_apic),
+            _ => None,
+        })
+    }
+
+    pub fn interrupt_overrides(&self) -> impl Iterator<Item = InterruptOverride> + '_ {
+        self.entries().filter_map(|entry| match entry {
+            MadtEntry::InterruptOverride(interrupt_override) => Some(interrupt_override),
+            _ => None,
+        })
+    }
+}
+
+/// A CPU, through its local APIC (or x2APIC)
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct LocalApic {
+    pub processor_id: u32,
+    pub apic_id: u32,
+    pub flags: u32,
+}
+
+impl LocalApic {
+    /// Returns true if the CPU is enabled, or can be brought online
+    pub fn is_usable(&self) -> bool {
+        self.flags & (PROCESSOR_ENABLED | PROCESSOR_ONLINE_CAPABLE) != 0
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct IoApic {
+    pub id: u8,
+    pub address: PhysicalAddress,
+    /// The first global system interrupt this I/O APIC handles
+    pub gsi_base: u32,
+}
+
+/// An ISA IRQ that isn't identity mapped to a global system interrupt, or isn't edge triggered
+/// and active high like the ISA bus
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct InterruptOverride {
+    pub bus: u8,
+    /// The ISA IRQ
+    pub source: u8,
+    pub gsi: u32,
+    pub flags: u16,
+}
+
+impl InterruptOverride {
+    pub fn is_active_low(&self) -> bool {
+        self.flags & POLARITY_MASK == POLARITY_ACTIVE_LOW
+    }
+
+    pub fn is_level_triggered(&self) -> bool {
+        self.flags & TRIGGER_MASK == TRIGGER_LEVEL
+    }
+}
+
+/// The local APIC pin a CPU's NMI is connected to
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct LocalApicNmi {
+    /// 0xFF for every CPU
+    pub processor_id: u8,
+    pub flags: u16,
+    /// The LINT pin, 0 or 1
+    pub lint: u8,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MadtEntry {
+    LocalApic(LocalApic),
+    IoApic(IoApic),
+    InterruptOverride(InterruptOverride),
+    LocalApicNmi(LocalApicNmi),
+    LocalApicAddress(PhysicalAddress),
+    /// An entry type we don't parse (or one too short for its type)
+    Unknown(u8),
+}
+
+impl MadtEntry {
+    /// Parses an entry, including its type and length
+    fn parse(entry: &[u8]) -> MadtEntry {
+        let field = |offset, size| read_le(entry, offset, size);
+        match entry[0] {
+            LOCAL_APIC if entry.len() >= 8 => MadtEntry::LocalApic(LocalApic {
+                processor_id: field(2, 1) as u32,
+                apic_id: field(3, 1) as u32,
+                flags: field(4, 4) as u32,
+            }),
+            IO_APIC if entry.len() >= 12 => MadtEntry::IoApic(IoApic {
+                id: field(2, 1) as u8,
+                address: PhysicalAddress::new(field(4, 4)),
+                gsi_base: field(8, 4) as u32,
+            }),
+            INTERRUPT_OVERRIDE if entry.len() >= 10 => {
+                MadtEntry::InterruptOverride(InterruptOverride {
+                    bus: field(2, 1) as u8,
+                    source: field(3, 1) as u8,
+                    gsi: field(4, 4) as u32,
+                    flags: field(8, 2) as u16,
+                })
+            }
+            LOCAL_APIC_NMI if entry.len() >= 6 => MadtEntry::LocalApicNmi(LocalApicNmi {
+                processor_id: field(2, 1) as u8,
+                flags: field(3, 2) as u16,
+                lint: field(5, 1) as u8,
+            }),
+            LOCAL_APIC_ADDRESS if entry.len() >= 12 => {
+                MadtEntry::LocalApicAddress(PhysicalAddress::new(field(4, 8)))
+            }
+            LOCAL_X2APIC if entry.len() >= 16 => MadtEntry::LocalApic(LocalApic {
+                processor_id: field(12, 4) as u32,
+                apic_id: field(4, 4) as u32,
+                flags: field(8, 4) as u32,
+            }),
+            entry_type => MadtEntry::Unknown(entry_type),
+        }
+    }
+}
+
+/// An iterator over the entries of a MADT. It stops at the first malformed entry.
+pub struct MadtEntries<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> MadtEntries<'a> {
+    fn new(entries: &'a [u8]) -> MadtEntries<'a> {
+        MadtEntries { rest: entries }
+    }
+}
+
+impl<'a> Iterator for MadtEntries<'a> {
+    type Item = MadtEntry;
+
+    fn next(&mut self) -> Option<MadtEntry> {
+        // each entry starts with its type and length
+        let length = *self.rest.get(1)? as usize;
+        if length < 2 || length > self.rest.len() {
+            self.rest = &[];
+            return None;
+        }
+
+        let (entry, rest) = self.rest.split_at(length);
+        self.rest = rest;
+        Some(MadtEntry::parse(entry))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_entries() {
+        #[rustfmt::skip]
+        let entries = [
+            // local APIC 0, enabled
+            0, 8, 0, 0, 1, 0, 0, 0,
+            // local APIC 1, disabled
+            0, 8, 1, 1, 0, 0, 0, 0,
+            // I/O APIC 2 at 0xFEC00000, from GSI 0
+            1, 12, 2, 0, 0x00, 0x00, 0xC0, 0xFE, 0, 0, 0, 0,
+            // IRQ0 is GSI 2, level triggered and active low
+            2, 10, 0, 0, 2, 0, 0, 0, 0b1111, 0,
+            // an unknown entry
+            0x7F, 3, 0,
+            // an entry that runs past the end
+            0, 8, 0,
+        ];
+
+        let mut entries = MadtEntries::new(&entries);
+        let local_apic = |apic_id, flags| LocalApic {
+            processor_id: apic_id,
+            apic_id,
+            flags,
+        };
+        assert_eq!(entries.next(), Some(MadtEntry::LocalApic(local_apic(0, 1))));
+        assert_eq!(entries.next(), Some(MadtEntry::LocalApic(local_apic(1, 0))));
+        assert_eq!(
+            entries.next(),
+            Some(MadtEntry::IoApic(IoApic {
+                id: 2,
+                address: PhysicalAddress::new(0xFEC0_0000),
+                gsi_base: 0,
+            }))
+        );
+
+        let interrupt_override = match entries.next() {
+            Some(MadtEntry::InterruptOverride(interrupt_override)) => interrupt_override,
+            other => panic!("expected an interrupt override, got {:?}", other),
+        };
+        assert_eq!((interrupt_override.source, interrupt_override.gsi), (0, 2));
+        assert!(interrupt_override.is_active_low() && interrupt_override.is_level_triggered());
+
+        assert_eq!(entries.next(), Some(MadtEntry::Unknown(0x7F)));
+        assert_eq!(entries.next(), None);
+        assert_eq!(entries.next(), None);
+    }
+}
@@ -0,0 +1,89 @@
+//! The PCI Express memory mapped configuration table, locating each segment's configuration space.
+
+use super::{find_table_as, read_le, SdtHeader};
+use crate::arch::paging::PhysicalAddress;
+
+/// The size of an entry describing one segment
+const ENTRY_SIZE: usize = 16;
+
+#[derive(Debug)]
+#[repr(C, packed)]
+pub struct Mcfg {
+    header: SdtHeader,
+    _reserved: u64,
+    // the entries are after here
+}
+
+impl Mcfg {
+    pub fn get() -> Option<&'static Mcfg> {
+        // SAFETY: A table with the MCFG signature is always an Mcfg.
+        unsafe { find_table_as(b"MCFG") }
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = McfgEntry> + '_ {
+        // skip the reserved field
+        parse_entries(&self.header.data()[8..])
+    }
+}
+
+/// The configuration space for a range of buses in a PCI segment group
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct McfgEntry {
+    /// The physical address of bus 0's configuration space, even if `start_bus` isn't 0
+    pub base_address: PhysicalAddress,
+    pub segment_group: u16,
+    pub start_bus: u8,
+    pub end_bus: u8,
+}
+
+impl McfgEntry {
+    /// Returns the physical address of a function's 4 KiB configuration space, or `None` if its
+    /// bus isn't covered by this entry
+    pub fn function_address(&self, bus: u8, device: u8, function: u8) -> Option<PhysicalAddress> {
+        if bus < self.start_bus || bus > self.end_bus || device >= 32 || function >= 8 {
+            return None;
+        }
+
+        let offset = (bus as u64) << 20 | (device as u64) << 15 | (function as u64) << 12;
+        Some(PhysicalAddress::new(self.base_address.as_u64() + offset))
+    }
+}
+
+fn parse_entries(entries: &[u8]) -> impl Iterator<Item = McfgEntry> + '_ {
+    entries.chunks_exact(ENTRY_SIZE).map(|entry| McfgEntry {
+        base_address: PhysicalAddress::new(read_le(entry, 0, 8)),
+        segment_group: read_le(entry, 8, 2) as u16,
+        start_bus: entry[10],
+        end_bus: entry[11],
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn function_addresses() {
+        #[rustfmt::skip]
+        let entries = [
+            // segment 0, buses 0-255 at 0xB0000000
+            0x00, 0x00, 0x00, 0xB0, 0, 0, 0, 0, 0, 0, 0, 255, 0, 0, 0, 0,
+            // a truncated entry
+            0x00, 0x00,
+        ];
+
+        let mut entries = parse_entries(&entries);
+        let entry = entries.next().unwrap();
+        assert_eq!(entries.next(), None);
+
+        assert_eq!(
+            entry.function_address(0, 0, 0),
+            Some(PhysicalAddress::new(0xB000_0000))
+        );
+        assert_eq!(
+            entry.function_address(1, 2, 3),
+            Some(PhysicalAddress::new(0xB011_3000))
+        );
+        assert_eq!(entry.function_address(0, 32, 0), None);
+    }
+}
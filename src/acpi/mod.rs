@@ -0,0 +1,271 @@
+//! Discovery of ACPI tables.
+//! The firmware leaves a Root System Description Pointer (RSDP) in memory, which points to the
+//! root table (the RSDT, or the XSDT with 64 bit pointers on ACPI 2.0+), a list of physical
+//! pointers to every other table. The bootloader passes a copy of the RSDP, otherwise we search
+//! the EBDA and BIOS area for it.
+
+pub mod aml;
+pub mod fadt;
+pub mod hpet;
+pub mod madt;
+pub mod mcfg;
+mod rsdp;
+
+use core::mem::size_of;
+use core::{ptr, slice, str};
+use spin::Once;
+
+use crate::arch::paging::{map_physical, EntryFlags, PhysicalAddress};
+use crate::{info, warn};
+pub use rsdp::{Rsdp, Xsdp};
+
+/// Where the BIOS keeps the segment of the Extended BIOS Data Area, which may contain the RSDP
+const EBDA_SEGMENT_POINTER: u64 = 0x40E;
+/// Only the first KiB of the EBDA is searched
+const EBDA_SEARCH_LENGTH: usize = 1024;
+
+/// Where the BIOS read-only memory area lives, which may contain the RSDP
+const BIOS_AREA_START: u64 = 0xE_0000;
+const BIOS_AREA_END: u64 = 0x10_0000;
+
+/// The most tables kept from the root table
+const MAX_TABLES: usize = 32;
+
+/// Every valid table the root table lists, each mapped once by `init`
+static TABLES: Once<[Option<&'static SdtHeader>; MAX_TABLES]> = Once::new();
+
+/// The DSDT, mapped the first time it is used
+static DSDT: Once<Option<&'static SdtHeader>> = Once::new();
+
+/// The table listing every other table
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum RootTable {
+    /// Holds 32 bit pointers
+    Rsdt(PhysicalAddress),
+    /// Holds 64 bit pointers
+    Xsdt(PhysicalAddress),
+}
+
+impl RootTable {
+    fn address(&self) -> PhysicalAddress {
+        match *self {
+            RootTable::Rsdt(addr) | RootTable::Xsdt(addr) => addr,
+        }
+    }
+
+    fn entry_size(&self) -> usize {
+        match self {
+            RootTable::Rsdt(_) => size_of::<u32>(),
+            RootTable::Xsdt(_) => size_of::<u64>(),
+        }
+    }
+}
+
+/// The header common to every System Description Table
+#[derive(Debug, Copy, Clone)]
+#[repr(C, packed)]
+pub struct SdtHeader {
+    signature: [u8; 4],
+    length: u32,
+    revision: u8,
+    checksum: u8,
+    oem_id: [u8; 6],
+    oem_table_id: [u8; 8],
+    oem_revision: u32,
+    creator_id: u32,
+    creator_revision: u32,
+}
+
+impl SdtHeader {
+    pub fn signature(&self) -> &str {
+        str::from_utf8(&self.signature).unwrap_or("????")
+    }
+
+    /// The length of the entire table, including this header
+    pub fn length(&self) -> usize {
+        self.length as usize
+    }
+
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    /// Returns the table as raw bytes, including this header
+    pub fn as_bytes(&self) -> &[u8] {
+        // SAFETY: Every table is mapped in its entirety by `map_table`.
+        unsafe { slice::from_raw_parts(self as *const _ as *const u8, self.length()) }
+    }
+
+    /// Returns the table after this header as raw bytes
+    pub fn data(&self) -> &[u8] {
+        &self.as_bytes()[size_of::<SdtHeader>()..]
+    }
+
+    fn is_valid(&self) -> bool {
+        self.length() >= size_of::<SdtHeader>() && checksum(self.as_bytes()) == 0
+    }
+}
+
+/// An ACPI Generic Address Structure, describing the location of a register
+#[derive(Debug, Copy, Clone)]
+#[repr(C, packed)]
+pub struct GenericAddress {
+    pub address_space: u8,
+    pub bit_width: u8,
+    pub bit_offset: u8,
+    pub access_size: u8,
+    pub address: u64,
+}
+
+/// Sums all bytes, which must be 0 for any valid ACPI structure
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0, |sum: u8, byte| sum.wrapping_add(*byte))
+}
+
+/// Reads a little endian field out of a table, or 0 if the table is too short to have it
+fn read_le(bytes: &[u8], offset: usize, size: usize) -> u64 {
+    let mut value = [0u8; 8];
+    if let Some(field) = bytes.get(offset..offset + size) {
+        value[..size].copy_from_slice(field);
+    }
+    u64::from_le_bytes(value)
+}
+
+/// Finds the root table, from the copy of the RSDP the bootloader passed if there is one, and maps
+/// every table it lists. Must be called after the frame allocator is initialized.
+pub fn init(xsdp: Option<&Xsdp>, rsdp: Option<&Rsdp>) {
+    TABLES.call_once(|| map_tables(xsdp, rsdp));
+
+    if let Some(madt) = madt::Madt::get() {
+        info!(
+            "{} CPUs, {} I/O APICs",
+            madt.processors().count(),
+            madt.io_apics().count()
+        );
+    }
+}
+
+/// Maps every valid table the root table points to
+fn map_tables(
+    xsdp: Option<&Xsdp>,
+    rsdp: Option<&Rsdp>,
+) -> [Option<&'static SdtHeader>; MAX_TABLES] {
+    let mut tables = [None; MAX_TABLES];
+    let (root_table, header) = match find_root_table(xsdp, rsdp) {
+        Some(found) => found,
+        None => {
+            warn!("No ACPI tables found");
+            return tables;
+        }
+    };
+    info!("Found {:x?}", root_table);
+
+    let entry_size = root_table.entry_size();
+    let entries = header.data().chunks_exact(entry_size);
+    if entries.len() > MAX_TABLES {
+        warn!("Only using {} of {} ACPI tables", MAX_TABLES, entries.len());
+    }
+
+    let valid = entries
+        .map(|entry| PhysicalAddress::new(read_le(entry, 0, entry_size)))
+        // SAFETY: Every root table entry is the address of a table.
+        .map(|addr| unsafe { map_table(addr) })
+        .filter(|table| table.is_valid());
+    for (slot, table) in tables.iter_mut().zip(valid) {
+        *slot = Some(table);
+    }
+    tables
+}
+
+fn find_root_table(
+    xsdp: Option<&Xsdp>,
+    rsdp: Option<&Rsdp>,
+) -> Option<(RootTable, &'static SdtHeader)> {
+    let root_table = match (xsdp, rsdp) {
+        (Some(xsdp), _) if xsdp.is_valid() => xsdp.root_table(),
+        (_, Some(rsdp)) if rsdp.is_valid() => rsdp.root_table(),
+        _ => scan_ebda().or_else(scan_bios_area)?,
+    };
+
+    // SAFETY: The RSDP always points to the root table.
+    let table = unsafe { map_table(root_table.address()) };
+    if !table.is_valid() {
+        warn!("{} checksum is invalid!", table.signature());
+        return None;
+    }
+
+    Some((root_table, table))
+}
+
+/// Searches the first KiB of the Extended BIOS Data Area for the RSDP
+fn scan_ebda() -> Option<RootTable> {
+    let pointer = map_physical(
+        PhysicalAddress::new(EBDA_SEGMENT_POINTER),
+        size_of::<u16>(),
+        EntryFlags::PRESENT,
+    );
+    // SAFETY: We just mapped the pointer, which the BIOS always sets up.
+    let segment = unsafe { ptr::read_unaligned(pointer.as_ptr::<u16>()) };
+    if segment == 0 {
+        return None;
+    }
+
+    scan_physical((segment as u64) << 4, EBDA_SEARCH_LENGTH)
+}
+
+/// Searches the BIOS read-only memory area for the RSDP
+fn scan_bios_area() -> Option<RootTable> {
+    let length = (BIOS_AREA_END - BIOS_AREA_START) as usize;
+    scan_physical(BIOS_AREA_START, length)
+}
+
+fn scan_physical(start: u64, length: usize) -> Option<RootTable> {
+    let area = map_physical(PhysicalAddress::new(start), length, EntryFlags::PRESENT);
+    // SAFETY: We just mapped the entire area.
+    let bytes = unsafe { slice::from_raw_parts(area.as_ptr::<u8>(), length) };
+    rsdp::scan(bytes)
+}
+
+/// Maps an entire table into memory, returning its header.
+///
+/// # Safety
+/// `addr` must be the physical address of an ACPI table.
+unsafe fn map_table(addr: PhysicalAddress) -> &'static SdtHeader {
+    let header =
+        &*map_physical(addr, size_of::<SdtHeader>(), EntryFlags::PRESENT).as_ptr::<SdtHeader>();
+    let length = header.length().max(size_of::<SdtHeader>());
+
+    &*map_physical(addr, length, EntryFlags::PRESENT).as_ptr::<SdtHeader>()
+}
+
+/// Finds the first table with the given signature, i.e. `b"HPET"`
+pub fn find_table(signature: &[u8; 4]) -> Option<&'static SdtHeader> {
+    TABLES
+        .call_once(|| map_tables(None, None))
+        .iter()
+        .flatten()
+        .copied()
+        .find(|table| &table.signature == signature)
+}
+
+/// Finds the first table with the given signature, as the struct `T` that starts with its header.
+///
+/// # Safety
+/// Tables with this signature must start with a `T`, as long as they are long enough.
+unsafe fn find_table_as<T>(signature: &[u8; 4]) -> Option<&'static T> {
+    find_table(signature)
+        .filter(|table| table.length() >= size_of::<T>())
+        .map(|table| &*(table as *const SdtHeader as *const T))
+}
+
+/// Returns the DSDT, which the FADT points to rather than the root table
+pub fn dsdt() -> Option<&'static SdtHeader> {
+    *DSDT.call_once(|| {
+        let addr = fadt::Fadt::get()?.dsdt()?;
+        // SAFETY: The FADT always points to the DSDT.
+        let dsdt = unsafe { map_table(addr) };
+        Some(dsdt).filter(|dsdt| &dsdt.signature == b"DSDT" && dsdt.is_valid())
+    })
+}
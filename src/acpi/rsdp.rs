@@ -0,0 +1,156 @@
+use core::mem::size_of;
+use core::slice;
+
+use super::{checksum, RootTable};
+use crate::arch::paging::PhysicalAddress;
+
+const SIGNATURE: &[u8; 8] = b"RSD PTR ";
+
+/// The ACPI 1.0 Root System Description Pointer
+#[derive(Debug, Copy, Clone)]
+#[repr(C, packed)]
+pub struct Rsdp {
+    signature: [u8; 8],
+    checksum: u8,
+    oem_id: [u8; 6],
+    revision: u8,
+    rsdt_address: u32,
+}
+
+impl Rsdp {
+    /// Returns true if the signature and checksum match
+    pub fn is_valid(&self) -> bool {
+        self.signature == *SIGNATURE && checksum(self.as_bytes()) == 0
+    }
+
+    /// 0 for ACPI 1.0, or 2 and up if this is actually an `Xsdp`
+    pub fn revision(&self) -> u8 {
+        self.revision
+    }
+
+    pub fn rsdt_address(&self) -> PhysicalAddress {
+        PhysicalAddress::new(self.rsdt_address as u64)
+    }
+
+    pub(super) fn root_table(&self) -> RootTable {
+        RootTable::Rsdt(self.rsdt_address())
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        // SAFETY: The RSDP is plain bytes.
+        unsafe { slice::from_raw_parts(self as *const _ as *const u8, size_of::<Rsdp>()) }
+    }
+}
+
+/// The ACPI 2.0+ Root System Description Pointer, which adds the 64 bit XSDT
+#[derive(Debug, Copy, Clone)]
+#[repr(C, packed)]
+pub struct Xsdp {
+    rsdp: Rsdp,
+    length: u32,
+    xsdt_address: u64,
+    extended_checksum: u8,
+    _reserved: [u8; 3],
+}
+
+impl Xsdp {
+    /// Returns true if the signature and both checksums match
+    pub fn is_valid(&self) -> bool {
+        self.rsdp.is_valid() && self.rsdp.revision() >= 2 && checksum(self.as_bytes()) == 0
+    }
+
+    pub fn rsdp(&self) -> &Rsdp {
+        &self.rsdp
+    }
+
+    pub fn xsdt_address(&self) -> PhysicalAddress {
+        PhysicalAddress::new(self.xsdt_address)
+    }
+
+    /// Prefers the XSDT, but firmware may leave it out
+    pub(super) fn root_table(&self) -> RootTable {
+        match self.xsdt_address {
+            0 => self.rsdp.root_table(),
+            _ => RootTable::Xsdt(self.xsdt_address()),
+        }
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        // the checksum only covers `length` bytes, which newer revisions may grow past the
+        // fields we know of
+        let length = (self.length as usize).min(size_of::<Xsdp>());
+        // SAFETY: The XSDP is plain bytes.
+        unsafe { slice::from_raw_parts(self as *const _ as *const u8, length) }
+    }
+}
+
+/// Searches `bytes` for an RSDP, which is always on a 16 byte boundary
+pub(super) fn scan(bytes: &[u8]) -> Option<RootTable> {
+    (0..bytes.len().saturating_sub(size_of::<Rsdp>() - 1))
+        .step_by(16)
+        .filter(|offset| &bytes[*offset..*offset + SIGNATURE.len()] == SIGNATURE)
+        .find_map(|offset| {
+            // SAFETY: There is at least an RSDP's worth of bytes at the offset, and it has no
+            //         alignment requirement.
+            let rsdp = unsafe { &*(bytes[offset..].as_ptr() as *const Rsdp) };
+            if !rsdp.is_valid() {
+                return None;
+            }
+            if rsdp.revision() < 2 || bytes.len() - offset < size_of::<Xsdp>() {
+                return Some(rsdp.root_table());
+            }
+
+            // SAFETY: An RSDP with revision 2 is the start of an XSDP, and the bytes cover all
+            //         of it.
+            let xsdp = unsafe { &*(bytes[offset..].as_ptr() as *const Xsdp) };
+            if xsdp.is_valid() {
+                Some(xsdp.root_table())
+            } else {
+                Some(rsdp.root_table())
+            }
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Builds an RSDP (or XSDP for revision 2) with correct checksums
+    fn rsdp(revision: u8, rsdt: u32, xsdt: u64) -> [u8; 36] {
+        let mut bytes = [0u8; 36];
+        bytes[..8].copy_from_slice(SIGNATURE);
+        bytes[9..15].copy_from_slice(b"JUNTOS");
+        bytes[15] = revision;
+        bytes[16..20].copy_from_slice(&rsdt.to_le_bytes());
+        bytes[8] = 0u8.wrapping_sub(checksum(&bytes[..20]));
+        if revision >= 2 {
+            bytes[20..24].copy_from_slice(&36u32.to_le_bytes());
+            bytes[24..32].copy_from_slice(&xsdt.to_le_bytes());
+            bytes[32] = 0u8.wrapping_sub(checksum(&bytes));
+        }
+        bytes
+    }
+
+    #[test]
+    fn scan_for_rsdp() {
+        let mut area = [0u8; 128];
+        area[48..84].copy_from_slice(&rsdp(0, 0x1234, 0));
+        assert_eq!(
+            scan(&area),
+            Some(RootTable::Rsdt(PhysicalAddress::new(0x1234)))
+        );
+
+        area[48..84].copy_from_slice(&rsdp(2, 0x1234, 0x5678));
+        assert_eq!(
+            scan(&area),
+            Some(RootTable::Xsdt(PhysicalAddress::new(0x5678)))
+        );
+
+        // a bad checksum, or not being on a 16 byte boundary, is skipped
+        area[48 + 8] ^= 1;
+        assert_eq!(scan(&area), None);
+        let mut area = [0u8; 128];
+        area[40..76].copy_from_slice(&rsdp(0, 0x1234, 0));
+        assert_eq!(scan(&area), None);
+    }
+}
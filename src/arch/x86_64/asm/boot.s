@@ -266,6 +266,9 @@ long_mode_start:
     ; put boot info into third main param
     mov rdx, boot_info 
 
+    ; clear the frame pointer, so backtraces know where the stack ends
+    xor rbp, rbp
+
     ; must place address in register for near-aboslute call
     mov rax, kernel_main
     call rax
@@ -0,0 +1,33 @@
+//! The Bochs/QEMU debug console: a fake port (0xE9) that emulators write straight to a log file
+//! or their terminal, i.e. with `qemu -debugcon stdio`.
+
+use crate::arch::instructions::port::Port;
+use crate::klog::Sink;
+
+const DEBUGCON_PORT: u16 = 0xE9;
+
+/// The debug console, as a log sink
+pub static DEBUGCON: Debugcon = Debugcon;
+
+/// Returns true if we are running in an emulator with the debug console enabled, which reads
+/// back the port number from the port.
+pub fn is_present() -> bool {
+    // SAFETY: Port 0xE9 is unused on real hardware.
+    unsafe { Port::<u8>::new(DEBUGCON_PORT).read() == DEBUGCON_PORT as u8 }
+}
+
+pub struct Debugcon;
+
+impl Sink for Debugcon {
+    fn name(&self) -> &'static str {
+        "debugcon"
+    }
+
+    fn write_str(&self, s: &str) {
+        let mut port = Port::<u8>::new(DEBUGCON_PORT);
+        for byte in s.bytes() {
+            // SAFETY: Writing to the debug console has no side effects.
+            unsafe { port.write(byte) };
+        }
+    }
+}
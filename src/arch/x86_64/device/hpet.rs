@@ -0,0 +1,180 @@
+//! Driver for the High Precision Event Timer, found through the ACPI HPET table.
+//! Only timer 0 is used for events. As we still route IRQs through the PIC, it is delivered
+//! through the legacy replacement route, which replaces the PIT on IRQ0.
+
+use core::ptr;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use crate::acpi::hpet::HpetTable;
+use crate::arch::interrupt::irq;
+use crate::arch::paging::{map_physical, MMIO_FLAGS};
+use crate::time::clock::{ClockEvent, ClockSource, EventFeatures};
+use crate::time::{timer, NANOS_PER_SEC};
+
+const REGISTERS_SIZE: usize = 0x400;
+
+/// General register offsets
+const CAPABILITIES: usize = 0x000;
+const CONFIGURATION: usize = 0x010;
+const MAIN_COUNTER: usize = 0x0F0;
+
+/// Timer 0 register offsets
+const TIMER0_CONFIGURATION: usize = 0x100;
+const TIMER0_COMPARATOR: usize = 0x108;
+
+/// Bits in the capabilities register
+const LEGACY_REPLACEMENT_CAPABLE: u64 = 1 << 15;
+
+/// Bits in the configuration register
+const ENABLE: u64 = 1 << 0;
+const LEGACY_REPLACEMENT: u64 = 1 << 1;
+
+/// Bits in a timer's configuration register
+const TIMER_INTERRUPT_ENABLE: u64 = 1 << 2;
+const TIMER_PERIODIC: u64 = 1 << 3;
+const TIMER_PERIODIC_CAPABLE: u64 = 1 << 4;
+const TIMER_VALUE_SET: u64 = 1 << 6;
+
+/// The HPET may not tick slower than once every 100ns
+const MAX_PERIOD_FS: u64 = 100_000_000;
+const FEMTOS_PER_NANO: u64 = 1_000_000;
+const FEMTOS_PER_SEC: u64 = NANOS_PER_SEC * FEMTOS_PER_NANO;
+
+const HPET_IRQ: u8 = 0;
+
+/// The virtual address of the HPET's registers, or 0 if there is no HPET
+static BASE: AtomicU64 = AtomicU64::new(0);
+
+/// The length of one counter tick, in femtoseconds
+static PERIOD_FS: AtomicU64 = AtomicU64::new(0);
+
+/// The HPET, as both a clock source and a clock event device
+pub static HPET: Hpet = Hpet;
+
+/// Finds and starts the HPET's main counter. Returns false if there is no usable HPET.
+/// Must be called after the frame allocator is initialized, as this maps its registers.
+pub fn init() -> bool {
+    let table = match HpetTable::get() {
+        Some(table) => table,
+        None => return false,
+    };
+
+    let base = map_physical(table.base_address(), REGISTERS_SIZE, MMIO_FLAGS);
+    BASE.store(base.as_u64(), Ordering::SeqCst);
+
+    let period = read(CAPABILITIES) >> 32;
+    if period == 0 || period > MAX_PERIOD_FS {
+        BASE.store(0, Ordering::SeqCst);
+        return false;
+    }
+    PERIOD_FS.store(period, Ordering::SeqCst);
+
+    // restart the main counter from 0 with every timer disabled
+    write(
+        CONFIGURATION,
+        read(CONFIGURATION) & !(ENABLE | LEGACY_REPLACEMENT),
+    );
+    write(TIMER0_CONFIGURATION, 0);
+    write(MAIN_COUNTER, 0);
+    write(CONFIGURATION, read(CONFIGURATION) | ENABLE);
+
+    true
+}
+
+/// Returns true if the HPET can raise interrupts without an I/O APIC
+pub fn can_raise_events() -> bool {
+    read(CAPABILITIES) & LEGACY_REPLACEMENT_CAPABLE != 0
+}
+
+fn read(offset: usize) -> u64 {
+    let base = BASE.load(Ordering::Relaxed);
+    assert!(base != 0, "HPET used before it was initialized!");
+
+    // SAFETY: The registers are mapped by `init`, and are always 8-byte aligned.
+    unsafe { ptr::read_volatile((base as usize + offset) as *const u64) }
+}
+
+fn write(offset: usize, value: u64) {
+    let base = BASE.load(Ordering::Relaxed);
+    assert!(base != 0, "HPET used before it was initialized!");
+
+    // SAFETY: The registers are mapped by `init`, and are always 8-byte aligned.
+    unsafe { ptr::write_volatile((base as usize + offset) as *mut u64, value) }
+}
+
+fn ns_to_ticks(ns: u64) -> u64 {
+    let ticks = ns as u128 * FEMTOS_PER_NANO as u128 / PERIOD_FS.load(Ordering::Relaxed) as u128;
+    (ticks as u64).max(1)
+}
+
+fn interrupt() {
+    timer::handle_event();
+}
+
+pub struct Hpet;
+
+impl ClockSource for Hpet {
+    fn name(&self) -> &'static str {
+        "hpet"
+    }
+
+    fn rating(&self) -> u32 {
+        250
+    }
+
+    fn frequency(&self) -> u64 {
+        FEMTOS_PER_SEC / PERIOD_FS.load(Ordering::Relaxed)
+    }
+
+    fn read(&self) -> u64 {
+        read(MAIN_COUNTER)
+    }
+}
+
+impl ClockEvent for Hpet {
+    fn name(&self) -> &'static str {
+        "hpet"
+    }
+
+    fn rating(&self) -> u32 {
+        200
+    }
+
+    fn features(&self) -> EventFeatures {
+        if read(TIMER0_CONFIGURATION) & TIMER_PERIODIC_CAPABLE != 0 {
+            EventFeatures::ONE_SHOT | EventFeatures::PERIODIC
+        } else {
+            EventFeatures::ONE_SHOT
+        }
+    }
+
+    fn set_periodic(&self, period_ns: u64) {
+        let period = ns_to_ticks(period_ns);
+
+        // with VALUE_SET, the first write sets the comparator and the second the period
+        write(
+            TIMER0_CONFIGURATION,
+            TIMER_INTERRUPT_ENABLE | TIMER_PERIODIC | TIMER_VALUE_SET,
+        );
+        write(TIMER0_COMPARATOR, read(MAIN_COUNTER) + period);
+        write(TIMER0_COMPARATOR, period);
+    }
+
+    fn set_oneshot(&self, delta_ns: u64) -> Result<(), &'static str> {
+        write(TIMER0_CONFIGURATION, TIMER_INTERRUPT_ENABLE);
+        write(
+            TIMER0_COMPARATOR,
+            read(MAIN_COUNTER) + ns_to_ticks(delta_ns),
+        );
+        Ok(())
+    }
+
+    fn enable(&self) {
+        irq::register(HPET_IRQ, interrupt);
+        write(CONFIGURATION, read(CONFIGURATION) | LEGACY_REPLACEMENT);
+    }
+
+    fn stop(&self) {
+        write(TIMER0_CONFIGURATION, 0);
+    }
+}
@@ -0,0 +1,32 @@
+//! The 8042 PS/2 keyboard controller, which (for historical reasons) can also reset the CPU.
+
+use crate::arch::instructions::port::{io_wait, Port};
+
+const COMMAND_PORT: u16 = 0x64;
+
+/// Bits in the status register, read from the command port
+const INPUT_BUFFER_FULL: u8 = 1 << 1;
+
+const PULSE_RESET_LINE: u8 = 0xFE;
+
+/// How many times to check that the controller is ready for a command before giving up, as the
+/// controller may not exist
+const MAX_POLLS: usize = 100_000;
+
+/// Asks the controller to pulse the CPU's reset line. This returns if there is no controller, or
+/// it ignored us.
+pub fn pulse_reset() {
+    let mut port = Port::<u8>::new(COMMAND_PORT);
+
+    // SAFETY: Reading the status register has no side effects, and resetting the CPU can't
+    //         violate memory safety, as nothing runs afterwards.
+    unsafe {
+        for _ in 0..MAX_POLLS {
+            if port.read() & INPUT_BUFFER_FULL == 0 {
+                port.write(PULSE_RESET_LINE);
+                return;
+            }
+            io_wait();
+        }
+    }
+}
@@ -0,0 +1,9 @@
+//! Drivers for devices that are part of the x86 PC platform itself
+
+pub mod debugcon;
+pub mod hpet;
+pub mod i8042;
+pub mod pit;
+pub mod rtc;
+pub mod serial;
+pub mod tsc;
@@ -0,0 +1,205 @@
+//! Driver for the 8253/8254 Programmable Interval Timer.
+//! Channel 0 is used as the periodic system tick on IRQ0, and channel 2 (whose output can be
+//! polled through the PC speaker port) is used to calibrate the TSC.
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::arch::instructions::port::Port;
+use crate::arch::instructions::rdtsc;
+use crate::arch::interrupt::irq;
+use crate::sync::IrqSafeMutex;
+use crate::time::clock::{ClockEvent, ClockSource, EventFeatures};
+use crate::time::{timer, NANOS_PER_SEC};
+
+/// The frequency of the PIT's input clock, in Hz
+pub const BASE_FREQUENCY: u64 = 1_193_182;
+
+const PIT_IRQ: u8 = 0;
+
+/// Select channel 0, access lobyte/hibyte, mode 2 (rate generator), binary
+const CHANNEL0_RATE_GENERATOR: u8 = 0b00_11_010_0;
+
+/// Select channel 2, access lobyte/hibyte, mode 0 (interrupt on terminal count), binary
+const CHANNEL2_ONE_SHOT: u8 = 0b10_11_000_0;
+
+/// Bits of the PC speaker port (0x61) used to drive channel 2
+const SPEAKER_GATE: u8 = 1 << 0;
+const SPEAKER_ENABLE: u8 = 1 << 1;
+const CHANNEL2_OUTPUT: u8 = 1 << 5;
+
+static PORTS: IrqSafeMutex<PitPorts> = IrqSafeMutex::new(PitPorts {
+    channel0: Port::new(0x40),
+    channel2: Port::new(0x42),
+    command: Port::new(0x43),
+    speaker: Port::new(0x61),
+});
+
+/// Number of IRQ0 ticks since the PIT was initialized
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Number of input clock cycles that have elapsed over all ticks so far. Tracked separately
+/// from TICKS, as the divisor may change while running.
+static ELAPSED_CYCLES: AtomicU64 = AtomicU64::new(0);
+
+/// The reload value channel 0 is programmed with
+static DIVISOR: AtomicU64 = AtomicU64::new(0);
+
+/// Whether the PIT is the current clock source, or should call into the timer subsystem on
+/// every tick as the current clock event device.
+static SOURCE_IN_USE: AtomicBool = AtomicBool::new(false);
+static EVENTS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The PIT, as both a clock source and a (periodic only) clock event device
+pub static PIT: Pit = Pit;
+
+struct PitPorts {
+    channel0: Port<u8>,
+    channel2: Port<u8>,
+    command: Port<u8>,
+    speaker: Port<u8>,
+}
+
+/// Programs channel 0 to fire IRQ0 at (approximately) `frequency` Hz, and starts counting ticks.
+pub fn init(frequency: u32) {
+    assert!(frequency > 0, "PIT frequency must be non-zero!");
+
+    set_divisor(BASE_FREQUENCY / frequency as u64);
+    irq::register(PIT_IRQ, tick);
+}
+
+fn set_divisor(divisor: u64) {
+    // a reload value of 0 is treated as 65536 by the PIT
+    let divisor = divisor.max(1).min(65536);
+    DIVISOR.store(divisor, Ordering::SeqCst);
+
+    let mut ports = PORTS.lock();
+
+    // SAFETY: Reprogramming channel 0 only changes how often IRQ0 fires.
+    unsafe {
+        ports.command.write(CHANNEL0_RATE_GENERATOR);
+        ports.channel0.write(divisor as u8);
+        ports.channel0.write((divisor >> 8) as u8);
+    }
+}
+
+fn tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+    ELAPSED_CYCLES.fetch_add(DIVISOR.load(Ordering::Relaxed), Ordering::Relaxed);
+
+    if EVENTS_ENABLED.load(Ordering::Relaxed) {
+        timer::handle_event();
+    }
+}
+
+/// Returns the number of ticks since `init` was called
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Returns the number of nanoseconds since `init` was called, with a resolution of one tick.
+pub fn elapsed_ns() -> u64 {
+    let cycles = ELAPSED_CYCLES.load(Ordering::Relaxed) as u128;
+
+    // computed from the raw cycle count every time, so rounding errors don't accumulate
+    (cycles * NANOS_PER_SEC as u128 / BASE_FREQUENCY as u128) as u64
+}
+
+/// Returns the length of a single tick, in nanoseconds
+pub fn tick_period_ns() -> u64 {
+    DIVISOR.load(Ordering::Relaxed) * NANOS_PER_SEC / BASE_FREQUENCY
+}
+
+/// Measures the frequency of the TSC (in Hz) by timing a `ms` millisecond one-shot countdown on
+/// channel 2. This polls the channel output, so it works with interrupts disabled.
+pub fn calibrate_tsc(ms: u64) -> u64 {
+    let count = BASE_FREQUENCY * ms / 1000;
+    assert!(
+        count > 0 && count <= 0xFFFF,
+        "TSC calibration period is out of range for the PIT!"
+    );
+
+    let mut ports = PORTS.lock();
+
+    // SAFETY: Channel 2 is only connected to the PC speaker, which we keep disabled.
+    unsafe {
+        // gate channel 2 off while programming it, and make sure the speaker stays quiet
+        let speaker = ports.speaker.read() & !(SPEAKER_ENABLE | SPEAKER_GATE);
+        ports.speaker.write(speaker);
+
+        ports.command.write(CHANNEL2_ONE_SHOT);
+        ports.channel2.write(count as u8);
+        ports.channel2.write((count >> 8) as u8);
+
+        // raising the gate starts the countdown
+        ports.speaker.write(speaker | SPEAKER_GATE);
+        let start = rdtsc();
+
+        while ports.speaker.read() & CHANNEL2_OUTPUT == 0 {
+            core::hint::spin_loop();
+        }
+
+        let end = rdtsc();
+        ports.speaker.write(speaker);
+
+        (end - start) * 1000 / ms
+    }
+}
+
+pub struct Pit;
+
+impl ClockSource for Pit {
+    fn name(&self) -> &'static str {
+        "pit"
+    }
+
+    fn rating(&self) -> u32 {
+        100
+    }
+
+    fn frequency(&self) -> u64 {
+        BASE_FREQUENCY
+    }
+
+    fn read(&self) -> u64 {
+        ELAPSED_CYCLES.load(Ordering::Relaxed)
+    }
+
+    fn enable(&self) {
+        SOURCE_IN_USE.store(true, Ordering::SeqCst);
+    }
+}
+
+impl ClockEvent for Pit {
+    fn name(&self) -> &'static str {
+        "pit"
+    }
+
+    fn rating(&self) -> u32 {
+        100
+    }
+
+    fn features(&self) -> EventFeatures {
+        EventFeatures::PERIODIC
+    }
+
+    fn set_periodic(&self, period_ns: u64) {
+        set_divisor(period_ns * BASE_FREQUENCY / NANOS_PER_SEC);
+    }
+
+    fn set_oneshot(&self, _delta_ns: u64) -> Result<(), &'static str> {
+        Err("The PIT is only used as a periodic clock event device")
+    }
+
+    fn enable(&self) {
+        EVENTS_ENABLED.store(true, Ordering::SeqCst);
+    }
+
+    fn stop(&self) {
+        EVENTS_ENABLED.store(false, Ordering::SeqCst);
+
+        // the tick still needs to run if we are keeping time with it
+        if !SOURCE_IN_USE.load(Ordering::SeqCst) {
+            irq::unregister(PIT_IRQ);
+        }
+    }
+}
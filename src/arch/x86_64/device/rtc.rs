@@ -0,0 +1,307 @@
+//! Driver for the CMOS real-time clock.
+//! The RTC keeps the wall-clock time while the machine is off, and can also raise a periodic
+//! interrupt on IRQ8, which makes it usable as a (low resolution) clock event device.
+
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::arch::instructions::port::Port;
+use crate::arch::interrupt::irq;
+use crate::sync::IrqSafeMutex;
+use crate::time::clock::{ClockEvent, EventFeatures};
+use crate::time::datetime::DateTime;
+use crate::time::{timer, NANOS_PER_SEC};
+
+/// CMOS register indices
+const SECONDS: u8 = 0x00;
+const MINUTES: u8 = 0x02;
+const HOURS: u8 = 0x04;
+const DAY: u8 = 0x07;
+const MONTH: u8 = 0x08;
+const YEAR: u8 = 0x09;
+const STATUS_A: u8 = 0x0A;
+const STATUS_B: u8 = 0x0B;
+const STATUS_C: u8 = 0x0C;
+
+/// Set in status register A while the RTC is updating its registers
+const UPDATE_IN_PROGRESS: u8 = 1 << 7;
+
+/// Bits in status register B
+const HOURS_24: u8 = 1 << 1;
+const BINARY_MODE: u8 = 1 << 2;
+const PERIODIC_INTERRUPT: u8 = 1 << 6;
+
+/// Set in the hour register for PM times in 12 hour mode
+const HOUR_PM: u8 = 1 << 7;
+
+/// The periodic interrupt runs at `BASE_FREQUENCY >> (rate - 1)`, for rates 3 to 15
+const BASE_FREQUENCY: u64 = 32768;
+const FASTEST_RATE: u8 = 3;
+const SLOWEST_RATE: u8 = 15;
+
+const RTC_IRQ: u8 = 8;
+
+static PORTS: IrqSafeMutex<CmosPorts> = IrqSafeMutex::new(CmosPorts {
+    index: Port::new(0x70),
+    data: Port::new(0x71),
+});
+
+/// The CMOS register holding the century, if the firmware told us where it is (through the
+/// ACPI FADT). Without it, we assume years are in 2000-2099.
+static CENTURY_REGISTER: AtomicU8 = AtomicU8::new(0);
+
+/// The RTC's periodic interrupt, as a clock event device
+pub static RTC: Rtc = Rtc;
+
+struct CmosPorts {
+    index: Port<u8>,
+    data: Port<u8>,
+}
+
+impl CmosPorts {
+    fn read(&mut self, register: u8) -> u8 {
+        // SAFETY: Reading CMOS registers has no side effects, apart from status register C
+        //         acknowledging interrupts, which only `interrupt` reads.
+        unsafe {
+            self.index.write(register);
+            self.data.read()
+        }
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        // SAFETY: We only write the status registers, which cannot violate memory safety.
+        unsafe {
+            self.index.write(register);
+            self.data.write(value);
+        }
+    }
+}
+
+/// The raw values of the time registers, as stored by the RTC
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct RawTime {
+    second: u8,
+    minute: u8,
+    hour: u8,
+    day: u8,
+    month: u8,
+    year: u8,
+    century: u8,
+}
+
+impl RawTime {
+    fn read(ports: &mut CmosPorts) -> RawTime {
+        let century_register = CENTURY_REGISTER.load(Ordering::Relaxed);
+
+        RawTime {
+            second: ports.read(SECONDS),
+            minute: ports.read(MINUTES),
+            hour: ports.read(HOURS),
+            day: ports.read(DAY),
+            month: ports.read(MONTH),
+            year: ports.read(YEAR),
+            century: match century_register {
+                0 => 0,
+                register => ports.read(register),
+            },
+        }
+    }
+
+    /// Converts the raw register values into a date and time, given the format in status
+    /// register B.
+    fn decode(self, status_b: u8) -> DateTime {
+        let binary = status_b & BINARY_MODE != 0;
+        let convert = |value: u8| {
+            if binary {
+                value
+            } else {
+                from_bcd(value)
+            }
+        };
+
+        // in 12 hour mode, the PM flag is stored in the top bit regardless of the number format
+        let pm = self.hour & HOUR_PM != 0;
+        let mut hour = convert(self.hour & !HOUR_PM);
+        if status_b & HOURS_24 == 0 {
+            hour = hour % 12 + if pm { 12 } else { 0 };
+        }
+
+        let century = match self.century {
+            0 => 20,
+            century => convert(century) as u16,
+        };
+
+        DateTime {
+            year: century * 100 + convert(self.year) as u16,
+            month: convert(self.month),
+            day: convert(self.day),
+            hour,
+            minute: convert(self.minute),
+            second: convert(self.second),
+        }
+    }
+}
+
+fn from_bcd(value: u8) -> u8 {
+    (value >> 4) * 10 + (value & 0x0F)
+}
+
+/// Tells the driver which CMOS register holds the century
+pub fn set_century_register(register: u8) {
+    CENTURY_REGISTER.store(register, Ordering::SeqCst);
+}
+
+/// Reads the current date and time from the RTC. The RTC's registers are not consistent while
+/// it is updating them, so this waits for any update to finish, and retries until two reads in
+/// a row agree.
+pub fn read_time() -> DateTime {
+    let mut ports = PORTS.lock();
+
+    let mut read_consistent = || {
+        while ports.read(STATUS_A) & UPDATE_IN_PROGRESS != 0 {
+            core::hint::spin_loop();
+        }
+        RawTime::read(&mut ports)
+    };
+
+    let mut previous = read_consistent();
+    loop {
+        let current = read_consistent();
+        if current == previous {
+            break;
+        }
+        previous = current;
+    }
+
+    let status_b = ports.read(STATUS_B);
+    previous.decode(status_b)
+}
+
+/// Returns the periodic interrupt rate whose period is closest to (but not over) `period_ns`
+fn rate_for_period(period_ns: u64) -> u8 {
+    (FASTEST_RATE..=SLOWEST_RATE)
+        .rev()
+        .find(|rate| period_of_rate(*rate) <= period_ns)
+        .unwrap_or(FASTEST_RATE)
+}
+
+fn period_of_rate(rate: u8) -> u64 {
+    NANOS_PER_SEC / (BASE_FREQUENCY >> (rate - 1))
+}
+
+fn interrupt() {
+    // the RTC will not raise another interrupt until status register C is read
+    PORTS.lock().read(STATUS_C);
+    timer::handle_event();
+}
+
+pub struct Rtc;
+
+impl ClockEvent for Rtc {
+    fn name(&self) -> &'static str {
+        "rtc"
+    }
+
+    fn rating(&self) -> u32 {
+        50
+    }
+
+    fn features(&self) -> EventFeatures {
+        EventFeatures::PERIODIC
+    }
+
+    fn set_periodic(&self, period_ns: u64) {
+        let rate = rate_for_period(period_ns);
+
+        let mut ports = PORTS.lock();
+        let status_a = ports.read(STATUS_A);
+        ports.write(STATUS_A, (status_a & 0xF0) | rate);
+        let status_b = ports.read(STATUS_B);
+        ports.write(STATUS_B, status_b | PERIODIC_INTERRUPT);
+
+        // clear any interrupt that is already pending
+        ports.read(STATUS_C);
+    }
+
+    fn set_oneshot(&self, _delta_ns: u64) -> Result<(), &'static str> {
+        Err("The RTC can only raise periodic interrupts")
+    }
+
+    fn enable(&self) {
+        irq::register(RTC_IRQ, interrupt);
+    }
+
+    fn stop(&self) {
+        irq::unregister(RTC_IRQ);
+
+        let mut ports = PORTS.lock();
+        let status_b = ports.read(STATUS_B);
+        ports.write(STATUS_B, status_b & !PERIODIC_INTERRUPT);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn raw(hour: u8) -> RawTime {
+        RawTime {
+            second: 0x45,
+            minute: 0x30,
+            hour,
+            day: 0x18,
+            month: 0x10,
+            year: 0x26,
+            century: 0,
+        }
+    }
+
+    #[test]
+    fn bcd_24_hour() {
+        let time = raw(0x23).decode(HOURS_24);
+        assert_eq!(
+            time,
+            DateTime {
+                year: 2026,
+                month: 10,
+                day: 18,
+                hour: 23,
+                minute: 30,
+                second: 45,
+            }
+        );
+    }
+
+    #[test]
+    fn bcd_12_hour() {
+        assert_eq!(raw(0x12).decode(0).hour, 0);
+        assert_eq!(raw(0x01).decode(0).hour, 1);
+        assert_eq!(raw(HOUR_PM | 0x12).decode(0).hour, 12);
+        assert_eq!(raw(HOUR_PM | 0x11).decode(0).hour, 23);
+    }
+
+    #[test]
+    fn binary() {
+        let time = RawTime {
+            second: 45,
+            minute: 30,
+            hour: HOUR_PM | 7,
+            day: 18,
+            month: 10,
+            year: 26,
+            century: 20,
+        };
+        let time = time.decode(BINARY_MODE);
+
+        assert_eq!(time.year, 2026);
+        assert_eq!(time.hour, 19);
+        assert_eq!(time.second, 45);
+    }
+
+    #[test]
+    fn periodic_rates() {
+        assert_eq!(period_of_rate(6), 976_562);
+        assert_eq!(rate_for_period(1_000_000), 6);
+        assert_eq!(rate_for_period(1), FASTEST_RATE);
+        assert_eq!(rate_for_period(NANOS_PER_SEC), SLOWEST_RATE);
+    }
+}
@@ -0,0 +1,288 @@
+//! Driver for the 16550 UART behind the COM1 serial port.
+//! Transmitting is polled, so output works before interrupts are set up (and from the panic
+//! handler). Received bytes are buffered by the IRQ4 handler until they are read.
+
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::arch::instructions::port::Port;
+use crate::arch::interrupt::irq;
+use crate::klog::Sink;
+use crate::sync::IrqSafeMutex;
+
+/// The I/O port base of COM1
+const COM1_BASE: u16 = 0x3F8;
+const COM1_IRQ: u8 = 4;
+
+/// The I/O port base of COM2, which is left for the GDB stub
+const COM2_BASE: u16 = 0x2F8;
+
+/// The frequency of the UART's clock divided by 16, i.e. the fastest possible baud rate
+pub const MAX_BAUD_RATE: u32 = 115_200;
+
+/// Bits in the interrupt enable register
+const INTERRUPT_DATA_AVAILABLE: u8 = 1 << 0;
+
+/// Enable and clear both FIFOs, with a 14 byte receive interrupt threshold
+const FIFO_ENABLE_CLEAR_14: u8 = 0xC7;
+
+/// Bits in the line control register
+const LINE_8N1: u8 = 0x03;
+const DIVISOR_LATCH_ACCESS: u8 = 1 << 7;
+
+/// Bits in the modem control register
+const MODEM_DTR_RTS: u8 = 0x03;
+const MODEM_OUT2: u8 = 1 << 3;
+const MODEM_LOOPBACK: u8 = 1 << 4;
+
+/// Bits in the line status register
+const LINE_DATA_READY: u8 = 1 << 0;
+const LINE_TRANSMIT_EMPTY: u8 = 1 << 5;
+
+/// The byte sent through the loopback test in `init`
+const LOOPBACK_TEST_BYTE: u8 = 0xAE;
+
+const RX_BUFFER_SIZE: usize = 256;
+
+pub static COM1: IrqSafeMutex<SerialPort> = IrqSafeMutex::new(SerialPort::new(COM1_BASE));
+
+/// Only used through `send` and `receive`, as the `Write` impl follows whether COM1 is present
+pub static COM2: IrqSafeMutex<SerialPort> = IrqSafeMutex::new(SerialPort::new(COM2_BASE));
+
+/// COM1, as a log sink
+pub static SERIAL_SINK: SerialSink = SerialSink;
+
+/// Set once COM1 has passed its loopback test. Until then, output to it is dropped, so we
+/// don't spin forever waiting on a UART that isn't there.
+static PRESENT: AtomicBool = AtomicBool::new(false);
+
+pub struct SerialPort {
+    /// Also the low byte of the baud rate divisor while DLAB is set
+    data: Port<u8>,
+    /// Also the high byte of the baud rate divisor while DLAB is set
+    interrupt_enable: Port<u8>,
+    fifo_control: Port<u8>,
+    line_control: Port<u8>,
+    modem_control: Port<u8>,
+    line_status: Port<u8>,
+    rx_buffer: RxBuffer,
+}
+
+impl SerialPort {
+    pub const fn new(base: u16) -> SerialPort {
+        SerialPort {
+            data: Port::new(base),
+            interrupt_enable: Port::new(base + 1),
+            fifo_control: Port::new(base + 2),
+            line_control: Port::new(base + 3),
+            modem_control: Port::new(base + 4),
+            line_status: Port::new(base + 5),
+            rx_buffer: RxBuffer::new(),
+        }
+    }
+
+    /// Programs the UART for 8N1 at `baud_rate`, with FIFOs enabled. Returns false if the UART
+    /// did not pass a loopback test, i.e. there is probably no UART there.
+    pub fn init(&mut self, baud_rate: u32) -> bool {
+        let divisor = (MAX_BAUD_RATE / baud_rate).max(1).min(0xFFFF) as u16;
+
+        // SAFETY: These ports only configure the UART.
+        unsafe {
+            self.interrupt_enable.write(0);
+
+            self.line_control.write(DIVISOR_LATCH_ACCESS);
+            self.data.write(divisor as u8);
+            self.interrupt_enable.write((divisor >> 8) as u8);
+            self.line_control.write(LINE_8N1);
+
+            self.fifo_control.write(FIFO_ENABLE_CLEAR_14);
+
+            // anything sent in loopback mode should be received straight back
+            self.modem_control
+                .write(MODEM_DTR_RTS | MODEM_OUT2 | MODEM_LOOPBACK);
+            self.data.write(LOOPBACK_TEST_BYTE);
+            if self.data.read() != LOOPBACK_TEST_BYTE {
+                return false;
+            }
+
+            self.modem_control.write(MODEM_DTR_RTS | MODEM_OUT2);
+        }
+
+        true
+    }
+
+    /// Sends a byte, waiting for the transmit buffer to empty first
+    pub fn send(&mut self, byte: u8) {
+        // SAFETY: Reading the line status and writing data have no side effects beyond sending.
+        unsafe {
+            while self.line_status.read() & LINE_TRANSMIT_EMPTY == 0 {
+                core::hint::spin_loop();
+            }
+            self.data.write(byte);
+        }
+    }
+
+    /// Returns the next received byte, if there is one
+    pub fn receive(&mut self) -> Option<u8> {
+        self.rx_buffer.pop().or_else(|| self.poll_receive())
+    }
+
+    fn poll_receive(&mut self) -> Option<u8> {
+        // SAFETY: Reading data only consumes the received byte.
+        unsafe {
+            if self.line_status.read() & LINE_DATA_READY != 0 {
+                Some(self.data.read())
+            } else {
+                None
+            }
+        }
+    }
+
+    fn enable_receive_interrupt(&mut self) {
+        // SAFETY: This only makes the UART raise IRQ4 when data is available.
+        unsafe { self.interrupt_enable.write(INTERRUPT_DATA_AVAILABLE) };
+    }
+}
+
+impl Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if !PRESENT.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        for byte in s.bytes() {
+            // terminals expect CRLF line endings
+            if byte == b'\n' {
+                self.send(b'\r');
+            }
+            self.send(byte);
+        }
+
+        Ok(())
+    }
+}
+
+/// A ring buffer of received bytes. When full, new bytes are dropped.
+struct RxBuffer {
+    bytes: [u8; RX_BUFFER_SIZE],
+    head: usize,
+    len: usize,
+}
+
+impl RxBuffer {
+    const fn new() -> RxBuffer {
+        RxBuffer {
+            bytes: [0; RX_BUFFER_SIZE],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.len < RX_BUFFER_SIZE {
+            self.bytes[(self.head + self.len) % RX_BUFFER_SIZE] = byte;
+            self.len += 1;
+        }
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let byte = self.bytes[self.head];
+        self.head = (self.head + 1) % RX_BUFFER_SIZE;
+        self.len -= 1;
+        Some(byte)
+    }
+}
+
+/// Initializes COM1 at 115200 baud, so it can be used for output. This doesn't need
+/// interrupts, so can be called before anything else.
+pub fn init() {
+    if COM1.lock().init(MAX_BAUD_RATE) {
+        PRESENT.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Returns true if COM1 was found by `init`
+pub fn is_present() -> bool {
+    PRESENT.load(Ordering::Relaxed)
+}
+
+/// Starts buffering received bytes from IRQ4. The PIC must already be initialized.
+pub fn init_receive() {
+    if !PRESENT.load(Ordering::SeqCst) {
+        return;
+    }
+
+    irq::register(COM1_IRQ, interrupt);
+    COM1.lock().enable_receive_interrupt();
+}
+
+/// Returns the next byte received on COM1, if there is one
+pub fn read_byte() -> Option<u8> {
+    if !PRESENT.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    COM1.lock().receive()
+}
+
+fn interrupt() {
+    let mut port = COM1.lock();
+
+    // drain the FIFO, as we are only interrupted once per threshold
+    while let Some(byte) = port.poll_receive() {
+        port.rx_buffer.push(byte);
+    }
+}
+
+pub struct SerialSink;
+
+impl Sink for SerialSink {
+    fn name(&self) -> &'static str {
+        "serial"
+    }
+
+    fn write_str(&self, s: &str) {
+        COM1.lock().write_str(s).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rx_buffer_order() {
+        let mut buffer = RxBuffer::new();
+        assert_eq!(buffer.pop(), None);
+
+        for byte in 0..10 {
+            buffer.push(byte);
+        }
+        for byte in 0..10 {
+            assert_eq!(buffer.pop(), Some(byte));
+        }
+        assert_eq!(buffer.pop(), None);
+    }
+
+    #[test]
+    fn rx_buffer_full() {
+        let mut buffer = RxBuffer::new();
+        for byte in 0..RX_BUFFER_SIZE + 10 {
+            buffer.push(byte as u8);
+        }
+
+        assert_eq!(buffer.len, RX_BUFFER_SIZE);
+        assert_eq!(buffer.pop(), Some(0));
+
+        // wraps around once there is room again
+        buffer.push(42);
+        for _ in 1..RX_BUFFER_SIZE {
+            buffer.pop();
+        }
+        assert_eq!(buffer.pop(), Some(42));
+    }
+}
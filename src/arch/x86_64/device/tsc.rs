@@ -0,0 +1,32 @@
+//! The CPU's time-stamp counter, as a clock source
+
+use crate::arch::instructions::{cpuid, rdtsc};
+use crate::time::clock::ClockSource;
+use crate::time::tsc_frequency;
+
+pub static TSC: Tsc = Tsc;
+
+pub struct Tsc;
+
+impl ClockSource for Tsc {
+    fn name(&self) -> &'static str {
+        "tsc"
+    }
+
+    fn rating(&self) -> u32 {
+        // if the TSC can change frequency (i.e. with power states), it is nearly useless
+        if cpuid::has_invariant_tsc() {
+            300
+        } else {
+            50
+        }
+    }
+
+    fn frequency(&self) -> u64 {
+        tsc_frequency().expect("TSC used as a clock source before it was calibrated!")
+    }
+
+    fn read(&self) -> u64 {
+        rdtsc()
+    }
+}
@@ -8,7 +8,7 @@ use lazy_static::lazy_static;
 use tss::Tss;
 
 use super::instructions::registers::segmentation::*;
-use crate::println;
+use crate::debug;
 
 pub const DOUBLE_FAULT_STACK_INDEX: u8 = 1;
 const INTERRUPT_STACK_SIZE: usize = 4096;
@@ -19,7 +19,7 @@ static mut INTERRUPT_STACK: [u8; INTERRUPT_STACK_SIZE] = [0; INTERRUPT_STACK_SIZ
 
 lazy_static! {
     pub static ref TSS: Tss = {
-        println!("Making tss...");
+        debug!("Making tss...");
         let mut tss = Tss::new();
 
         // set the first index of the TSS IST to a new stack
@@ -32,7 +32,7 @@ lazy_static! {
     };
 
     pub static ref GDT: Gdt = {
-        println!("Making gdt...");
+        debug!("Making gdt...");
 
         let mut gdt = Gdt::new();
 
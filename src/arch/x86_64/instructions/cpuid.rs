@@ -0,0 +1,221 @@
+use bitflags::bitflags;
+use core::str;
+
+pub use core::arch::x86_64::CpuidResult;
+
+/// Leaves used for decoding
+const VENDOR_LEAF: u32 = 0x0;
+const FEATURE_LEAF: u32 = 0x1;
+const EXTENDED_BASE_LEAF: u32 = 0x8000_0000;
+const EXTENDED_FEATURE_LEAF: u32 = 0x8000_0001;
+const BRAND_LEAVES: [u32; 3] = [0x8000_0002, 0x8000_0003, 0x8000_0004];
+const POWER_MANAGEMENT_LEAF: u32 = 0x8000_0007;
+
+/// Bit in EDX of the power management leaf
+const INVARIANT_TSC: u32 = 1 << 8;
+
+bitflags! {
+    /// Feature bits from leaf 1, with EDX in the low half and ECX in the high half
+    pub struct Features: u64 {
+        const FPU = 1 << 0;
+        const DEBUGGING_EXTENSIONS = 1 << 2;
+        const PAGE_SIZE_EXTENSION = 1 << 3;
+        const TSC = 1 << 4;
+        const MSR = 1 << 5;
+        const PAE = 1 << 6;
+        const MACHINE_CHECK_EXCEPTION = 1 << 7;
+        const APIC = 1 << 9;
+        const SYSENTER = 1 << 11;
+        const MTRR = 1 << 12;
+        const PAGE_GLOBAL = 1 << 13;
+        const PAT = 1 << 16;
+        const CLFLUSH = 1 << 19;
+        const FXSR = 1 << 24;
+        const SSE = 1 << 25;
+        const SSE2 = 1 << 26;
+        const HYPER_THREADING = 1 << 28;
+
+        const SSE3 = 1 << 32;
+        const MONITOR = 1 << (32 + 3);
+        const SSSE3 = 1 << (32 + 9);
+        const CMPXCHG16B = 1 << (32 + 13);
+        const PCID = 1 << (32 + 17);
+        const SSE4_1 = 1 << (32 + 19);
+        const SSE4_2 = 1 << (32 + 20);
+        const X2APIC = 1 << (32 + 21);
+        const POPCNT = 1 << (32 + 23);
+        const TSC_DEADLINE = 1 << (32 + 24);
+        const XSAVE = 1 << (32 + 26);
+        const AVX = 1 << (32 + 28);
+        const RDRAND = 1 << (32 + 30);
+        const HYPERVISOR = 1 << (32 + 31);
+    }
+}
+
+bitflags! {
+    /// Feature bits from leaf 0x8000_0001, with EDX in the low half and ECX in the high half
+    pub struct ExtendedFeatures: u64 {
+        const SYSCALL = 1 << 11;
+        const NO_EXECUTE = 1 << 20;
+        const HUGE_PAGES = 1 << 26;
+        const RDTSCP = 1 << 27;
+        const LONG_MODE = 1 << 29;
+
+        const LAHF_SAHF = 1 << 32;
+        const LZCNT = 1 << (32 + 5);
+    }
+}
+
+/// The identity and features of the CPU, decoded from `cpuid`
+#[derive(Debug, Copy, Clone)]
+pub struct CpuInfo {
+    vendor: [u8; 12],
+    brand: [u8; 48],
+    pub features: Features,
+    pub extended_features: ExtendedFeatures,
+    pub invariant_tsc: bool,
+}
+
+impl CpuInfo {
+    /// Queries and decodes the CPU's identity and features
+    pub fn read() -> CpuInfo {
+        let max_extended = max_extended_leaf();
+        let has_extended = |leaf| max_extended >= leaf;
+
+        let mut brand = [0; 48];
+        if has_extended(BRAND_LEAVES[2]) {
+            for (chunk, leaf) in brand.chunks_mut(16).zip(BRAND_LEAVES.iter()) {
+                chunk.copy_from_slice(&registers_to_bytes(&cpuid(*leaf, 0)));
+            }
+        }
+
+        let extended_features = if has_extended(EXTENDED_FEATURE_LEAF) {
+            let result = cpuid(EXTENDED_FEATURE_LEAF, 0);
+            ExtendedFeatures::from_bits_truncate(combine(result.edx, result.ecx))
+        } else {
+            ExtendedFeatures::empty()
+        };
+
+        CpuInfo {
+            vendor: decode_vendor(&cpuid(VENDOR_LEAF, 0)),
+            brand,
+            features: Features::read(),
+            extended_features,
+            invariant_tsc: has_extended(POWER_MANAGEMENT_LEAF)
+                && cpuid(POWER_MANAGEMENT_LEAF, 0).edx & INVARIANT_TSC != 0,
+        }
+    }
+
+    /// Returns the vendor string, i.e. "GenuineIntel" or "AuthenticAMD"
+    pub fn vendor(&self) -> &str {
+        trim_string(&self.vendor)
+    }
+
+    /// Returns the brand string, or an empty string if the CPU doesn't report one
+    pub fn brand(&self) -> &str {
+        trim_string(&self.brand)
+    }
+}
+
+impl Features {
+    /// Reads the feature bits of leaf 1
+    pub fn read() -> Features {
+        let result = cpuid(FEATURE_LEAF, 0);
+        Features::from_bits_truncate(combine(result.edx, result.ecx))
+    }
+}
+
+/// Executes `cpuid` for the given leaf and subleaf
+pub fn cpuid(leaf: u32, subleaf: u32) -> CpuidResult {
+    // SAFETY: Every x86_64 CPU supports cpuid (boot.s already checked), and it has no side effects.
+    unsafe { core::arch::x86_64::__cpuid_count(leaf, subleaf) }
+}
+
+/// Returns the highest supported extended leaf
+pub fn max_extended_leaf() -> u32 {
+    cpuid(EXTENDED_BASE_LEAF, 0).eax
+}
+
+/// Returns true if the local APIC timer supports TSC-deadline mode
+pub fn has_tsc_deadline() -> bool {
+    Features::read().contains(Features::TSC_DEADLINE)
+}
+
+/// Returns true if the CPU has a local APIC
+pub fn has_apic() -> bool {
+    Features::read().contains(Features::APIC)
+}
+
+/// Returns true if the TSC runs at a constant rate in all power states
+pub fn has_invariant_tsc() -> bool {
+    max_extended_leaf() >= POWER_MANAGEMENT_LEAF
+        && cpuid(POWER_MANAGEMENT_LEAF, 0).edx & INVARIANT_TSC != 0
+}
+
+fn combine(low: u32, high: u32) -> u64 {
+    ((high as u64) << 32) | low as u64
+}
+
+/// The vendor string is stored in EBX, EDX, ECX (in that order)
+fn decode_vendor(result: &CpuidResult) -> [u8; 12] {
+    let mut vendor = [0; 12];
+    vendor[0..4].copy_from_slice(&result.ebx.to_le_bytes());
+    vendor[4..8].copy_from_slice(&result.edx.to_le_bytes());
+    vendor[8..12].copy_from_slice(&result.ecx.to_le_bytes());
+    vendor
+}
+
+/// Each brand string leaf stores 16 bytes in EAX, EBX, ECX, EDX (in that order)
+fn registers_to_bytes(result: &CpuidResult) -> [u8; 16] {
+    let mut bytes = [0; 16];
+    for (chunk, register) in bytes
+        .chunks_mut(4)
+        .zip([result.eax, result.ebx, result.ecx, result.edx].iter())
+    {
+        chunk.copy_from_slice(&register.to_le_bytes());
+    }
+    bytes
+}
+
+/// Strips the null terminator and padding spaces from a cpuid string
+fn trim_string(bytes: &[u8]) -> &str {
+    let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+    str::from_utf8(&bytes[..len]).unwrap_or("").trim()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn result(eax: u32, ebx: u32, ecx: u32, edx: u32) -> CpuidResult {
+        CpuidResult { eax, ebx, ecx, edx }
+    }
+
+    #[test]
+    fn vendor() {
+        // "Genu" "ineI" "ntel"
+        let vendor = decode_vendor(&result(0xD, 0x756E_6547, 0x6C65_746E, 0x4965_6E69));
+        assert_eq!(trim_string(&vendor), "GenuineIntel");
+    }
+
+    #[test]
+    fn brand() {
+        let mut brand = [0; 48];
+        brand[..16].copy_from_slice(&registers_to_bytes(&result(
+            u32::from_le_bytes(*b"  QE"),
+            u32::from_le_bytes(*b"MU V"),
+            u32::from_le_bytes(*b"irtu"),
+            u32::from_le_bytes(*b"al C"),
+        )));
+        brand[16..19].copy_from_slice(b"PU ");
+
+        assert_eq!(trim_string(&brand), "QEMU Virtual CPU");
+        assert_eq!(trim_string(&[0; 48]), "");
+    }
+
+    #[test]
+    fn feature_halves() {
+        let features = Features::from_bits_truncate(combine(1 << 9, 1 << 24));
+        assert_eq!(features, Features::APIC | Features::TSC_DEADLINE);
+    }
+}
@@ -0,0 +1,68 @@
+#[cfg(not(test))]
+use super::registers::rflags::{self, RFlags};
+
+/// Returns true if maskable interrupts are currently enabled (i.e. RFLAGS.IF is set)
+#[cfg(not(test))]
+pub fn are_enabled() -> bool {
+    rflags::read().contains(RFlags::INTERRUPT)
+}
+
+/// Enables maskable interrupts
+#[cfg(not(test))]
+pub fn enable() {
+    // SAFETY: Enabling interrupts cannot violate memory safety by itself. Any code that relies
+    //         on interrupts being disabled should be holding an `IrqSafeMutex` or be within
+    //         `without_interrupts`, which restore the previous state correctly.
+    //         Not `nomem`, so memory accesses can't be moved out of the critical section.
+    unsafe { asm!("sti", options(nostack)) };
+}
+
+/// Disables maskable interrupts
+#[cfg(not(test))]
+pub fn disable() {
+    // SAFETY: Disabling interrupts cannot violate memory safety. Not `nomem`, so memory
+    //         accesses can't be moved before the critical section starts.
+    unsafe { asm!("cli", options(nostack)) };
+}
+
+// `cli` and `sti` are privileged, so running them on the host would fault. Instead, just
+// simulate the interrupt flag (per test thread) so code built on top of these can still be tested.
+#[cfg(test)]
+std::thread_local! {
+    static SIMULATED_FLAG: core::cell::Cell<bool> = core::cell::Cell::new(true);
+}
+
+#[cfg(test)]
+pub fn are_enabled() -> bool {
+    SIMULATED_FLAG.with(|flag| flag.get())
+}
+
+#[cfg(test)]
+pub fn enable() {
+    SIMULATED_FLAG.with(|flag| flag.set(true))
+}
+
+#[cfg(test)]
+pub fn disable() {
+    SIMULATED_FLAG.with(|flag| flag.set(false))
+}
+
+/// Runs `f` with maskable interrupts disabled, restoring the previous interrupt state afterwards.
+/// This can be safely nested, as interrupts are only re-enabled if they were enabled before.
+pub fn without_interrupts<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let were_enabled = are_enabled();
+    if were_enabled {
+        disable();
+    }
+
+    let result = f();
+
+    if were_enabled {
+        enable();
+    }
+
+    result
+}
@@ -1,5 +1,26 @@
 // TODO: This may be better as a sub-crate
 
 #![allow(dead_code)]
+pub mod cpuid;
+pub mod interrupts;
+pub mod msr;
+pub mod port;
 pub mod registers;
 pub mod tlb;
+
+/// Halts the CPU until the next interrupt
+pub fn halt() {
+    // SAFETY: hlt only waits for an interrupt.
+    unsafe { asm!("hlt", options(nomem, nostack)) };
+}
+
+/// Reads the current value of the CPU's time-stamp counter
+pub fn rdtsc() -> u64 {
+    let low: u32;
+    let high: u32;
+
+    // SAFETY: rdtsc only reads the time-stamp counter.
+    unsafe { asm!("rdtsc", out("eax") low, out("edx") high, options(nomem, nostack)) };
+
+    ((high as u64) << 32) | low as u64
+}
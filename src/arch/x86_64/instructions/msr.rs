@@ -0,0 +1,83 @@
+use bitflags::bitflags;
+
+/// A Model Specific Register
+#[derive(Debug, Copy, Clone)]
+pub struct Msr(u32);
+
+impl Msr {
+    pub const fn new(register: u32) -> Msr {
+        Msr(register)
+    }
+
+    /// # Safety
+    /// The caller must ensure the MSR exists on this CPU, or a general protection fault is raised.
+    pub unsafe fn read(&self) -> u64 {
+        let low: u32;
+        let high: u32;
+        asm!("rdmsr", in("ecx") self.0, out("eax") low, out("edx") high, options(nomem, nostack));
+        ((high as u64) << 32) | low as u64
+    }
+
+    /// # Safety
+    /// The caller must ensure the MSR exists on this CPU, and that the new value does not violate
+    /// memory safety (many MSRs control paging, system calls, and segmentation).
+    pub unsafe fn write(&self, value: u64) {
+        let low = value as u32;
+        let high = (value >> 32) as u32;
+        asm!("wrmsr", in("ecx") self.0, in("eax") low, in("edx") high, options(nostack));
+    }
+}
+
+/// Local APIC base address and enable bit
+pub const IA32_APIC_BASE: Msr = Msr::new(0x1B);
+
+/// The memory types selected by the PAT, PCD and PWT page table bits
+pub const IA32_PAT: Msr = Msr::new(0x277);
+
+/// Extended feature enables, see `EferFlags`
+pub const IA32_EFER: Msr = Msr::new(0xC000_0080);
+
+/// Segment selectors loaded by `syscall` and `sysret`
+pub const IA32_STAR: Msr = Msr::new(0xC000_0081);
+
+/// The 64-bit `syscall` entry point
+pub const IA32_LSTAR: Msr = Msr::new(0xC000_0082);
+
+/// The RFLAGS bits cleared by `syscall`
+pub const IA32_FMASK: Msr = Msr::new(0xC000_0084);
+
+/// The base addresses of the FS and GS segments
+pub const IA32_FS_BASE: Msr = Msr::new(0xC000_0100);
+pub const IA32_GS_BASE: Msr = Msr::new(0xC000_0101);
+
+/// The GS base swapped in by `swapgs`
+pub const IA32_KERNEL_GS_BASE: Msr = Msr::new(0xC000_0102);
+
+/// The TSC value at which the local APIC timer fires in TSC-deadline mode
+pub const IA32_TSC_DEADLINE: Msr = Msr::new(0x6E0);
+
+bitflags! {
+    pub struct EferFlags: u64 {
+        const SYSTEM_CALL_EXTENSIONS = 1 << 0;
+        const LONG_MODE_ENABLE = 1 << 8;
+        const LONG_MODE_ACTIVE = 1 << 10;
+        const NO_EXECUTE_ENABLE = 1 << 11;
+        const SECURE_VIRTUAL_MACHINE_ENABLE = 1 << 12;
+        const FAST_FXSAVE_FXRSTOR = 1 << 14;
+    }
+}
+
+/// Returns the flags currently set in the EFER
+pub fn efer() -> EferFlags {
+    // SAFETY: The EFER exists on every x86_64 CPU, as it is needed to enter long mode.
+    EferFlags::from_bits_truncate(unsafe { IA32_EFER.read() })
+}
+
+/// Sets the flags in the EFER, leaving any reserved bits untouched
+///
+/// # Safety
+/// Changing the EFER can leave long mode, or change how page table entries are interpreted.
+pub unsafe fn set_efer(flags: EferFlags) {
+    let reserved = IA32_EFER.read() & !EferFlags::all().bits();
+    IA32_EFER.write(reserved | flags.bits());
+}
@@ -0,0 +1,87 @@
+use core::marker::PhantomData;
+
+/// A value that can be read from or written to an I/O port
+pub trait PortValue: Copy {
+    /// # Safety
+    /// Reading from an I/O port can have side effects on the device behind it.
+    unsafe fn read_from_port(port: u16) -> Self;
+
+    /// # Safety
+    /// Writing to an I/O port can have side effects on the device behind it.
+    unsafe fn write_to_port(port: u16, value: Self);
+}
+
+impl PortValue for u8 {
+    unsafe fn read_from_port(port: u16) -> u8 {
+        let value: u8;
+        asm!("in al, dx", out("al") value, in("dx") port, options(nomem, nostack));
+        value
+    }
+
+    unsafe fn write_to_port(port: u16, value: u8) {
+        asm!("out dx, al", in("dx") port, in("al") value, options(nomem, nostack));
+    }
+}
+
+impl PortValue for u16 {
+    unsafe fn read_from_port(port: u16) -> u16 {
+        let value: u16;
+        asm!("in ax, dx", out("ax") value, in("dx") port, options(nomem, nostack));
+        value
+    }
+
+    unsafe fn write_to_port(port: u16, value: u16) {
+        asm!("out dx, ax", in("dx") port, in("ax") value, options(nomem, nostack));
+    }
+}
+
+impl PortValue for u32 {
+    unsafe fn read_from_port(port: u16) -> u32 {
+        let value: u32;
+        asm!("in eax, dx", out("eax") value, in("dx") port, options(nomem, nostack));
+        value
+    }
+
+    unsafe fn write_to_port(port: u16, value: u32) {
+        asm!("out dx, eax", in("dx") port, in("eax") value, options(nomem, nostack));
+    }
+}
+
+/// An x86 I/O port, which reads and writes values of type `T`
+#[derive(Debug)]
+pub struct Port<T> {
+    port: u16,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> Port<T> {
+    pub const fn new(port: u16) -> Port<T> {
+        Port {
+            port,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: PortValue> Port<T> {
+    /// # Safety
+    /// The caller must ensure reading from this port does not violate memory safety, as devices
+    /// may change their state (or even perform DMA) when read from.
+    pub unsafe fn read(&mut self) -> T {
+        T::read_from_port(self.port)
+    }
+
+    /// # Safety
+    /// The caller must ensure writing to this port does not violate memory safety, as devices
+    /// may change their state (or even perform DMA) when written to.
+    pub unsafe fn write(&mut self, value: T) {
+        T::write_to_port(self.port, value)
+    }
+}
+
+/// Waits a very small amount of time (~1-4 microseconds) by writing to an unused port. Some
+/// older devices (i.e. the PIC) need this between commands.
+pub fn io_wait() {
+    // SAFETY: Port 0x80 is only used for POST codes, so writing to it has no effect.
+    unsafe { Port::<u8>::new(0x80).write(0) };
+}
@@ -1,6 +1,104 @@
+use bitflags::bitflags;
+
+use crate::arch::paging::PhysicalAddress;
 use crate::define_read_reg_func;
 
+/// A macro for defining a function to write a control register
+macro_rules! define_write_reg_func {
+    ($register:tt) => {
+        paste::item! {
+            pub unsafe fn [<set_$register>](value: u64) {
+                asm!(concat!("mov ", stringify!($register), ", {}"), in(reg) value);
+            }
+        }
+    };
+}
+
 define_read_reg_func!(cr0, u64);
 define_read_reg_func!(cr2, u64);
 define_read_reg_func!(cr3, u64);
 define_read_reg_func!(cr4, u64);
+
+define_write_reg_func!(cr0);
+define_write_reg_func!(cr3);
+define_write_reg_func!(cr4);
+
+bitflags! {
+    pub struct Cr0Flags: u64 {
+        const PROTECTED_MODE = 1 << 0;
+        const MONITOR_COPROCESSOR = 1 << 1;
+        const EMULATE_COPROCESSOR = 1 << 2;
+        const TASK_SWITCHED = 1 << 3;
+        const EXTENSION_TYPE = 1 << 4;
+        const NUMERIC_ERROR = 1 << 5;
+        const WRITE_PROTECT = 1 << 16;
+        const ALIGNMENT_MASK = 1 << 18;
+        const NOT_WRITE_THROUGH = 1 << 29;
+        const CACHE_DISABLE = 1 << 30;
+        const PAGING = 1 << 31;
+    }
+}
+
+bitflags! {
+    pub struct Cr4Flags: u64 {
+        const VIRTUAL_8086_EXTENSIONS = 1 << 0;
+        const PROTECTED_VIRTUAL_INTERRUPTS = 1 << 1;
+        const TIMESTAMP_DISABLE = 1 << 2;
+        const DEBUGGING_EXTENSIONS = 1 << 3;
+        const PAGE_SIZE_EXTENSION = 1 << 4;
+        const PHYSICAL_ADDRESS_EXTENSION = 1 << 5;
+        const MACHINE_CHECK = 1 << 6;
+        const PAGE_GLOBAL = 1 << 7;
+        const PERFORMANCE_COUNTER = 1 << 8;
+        const OSFXSR = 1 << 9;
+        const OSXMMEXCPT = 1 << 10;
+        const USER_MODE_INSTRUCTION_PREVENTION = 1 << 11;
+        const FSGSBASE = 1 << 16;
+        const PCID = 1 << 17;
+        const OSXSAVE = 1 << 18;
+        const SMEP = 1 << 20;
+        const SMAP = 1 << 21;
+    }
+}
+
+/// Bits of CR3 that hold the physical address of the L4 page table
+const CR3_ADDRESS_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+/// Returns the flags currently set in CR0
+pub fn cr0_flags() -> Cr0Flags {
+    Cr0Flags::from_bits_truncate(cr0())
+}
+
+/// Sets the flags in CR0, leaving any reserved bits untouched
+///
+/// # Safety
+/// Changing CR0 can disable paging or protection, which can violate memory safety.
+pub unsafe fn set_cr0_flags(flags: Cr0Flags) {
+    set_cr0((cr0() & !Cr0Flags::all().bits()) | flags.bits());
+}
+
+/// Returns the flags currently set in CR4
+pub fn cr4_flags() -> Cr4Flags {
+    Cr4Flags::from_bits_truncate(cr4())
+}
+
+/// Sets the flags in CR4, leaving any reserved bits untouched
+///
+/// # Safety
+/// Changing CR4 alters how paging and protection work, which can violate memory safety.
+pub unsafe fn set_cr4_flags(flags: Cr4Flags) {
+    set_cr4((cr4() & !Cr4Flags::all().bits()) | flags.bits());
+}
+
+/// Returns the physical address of the active L4 page table
+pub fn page_table_address() -> PhysicalAddress {
+    PhysicalAddress::new(cr3() & CR3_ADDRESS_MASK)
+}
+
+/// Switches to the L4 page table at `address`, which also flushes the TLB
+///
+/// # Safety
+/// The new page table must be valid, and must map the currently executing code and stack.
+pub unsafe fn set_page_table_address(address: PhysicalAddress) {
+    set_cr3((cr3() & !CR3_ADDRESS_MASK) | (address.as_u64() & CR3_ADDRESS_MASK));
+}
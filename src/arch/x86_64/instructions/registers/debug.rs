@@ -0,0 +1,183 @@
+//! The debug registers. DR0-DR3 hold the addresses of up to four hardware breakpoints, DR7
+//! enables them and sets what they trigger on, and DR6 reports which one caused a debug
+//! exception.
+
+use bitflags::bitflags;
+
+/// The number of hardware breakpoints, i.e. address registers DR0-DR3
+pub const NUM_BREAKPOINTS: usize = 4;
+
+/// A macro for defining functions to read and write a debug register
+macro_rules! define_debug_reg_funcs {
+    ($register:tt) => {
+        paste::item! {
+            pub fn $register() -> u64 {
+                let value: u64;
+                // SAFETY: Reading a debug register has no side effects.
+                unsafe { asm!(concat!("mov {}, ", stringify!($register)), out(reg) value) };
+                value
+            }
+
+            pub unsafe fn [<set_$register>](value: u64) {
+                asm!(concat!("mov ", stringify!($register), ", {}"), in(reg) value);
+            }
+        }
+    };
+}
+
+define_debug_reg_funcs!(dr0);
+define_debug_reg_funcs!(dr1);
+define_debug_reg_funcs!(dr2);
+define_debug_reg_funcs!(dr3);
+define_debug_reg_funcs!(dr6);
+define_debug_reg_funcs!(dr7);
+
+bitflags! {
+    pub struct Dr6Flags: u64 {
+        const BREAKPOINT_0 = 1 << 0;
+        const BREAKPOINT_1 = 1 << 1;
+        const BREAKPOINT_2 = 1 << 2;
+        const BREAKPOINT_3 = 1 << 3;
+        const DEBUG_REGISTER_ACCESS = 1 << 13;
+        const SINGLE_STEP = 1 << 14;
+        const TASK_SWITCH = 1 << 15;
+    }
+}
+
+impl Dr6Flags {
+    /// Returns the index of the first breakpoint that was hit, if any
+    pub fn breakpoint_hit(&self) -> Option<usize> {
+        self.breakpoints_hit().next()
+    }
+
+    /// Returns the index of every breakpoint that was hit. The CPU can also report breakpoints
+    /// that aren't enabled in DR7, if their conditions are met.
+    pub fn breakpoints_hit(&self) -> impl Iterator<Item = usize> {
+        let bits = self.bits();
+        (0..NUM_BREAKPOINTS).filter(move |index| bits & (1 << index) != 0)
+    }
+}
+
+/// The value DR6 holds when nothing has been reported. Reserved bits read as one.
+const DR6_CLEAR: u64 = 0xFFFF_0FF0;
+
+/// What kind of access triggers a hardware breakpoint
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[repr(u64)]
+pub enum Condition {
+    Execute = 0b00,
+    Write = 0b01,
+    ReadWrite = 0b11,
+}
+
+/// How many bytes a hardware breakpoint covers. Breakpoints must be aligned to their size, and
+/// execute breakpoints must use `One`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[repr(u64)]
+pub enum Size {
+    One = 0b00,
+    Two = 0b01,
+    Eight = 0b10,
+    Four = 0b11,
+}
+
+impl Size {
+    pub fn from_bytes(bytes: usize) -> Option<Size> {
+        match bytes {
+            1 => Some(Size::One),
+            2 => Some(Size::Two),
+            4 => Some(Size::Four),
+            8 => Some(Size::Eight),
+            _ => None,
+        }
+    }
+
+    pub fn bytes(&self) -> usize {
+        match self {
+            Size::One => 1,
+            Size::Two => 2,
+            Size::Four => 4,
+            Size::Eight => 8,
+        }
+    }
+}
+
+/// Returns DR7 with breakpoint `index` enabled (globally, so it survives task switches) for
+/// `condition` and `size`
+pub fn dr7_enable(dr7: u64, index: usize, condition: Condition, size: Size) -> u64 {
+    let control_shift = 16 + index * 4;
+    let control = ((size as u64) << 2) | condition as u64;
+
+    (dr7 & !(0b1111 << control_shift)) | (control << control_shift) | (1 << (index * 2 + 1))
+}
+
+/// Returns DR7 with breakpoint `index` disabled
+pub fn dr7_disable(dr7: u64, index: usize) -> u64 {
+    dr7 & !(0b11 << (index * 2)) & !(0b1111 << (16 + index * 4))
+}
+
+/// Returns the flags set in DR6
+pub fn dr6_flags() -> Dr6Flags {
+    Dr6Flags::from_bits_truncate(dr6())
+}
+
+/// Clears DR6. The CPU never clears it, so this should be done after handling each debug
+/// exception.
+pub fn clear_dr6() {
+    // SAFETY: DR6 only reports status.
+    unsafe { set_dr6(DR6_CLEAR) };
+}
+
+/// Sets the address of breakpoint `index`
+///
+/// # Safety
+/// If the breakpoint is enabled, accessing the address will raise a debug exception.
+pub unsafe fn set_address(index: usize, addr: u64) {
+    match index {
+        0 => set_dr0(addr),
+        1 => set_dr1(addr),
+        2 => set_dr2(addr),
+        3 => set_dr3(addr),
+        _ => panic!("There are only {} hardware breakpoints", NUM_BREAKPOINTS),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn enable_sets_control_bits() {
+        let dr7 = dr7_enable(0, 1, Condition::Write, Size::Four);
+        assert_eq!(dr7, (1 << 3) | (0b1101 << 20));
+
+        // doesn't disturb other breakpoints
+        let dr7 = dr7_enable(dr7, 3, Condition::Execute, Size::One);
+        assert_eq!(dr7, (1 << 3) | (0b1101 << 20) | (1 << 7));
+    }
+
+    #[test]
+    fn disable_clears_only_that_breakpoint() {
+        let dr7 = dr7_enable(0, 0, Condition::ReadWrite, Size::Eight);
+        let dr7 = dr7_enable(dr7, 2, Condition::Write, Size::Two);
+        assert_eq!(
+            dr7_disable(dr7, 0),
+            dr7_enable(0, 2, Condition::Write, Size::Two)
+        );
+        assert_eq!(dr7_disable(dr7_disable(dr7, 0), 2), 0);
+    }
+
+    #[test]
+    fn breakpoint_hit() {
+        assert_eq!(Dr6Flags::SINGLE_STEP.breakpoint_hit(), None);
+        assert_eq!(
+            (Dr6Flags::BREAKPOINT_2 | Dr6Flags::BREAKPOINT_3).breakpoint_hit(),
+            Some(2)
+        );
+        assert!(
+            (Dr6Flags::BREAKPOINT_0 | Dr6Flags::BREAKPOINT_3 | Dr6Flags::SINGLE_STEP)
+                .breakpoints_hit()
+                .eq([0, 3].iter().copied())
+        );
+    }
+}
@@ -1,5 +1,7 @@
 #![allow(dead_code)]
 pub mod control;
+pub mod debug;
+pub mod rflags;
 pub mod segmentation;
 
 #[macro_export]
@@ -12,3 +14,12 @@ macro_rules! define_read_reg_func {
         }
     };
 }
+
+/// Returns the current frame pointer, i.e. the address of the caller's saved `rbp`
+#[inline(always)]
+pub fn frame_pointer() -> usize {
+    let rbp: usize;
+    // SAFETY: This only reads rbp.
+    unsafe { asm!("mov {}, rbp", out(reg) rbp, options(nomem, nostack)) };
+    rbp
+}
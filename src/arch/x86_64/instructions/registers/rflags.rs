@@ -0,0 +1,57 @@
+use bitflags::bitflags;
+
+bitflags! {
+    pub struct RFlags: u64 {
+        const CARRY = 1 << 0;
+        const PARITY = 1 << 2;
+        const AUXILIARY_CARRY = 1 << 4;
+        const ZERO = 1 << 6;
+        const SIGN = 1 << 7;
+        const TRAP = 1 << 8;
+        const INTERRUPT = 1 << 9;
+        const DIRECTION = 1 << 10;
+        const OVERFLOW = 1 << 11;
+        const IO_PRIVILEGE_LOW = 1 << 12;
+        const IO_PRIVILEGE_HIGH = 1 << 13;
+        const NESTED_TASK = 1 << 14;
+        const RESUME = 1 << 16;
+        const VIRTUAL_8086_MODE = 1 << 17;
+        const ALIGNMENT_CHECK = 1 << 18;
+        const VIRTUAL_INTERRUPT = 1 << 19;
+        const VIRTUAL_INTERRUPT_PENDING = 1 << 20;
+        const ID = 1 << 21;
+    }
+}
+
+/// Returns the raw value of RFLAGS
+pub fn read_raw() -> u64 {
+    let rflags: u64;
+
+    // SAFETY: This only reads RFLAGS, and leaves the stack as it found it.
+    unsafe { asm!("pushfq; pop {}", out(reg) rflags, options(nomem, preserves_flags)) };
+
+    rflags
+}
+
+/// Returns the flags currently set in RFLAGS
+pub fn read() -> RFlags {
+    RFlags::from_bits_truncate(read_raw())
+}
+
+/// Writes the raw value of RFLAGS
+///
+/// # Safety
+/// Changing RFLAGS can enable interrupts, or change the I/O privilege level, which can violate
+/// memory safety.
+pub unsafe fn write_raw(rflags: u64) {
+    // not `nomem`, as this can set IF and so must be a compiler barrier (and pushes to the stack)
+    asm!("push {}; popfq", in(reg) rflags);
+}
+
+/// Sets the flags in RFLAGS, leaving any reserved bits untouched
+///
+/// # Safety
+/// See `write_raw`.
+pub unsafe fn write(flags: RFlags) {
+    write_raw((read_raw() & !RFlags::all().bits()) | flags.bits());
+}
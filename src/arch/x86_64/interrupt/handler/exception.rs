@@ -1,76 +1,144 @@
-use bitflags::bitflags;
-
-use super::{HandlerWithError, InterruptStackFrame, StandardHandler};
+use super::report::{ExceptionError, ExceptionReport, PageFaultError};
+use super::{HandlerWithError, InterruptStackFrame, SavedRegisters, StandardHandler};
+use crate::arch::instructions::registers::control;
+use crate::arch::instructions::registers::debug::{clear_dr6, dr6_flags};
+use crate::arch::instructions::registers::rflags::RFlags;
+use crate::arch::watchpoint::{self, Owner, WatchpointHit};
+use crate::backtrace::Address;
+use crate::gdb;
 use crate::println;
-use crate::{interrupt, interrupt_error, restore_scratch_registers, save_scratch_registers};
+use crate::{interrupt, interrupt_error};
+use crate::{restore_preserved_registers, restore_scratch_registers};
+use crate::{save_preserved_registers, save_scratch_registers};
 
-bitflags! {
-    struct PageFaultError: usize {
-        const PROTECTION_VIOLATION = 1 << 0;
-        const CAUSED_BY_WRITE = 1 << 1;
-        const USER = 1 << 2;
-        const RESERVED_WRITE = 1 << 3;
-        const CAUSED_BY_INSTR_FETCH = 1 << 4;
-    }
-}
+// Exceptions we can't recover from panic, so they are reported the same way as any other
+// fatal error (and can be caught by `#[kernel_test(should_panic)]`). The report has its own
+// backtrace of the interrupted code, starting at the faulting instruction, as the panic's
+// backtrace starts in the panic machinery.
 
-interrupt!(invalid_opcode, |stack_frame| {
-    println!("OPCODE: not handled!");
-    println!("{:x?}", stack_frame);
-    loop {}
+interrupt!(invalid_opcode, |stack_frame, registers| {
+    panic!(
+        "{}",
+        ExceptionReport::new(
+            "invalid opcode",
+            ExceptionError::None,
+            stack_frame,
+            registers
+        )
+    );
 });
 
-interrupt!(div_by_zero, |stack_frame| {
-    println!("Exception: div by 0 SHOOK ULTIMATE");
-    println!("{:x?}", stack_frame);
-    loop {}
+interrupt!(div_by_zero, |stack_frame, registers| {
+    panic!(
+        "{}",
+        ExceptionReport::new(
+            "divide by zero",
+            ExceptionError::None,
+            stack_frame,
+            registers
+        )
+    );
 });
 
-interrupt!(breakpoint, |stack_frame| {
-    println!("Exception: BREAKPOINT");
-    println!("{:x?}", stack_frame);
-});
+interrupt!(breakpoint, |stack_frame, registers| {
+    if gdb::is_active() {
+        gdb::handle_breakpoint(stack_frame, registers);
+        return;
+    }
 
-interrupt_error!(page_fault, |stack_frame, error_code| {
-    let pagefault_error = PageFaultError::from_bits(error_code).unwrap();
     println!(
-        "\nEXCEPTION: PAGE FAULT with error code {:?}\n{:#?}",
-        pagefault_error, stack_frame
+        "Exception: BREAKPOINT at {}",
+        Address::new(stack_frame.instruction_pointer())
     );
-    loop {}
+    println!("{:x?}", stack_frame);
 });
 
-interrupt_error!(segment_not_present, |stack_frame, error_code| {
-    println!(
-        "\nEXCEPTION: Segment not present {:?} code {:?}",
-        stack_frame, error_code
-    );
-    loop {}
+interrupt!(debug, |stack_frame, registers| {
+    let dr6 = dr6_flags();
+
+    match watchpoint::hit(dr6) {
+        Some((id, watchpoint)) if watchpoint.owner == Owner::Kernel => {
+            println!("{}", WatchpointHit::new(id, watchpoint, dr6, stack_frame));
+            // execute breakpoints fault before the instruction runs, so would be hit again
+            // without RF
+            stack_frame.set_flags(stack_frame.flags() | RFlags::RESUME.bits() as usize);
+        }
+        _ if gdb::is_active() && gdb::handle_debug(dr6, stack_frame, registers) => {}
+        _ => println!(
+            "Exception: DEBUG at {} ({:?})",
+            Address::new(stack_frame.instruction_pointer()),
+            dr6
+        ),
+    }
+    clear_dr6();
 });
 
-interrupt_error!(stack_segment_fault, |stack_frame, error_code| {
-    println!(
-        "\nEXCEPTION: stack segment fault {:?} code {:?}",
-        stack_frame, error_code
+interrupt_error!(page_fault, |stack_frame, error_code, registers| {
+    // read CR2 first, in case anything below faults again
+    let address = control::cr2();
+    let error = PageFaultError::from_bits_truncate(error_code);
+
+    panic!(
+        "{}",
+        ExceptionReport::new(
+            "page fault",
+            ExceptionError::PageFault(error, address),
+            stack_frame,
+            registers
+        )
     );
-    loop {}
 });
 
-interrupt_error!(general_protection_fault, |stack_frame, error_code| {
-    println!(
-        "\nEXCEPTION: general protection fault {:x?} with code {:x}",
-        stack_frame, error_code
+interrupt_error!(segment_not_present, |stack_frame, error_code, registers| {
+    panic!(
+        "{}",
+        ExceptionReport::new(
+            "segment not present",
+            ExceptionError::Selector(error_code),
+            stack_frame,
+            registers
+        )
     );
-    loop {}
 });
 
-interrupt_error!(double_fault, |stack_frame, error_code| {
-    println!(
-        "\nDOUBLE FAULT: {:x?} with code {:x}",
-        stack_frame, error_code
+interrupt_error!(stack_segment_fault, |stack_frame, error_code, registers| {
+    panic!(
+        "{}",
+        ExceptionReport::new(
+            "stack segment fault",
+            ExceptionError::Selector(error_code),
+            stack_frame,
+            registers
+        )
     );
+});
+
+interrupt_error!(
+    general_protection_fault,
+    |stack_frame, error_code, registers| {
+        panic!(
+            "{}",
+            ExceptionReport::new(
+                "general protection fault",
+                ExceptionError::Selector(error_code),
+                stack_frame,
+                registers
+            )
+        );
+    }
+);
+
+interrupt_error!(double_fault, |stack_frame, error_code, registers| {
     crate::magic_breakpoint!();
 
-    // Double faults are not allowed to return.
-    loop {}
+    // Double faults are not allowed to return. The error code is always zero.
+    panic!(
+        "{}",
+        ExceptionReport::new(
+            "double fault",
+            ExceptionError::Raw(error_code),
+            stack_frame,
+            registers
+        )
+    );
 });
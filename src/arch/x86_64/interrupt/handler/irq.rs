@@ -0,0 +1,26 @@
+use super::{InterruptStackFrame, StandardHandler};
+use crate::arch::interrupt::irq::{dispatch, NUM_IRQS};
+use crate::{interrupt, restore_scratch_registers, save_scratch_registers};
+
+interrupt!(irq0, |_stack_frame| { dispatch(0) });
+interrupt!(irq1, |_stack_frame| { dispatch(1) });
+interrupt!(irq2, |_stack_frame| { dispatch(2) });
+interrupt!(irq3, |_stack_frame| { dispatch(3) });
+interrupt!(irq4, |_stack_frame| { dispatch(4) });
+interrupt!(irq5, |_stack_frame| { dispatch(5) });
+interrupt!(irq6, |_stack_frame| { dispatch(6) });
+interrupt!(irq7, |_stack_frame| { dispatch(7) });
+interrupt!(irq8, |_stack_frame| { dispatch(8) });
+interrupt!(irq9, |_stack_frame| { dispatch(9) });
+interrupt!(irq10, |_stack_frame| { dispatch(10) });
+interrupt!(irq11, |_stack_frame| { dispatch(11) });
+interrupt!(irq12, |_stack_frame| { dispatch(12) });
+interrupt!(irq13, |_stack_frame| { dispatch(13) });
+interrupt!(irq14, |_stack_frame| { dispatch(14) });
+interrupt!(irq15, |_stack_frame| { dispatch(15) });
+
+/// The IRQ stubs, indexed by IRQ number
+pub const HANDLERS: [StandardHandler; NUM_IRQS] = [
+    irq0, irq1, irq2, irq3, irq4, irq5, irq6, irq7, irq8, irq9, irq10, irq11, irq12, irq13, irq14,
+    irq15,
+];
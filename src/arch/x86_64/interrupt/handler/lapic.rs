@@ -0,0 +1,8 @@
+use super::{InterruptStackFrame, StandardHandler};
+use crate::arch::interrupt::lapic;
+use crate::{interrupt, restore_scratch_registers, save_scratch_registers};
+
+interrupt!(timer, |_stack_frame| { lapic::timer_interrupt() });
+
+// Spurious interrupts must not be acknowledged with an EOI
+interrupt!(spurious, |_stack_frame| {});
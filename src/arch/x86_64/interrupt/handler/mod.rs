@@ -1,6 +1,10 @@
 /// This module is for x86 exception handling without using too much magic like the 'x86-interrupt'
 /// feature.
 pub mod exception;
+pub mod irq;
+pub mod lapic;
+pub mod msi;
+pub mod report;
 
 // TODO: I am not sure a trait is the best way to represent this type of behavior, but I cannot
 //       think of any other ways to do this while maintaining type checking and being generic.
@@ -75,6 +79,70 @@ pub struct InterruptStackFrame {
     stack_segment: usize,
 }
 
+impl InterruptStackFrame {
+    /// The address of the instruction that was interrupted, or that caused the exception
+    pub fn instruction_pointer(&self) -> usize {
+        self.instruction_pointer
+    }
+
+    pub fn code_segment(&self) -> usize {
+        self.code_segment
+    }
+
+    pub fn flags(&self) -> usize {
+        self.flags
+    }
+
+    pub fn stack_pointer(&self) -> usize {
+        self.stack_pointer
+    }
+
+    pub fn stack_segment(&self) -> usize {
+        self.stack_segment
+    }
+
+    /// Sets where the interrupted code resumes
+    pub fn set_instruction_pointer(&mut self, addr: usize) {
+        self.instruction_pointer = addr;
+    }
+
+    /// Sets the RFLAGS the interrupted code resumes with
+    pub fn set_flags(&mut self, flags: usize) {
+        self.flags = flags;
+    }
+
+    pub fn set_stack_pointer(&mut self, addr: usize) {
+        self.stack_pointer = addr;
+    }
+
+    /// The privilege level that was running when the interrupt happened
+    pub fn privilege_level(&self) -> u8 {
+        (self.code_segment & 0b11) as u8
+    }
+}
+
+/// The general purpose registers of the interrupted code, in the order `save_scratch_registers`
+/// and then `save_preserved_registers` leave them on the stack.
+#[derive(Debug)]
+#[repr(C)]
+pub struct SavedRegisters {
+    pub r15: usize,
+    pub r14: usize,
+    pub r13: usize,
+    pub r12: usize,
+    pub rbp: usize,
+    pub rbx: usize,
+    pub r11: usize,
+    pub r10: usize,
+    pub r9: usize,
+    pub r8: usize,
+    pub rdi: usize,
+    pub rsi: usize,
+    pub rdx: usize,
+    pub rcx: usize,
+    pub rax: usize,
+}
+
 #[macro_export]
 macro_rules! save_scratch_registers {
     () => {
@@ -109,7 +177,39 @@ macro_rules! restore_scratch_registers {
     };
 }
 
-/// Defines an interrupt function
+/// Saves the registers a called function must preserve. Only needed when the handler wants to
+/// see every register, i.e. to report them.
+#[macro_export]
+macro_rules! save_preserved_registers {
+    () => {
+        asm!(
+            "push rbx
+             push rbp
+             push r12
+             push r13
+             push r14
+             push r15"
+        );
+    };
+}
+
+#[macro_export]
+macro_rules! restore_preserved_registers {
+    () => {
+        asm!(
+            "pop r15
+             pop r14
+             pop r13
+             pop r12
+             pop rbp
+             pop rbx"
+        );
+    };
+}
+
+/// Defines an interrupt function. With a second argument, the handler is also passed the
+/// interrupted code's `SavedRegisters`, and may modify them (along with the stack frame) to
+/// change where and how the interrupted code resumes.
 /// TODO
 #[macro_export]
 macro_rules! interrupt {
@@ -152,10 +252,48 @@ macro_rules! interrupt {
             pub const $handler: StandardHandler = StandardHandler([<__raw_interrupt__ $handler>]);
         }
     };
+    ($handler:ident, |$stack_frame:ident, $registers:ident| $code:block) => {
+        paste::item! {
+            #[allow(non_snake_case)]
+            #[naked]
+            pub unsafe extern "C" fn [<__raw_interrupt__ $handler>]() -> ! {
+                extern "C" fn internal(
+                    $stack_frame: &mut InterruptStackFrame,
+                    $registers: &mut SavedRegisters,
+                ) {
+                    $code
+                }
+
+                save_scratch_registers!();
+                save_preserved_registers!();
+
+                asm!(
+                    "mov rsi, rsp // load saved registers
+                    mov rdi, rsp
+                    add rdi, 15*8 // load stack frame
+                    call {}",
+                    in(reg) internal,
+                    out("rdi") _,
+                    out("rsi") _
+                );
+
+                restore_preserved_registers!();
+                restore_scratch_registers!();
+
+                asm!("iretq");
+
+                ::core::intrinsics::unreachable();
+            }
+            #[allow(non_upper_case_globals)]
+            pub const $handler: StandardHandler = StandardHandler([<__raw_interrupt__ $handler>]);
+        }
+    };
 }
 
 // TODO: is there a way to reduce redundency between interrupt! and interrupt_error!?
-/// Defines an interrupt function with an error code
+/// Defines an interrupt function with an error code. With a third argument, the handler is
+/// also passed the interrupted code's `SavedRegisters`, which it may modify as with
+/// `interrupt!`.
 /// TODO
 #[macro_export]
 macro_rules! interrupt_error {
@@ -201,4 +339,52 @@ macro_rules! interrupt_error {
             pub const $handler: HandlerWithError = HandlerWithError([<__raw_interrupt__ $handler>]);
         }
     };
+    ($handler:ident, |$stack_frame:ident, $error_code:ident, $registers:ident| $code:block) => {
+        paste::item! {
+            #[allow(non_snake_case)]
+            #[naked]
+            pub unsafe extern "C" fn [<__raw_interrupt__ $handler>]() -> ! {
+                extern "C" fn internal(
+                    $stack_frame: &mut InterruptStackFrame,
+                    $error_code: usize,
+                    $registers: &mut SavedRegisters,
+                ) {
+                    $code
+                }
+
+                save_scratch_registers!();
+                save_preserved_registers!();
+
+                asm!(
+                    "
+                    mov rdx, rsp // load saved registers
+                    mov rsi, [rsp + 15*8] // load error code
+                    mov rdi, rsp
+                    add rdi, 16*8 // load stack frame
+                    sub rsp, 8 // align stack to 16 byte boundary
+                    call {}
+                    add rsp, 8 // undo stack alignment
+                    ",
+                    in(reg) internal,
+                    out("rdi") _,
+                    out("rsi") _,
+                    out("rdx") _
+                );
+
+                restore_preserved_registers!();
+                restore_scratch_registers!();
+
+                // return from interrupt handler
+                asm!(
+                    "
+                    add rsp, 8 // pop error code off stack
+                    iretq
+                    "
+                );
+                ::core::intrinsics::unreachable();
+            }
+            #[allow(non_upper_case_globals)]
+            pub const $handler: HandlerWithError = HandlerWithError([<__raw_interrupt__ $handler>]);
+        }
+    };
 }
@@ -0,0 +1,43 @@
+use super::{InterruptStackFrame, StandardHandler};
+use crate::arch::interrupt::msi::{dispatch, NUM_VECTORS};
+use crate::{interrupt, restore_scratch_registers, save_scratch_registers};
+
+interrupt!(msi0, |_stack_frame| { dispatch(0) });
+interrupt!(msi1, |_stack_frame| { dispatch(1) });
+interrupt!(msi2, |_stack_frame| { dispatch(2) });
+interrupt!(msi3, |_stack_frame| { dispatch(3) });
+interrupt!(msi4, |_stack_frame| { dispatch(4) });
+interrupt!(msi5, |_stack_frame| { dispatch(5) });
+interrupt!(msi6, |_stack_frame| { dispatch(6) });
+interrupt!(msi7, |_stack_frame| { dispatch(7) });
+interrupt!(msi8, |_stack_frame| { dispatch(8) });
+interrupt!(msi9, |_stack_frame| { dispatch(9) });
+interrupt!(msi10, |_stack_frame| { dispatch(10) });
+interrupt!(msi11, |_stack_frame| { dispatch(11) });
+interrupt!(msi12, |_stack_frame| { dispatch(12) });
+interrupt!(msi13, |_stack_frame| { dispatch(13) });
+interrupt!(msi14, |_stack_frame| { dispatch(14) });
+interrupt!(msi15, |_stack_frame| { dispatch(15) });
+interrupt!(msi16, |_stack_frame| { dispatch(16) });
+interrupt!(msi17, |_stack_frame| { dispatch(17) });
+interrupt!(msi18, |_stack_frame| { dispatch(18) });
+interrupt!(msi19, |_stack_frame| { dispatch(19) });
+interrupt!(msi20, |_stack_frame| { dispatch(20) });
+interrupt!(msi21, |_stack_frame| { dispatch(21) });
+interrupt!(msi22, |_stack_frame| { dispatch(22) });
+interrupt!(msi23, |_stack_frame| { dispatch(23) });
+interrupt!(msi24, |_stack_frame| { dispatch(24) });
+interrupt!(msi25, |_stack_frame| { dispatch(25) });
+interrupt!(msi26, |_stack_frame| { dispatch(26) });
+interrupt!(msi27, |_stack_frame| { dispatch(27) });
+interrupt!(msi28, |_stack_frame| { dispatch(28) });
+interrupt!(msi29, |_stack_frame| { dispatch(29) });
+interrupt!(msi30, |_stack_frame| { dispatch(30) });
+interrupt!(msi31, |_stack_frame| { dispatch(31) });
+
+/// The MSI stubs, indexed by the vector's index in the pool
+pub const HANDLERS: [StandardHandler; NUM_VECTORS] = [
+    msi0, msi1, msi2, msi3, msi4, msi5, msi6, msi7, msi8, msi9, msi10, msi11, msi12, msi13, msi14,
+    msi15, msi16, msi17, msi18, msi19, msi20, msi21, msi22, msi23, msi24, msi25, msi26, msi27,
+    msi28, msi29, msi30, msi31,
+];
@@ -0,0 +1,291 @@
+//! Detailed reports of fatal exceptions, which are printed by the panic they cause.
+
+use bitflags::bitflags;
+use core::fmt::{self, Display, Formatter};
+use core::mem::size_of;
+
+use super::{InterruptStackFrame, SavedRegisters};
+use crate::arch::instructions::registers::control;
+use crate::arch::instructions::registers::rflags::RFlags;
+use crate::arch::paging::is_range_mapped;
+use crate::backtrace::{Address, Backtrace};
+
+/// How many bytes of code are dumped, starting at the faulting instruction
+const CODE_DUMP_SIZE: usize = 16;
+
+/// How many words of the stack are dumped, starting at the interrupted stack pointer
+const STACK_DUMP_WORDS: usize = 8;
+
+bitflags! {
+    pub struct PageFaultError: usize {
+        const PROTECTION_VIOLATION = 1 << 0;
+        const CAUSED_BY_WRITE = 1 << 1;
+        const USER = 1 << 2;
+        const RESERVED_WRITE = 1 << 3;
+        const CAUSED_BY_INSTR_FETCH = 1 << 4;
+    }
+}
+
+/// The descriptor table a selector error code refers to
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum DescriptorTable {
+    Gdt,
+    Idt,
+    Ldt,
+}
+
+/// The error code pushed by exceptions caused by a segment selector (i.e. general protection
+/// faults and segment not present), identifying the selector
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SelectorErrorCode {
+    /// Set if the exception happened while delivering an event external to the program, i.e.
+    /// an interrupt
+    pub external: bool,
+    pub table: DescriptorTable,
+    pub index: u16,
+}
+
+impl SelectorErrorCode {
+    pub fn decode(error_code: usize) -> SelectorErrorCode {
+        let table = match (error_code >> 1) & 0b11 {
+            0b00 => DescriptorTable::Gdt,
+            0b10 => DescriptorTable::Ldt,
+            // both 0b01 and 0b11 mean the IDT
+            _ => DescriptorTable::Idt,
+        };
+
+        SelectorErrorCode {
+            external: error_code & 1 != 0,
+            table,
+            index: ((error_code >> 3) & 0x1FFF) as u16,
+        }
+    }
+}
+
+impl Display for SelectorErrorCode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}[{}]", self.table, self.index)?;
+        if self.external {
+            write!(f, " (external)")?;
+        }
+        Ok(())
+    }
+}
+
+/// The decoded error code of an exception
+#[derive(Debug, Copy, Clone)]
+pub enum ExceptionError {
+    None,
+    /// A page fault's error code, and the address that was accessed (from CR2)
+    PageFault(PageFaultError, u64),
+    /// An error code that may refer to a selector. Zero if it doesn't.
+    Selector(usize),
+    Raw(usize),
+}
+
+/// Everything we know about an exception: the decoded error code, the interrupted code's
+/// registers, the control registers, the faulting code, the top of the stack and a backtrace
+/// starting at the faulting instruction
+pub struct ExceptionReport<'a> {
+    name: &'static str,
+    error: ExceptionError,
+    stack_frame: &'a InterruptStackFrame,
+    registers: &'a SavedRegisters,
+}
+
+impl<'a> ExceptionReport<'a> {
+    pub fn new(
+        name: &'static str,
+        error: ExceptionError,
+        stack_frame: &'a InterruptStackFrame,
+        registers: &'a SavedRegisters,
+    ) -> ExceptionReport<'a> {
+        ExceptionReport {
+            name,
+            error,
+            stack_frame,
+            registers,
+        }
+    }
+
+    fn write_error(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.error {
+            ExceptionError::None => Ok(()),
+            ExceptionError::PageFault(error, address) => writeln!(
+                f,
+                "error: {:?} accessing {:#x} (code {:#x})",
+                error,
+                address,
+                error.bits()
+            ),
+            ExceptionError::Selector(0) => writeln!(f, "error code: 0"),
+            ExceptionError::Selector(code) => writeln!(
+                f,
+                "error: selector {} (code {:#x})",
+                SelectorErrorCode::decode(code),
+                code
+            ),
+            ExceptionError::Raw(code) => writeln!(f, "error code: {:#x}", code),
+        }
+    }
+
+    fn write_registers(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let r = self.registers;
+        let frame = self.stack_frame;
+
+        writeln!(
+            f,
+            "rax={:016x} rbx={:016x} rcx={:016x}",
+            r.rax, r.rbx, r.rcx
+        )?;
+        writeln!(
+            f,
+            "rdx={:016x} rsi={:016x} rdi={:016x}",
+            r.rdx, r.rsi, r.rdi
+        )?;
+        writeln!(
+            f,
+            "rbp={:016x} rsp={:016x} r8 ={:016x}",
+            r.rbp,
+            frame.stack_pointer(),
+            r.r8
+        )?;
+        writeln!(f, "r9 ={:016x} r10={:016x} r11={:016x}", r.r9, r.r10, r.r11)?;
+        writeln!(
+            f,
+            "r12={:016x} r13={:016x} r14={:016x}",
+            r.r12, r.r13, r.r14
+        )?;
+        writeln!(
+            f,
+            "r15={:016x} cs={:04x} ss={:04x}",
+            r.r15,
+            frame.code_segment(),
+            frame.stack_segment()
+        )?;
+        writeln!(
+            f,
+            "rflags={:016x} {:?}",
+            frame.flags(),
+            RFlags::from_bits_truncate(frame.flags() as u64)
+        )?;
+        writeln!(
+            f,
+            "cr0={:016x} cr3={:016x} cr4={:016x}",
+            control::cr0(),
+            control::cr3(),
+            control::cr4()
+        )
+    }
+
+    fn write_code(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let rip = self.stack_frame.instruction_pointer();
+
+        let mut bytes = [0; CODE_DUMP_SIZE];
+        if !read_into(rip, &mut bytes) {
+            return writeln!(f, "code at rip: <not mapped>");
+        }
+
+        write!(f, "code at rip:")?;
+        for byte in bytes.iter() {
+            write!(f, " {:02x}", byte)?;
+        }
+        writeln!(f)
+    }
+
+    fn write_stack(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let rsp = self.stack_frame.stack_pointer();
+        let mut words = [0usize; STACK_DUMP_WORDS];
+        if rsp % size_of::<usize>() != 0 || !read_into(rsp, &mut words) {
+            return writeln!(f, "stack at rsp: <not mapped>");
+        }
+
+        writeln!(f, "stack at rsp:")?;
+        for (i, pair) in words.chunks(2).enumerate() {
+            write!(f, "  {:016x}:", rsp + i * 2 * size_of::<usize>())?;
+            for word in pair {
+                write!(f, " {:016x}", word)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Display for ExceptionReport<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let level = self.stack_frame.privilege_level();
+        writeln!(
+            f,
+            "EXCEPTION: {} in {} mode (ring {})",
+            self.name,
+            if level == 0 { "kernel" } else { "user" },
+            level
+        )?;
+        writeln!(
+            f,
+            "rip: {}",
+            Address::new(self.stack_frame.instruction_pointer())
+        )?;
+        self.write_error(f)?;
+        self.write_registers(f)?;
+        self.write_code(f)?;
+        self.write_stack(f)?;
+        write!(
+            f,
+            "{}",
+            Backtrace::interrupted(self.stack_frame.instruction_pointer(), self.registers.rbp)
+        )
+    }
+}
+
+/// Fills `buffer` from memory at `addr`, returning false (and leaving it untouched) if any of
+/// that memory isn't mapped. `addr` must be aligned for `T`.
+fn read_into<T: Copy>(addr: usize, buffer: &mut [T]) -> bool {
+    if !is_range_mapped(addr as u64, buffer.len() * size_of::<T>()) {
+        return false;
+    }
+
+    // SAFETY: We just checked the whole range is mapped, and the caller ensures it is aligned.
+    unsafe { core::ptr::copy_nonoverlapping(addr as *const T, buffer.as_mut_ptr(), buffer.len()) };
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_selector_error_code() {
+        assert_eq!(
+            SelectorErrorCode::decode(0x10),
+            SelectorErrorCode {
+                external: false,
+                table: DescriptorTable::Gdt,
+                index: 2,
+            }
+        );
+        assert_eq!(
+            SelectorErrorCode::decode((13 << 3) | 0b010),
+            SelectorErrorCode {
+                external: false,
+                table: DescriptorTable::Idt,
+                index: 13,
+            }
+        );
+        assert_eq!(
+            SelectorErrorCode::decode((5 << 3) | 0b101),
+            SelectorErrorCode {
+                external: true,
+                table: DescriptorTable::Ldt,
+                index: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn display_selector_error_code() {
+        let code = SelectorErrorCode::decode((13 << 3) | 0b011);
+        assert_eq!(format!("{}", code), "Idt[13] (external)");
+    }
+}
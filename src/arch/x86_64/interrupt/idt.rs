@@ -89,6 +89,15 @@ impl Idt {
     }
 }
 
+/// Resets the CPU by loading an empty IDT and raising an exception. With no handler for it (or for
+/// the double fault that follows), the CPU triple faults.
+///
+/// ## Safety: This never returns, and nothing is shut down cleanly.
+pub unsafe fn triple_fault() -> ! {
+    let ptr = IdtPseudoDescriptor { limit: 0, base: 0 };
+    asm!("lidt [{}]", "int3", in(reg) &ptr, options(noreturn))
+}
+
 /// Represents a pseudo-descriptor to an IDT, that is used in the lidt instruction
 #[repr(C, packed)]
 struct IdtPseudoDescriptor {
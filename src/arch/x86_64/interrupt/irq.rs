@@ -0,0 +1,54 @@
+//! Dispatching of hardware interrupt requests (IRQs) to driver handlers.
+//! The IDT is built once at boot, so every IRQ vector points to a stub that looks up the
+//! currently registered handler here.
+
+use super::pic;
+use crate::sync::IrqSafeMutex;
+
+/// The vector that IRQ0 is remapped to. IRQs take up the first user-defined IDT descriptors.
+pub const IRQ_BASE: u8 = 32;
+
+/// The number of legacy IRQ lines
+pub const NUM_IRQS: usize = 16;
+
+static HANDLERS: IrqSafeMutex<[Option<fn()>; NUM_IRQS]> = IrqSafeMutex::new([None; NUM_IRQS]);
+
+/// Registers `handler` to be called whenever `irq` is raised, and unmasks the IRQ.
+/// Handlers run with interrupts disabled, and do not need to signal the end of the interrupt.
+pub fn register(irq: u8, handler: fn()) {
+    assert!((irq as usize) < NUM_IRQS, "Invalid IRQ number!");
+
+    HANDLERS.lock()[irq as usize] = Some(handler);
+    pic::unmask(irq);
+}
+
+/// Masks `irq` and removes its handler.
+pub fn unregister(irq: u8) {
+    assert!((irq as usize) < NUM_IRQS, "Invalid IRQ number!");
+
+    pic::mask(irq);
+    HANDLERS.lock()[irq as usize] = None;
+}
+
+/// Returns true if `irq` has a handler. Each IRQ has only one, so shared lines must be
+/// dispatched by whoever registered it.
+pub fn is_registered(irq: u8) -> bool {
+    assert!((irq as usize) < NUM_IRQS, "Invalid IRQ number!");
+
+    HANDLERS.lock()[irq as usize].is_some()
+}
+
+/// Called by the IRQ stubs in the IDT.
+pub(super) fn dispatch(irq: u8) {
+    if pic::is_spurious(irq) {
+        return;
+    }
+
+    // copy the handler out, so the handler itself is free to register or unregister IRQs
+    let handler = HANDLERS.lock()[irq as usize];
+    if let Some(handler) = handler {
+        handler();
+    }
+
+    pic::end_of_interrupt(irq);
+}
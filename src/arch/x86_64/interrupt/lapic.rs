@@ -0,0 +1,198 @@
+//! Driver for the local APIC, the per-CPU interrupt controller.
+//! It is used for its timer and to receive message signalled interrupts from PCI devices, while
+//! legacy IRQs still come in through the 8259 PICs (which the firmware leaves connected to LINT0).
+
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::arch::instructions::cpuid;
+use crate::arch::instructions::msr::{IA32_APIC_BASE, IA32_TSC_DEADLINE};
+use crate::arch::instructions::rdtsc;
+use crate::arch::paging::{map_physical, PhysicalAddress, MMIO_FLAGS, PAGE_SIZE};
+use crate::time::clock::{ClockEvent, EventFeatures};
+use crate::time::{timer, tsc_frequency, NANOS_PER_SEC};
+
+/// The vector the timer interrupt is raised on, just after the legacy IRQs
+pub const TIMER_VECTOR: u8 = 48;
+
+/// The vector spurious interrupts are raised on. The low 4 bits must be set on older APICs.
+pub const SPURIOUS_VECTOR: u8 = 0xFF;
+
+/// Register offsets
+const ID: usize = 0x20;
+const END_OF_INTERRUPT: usize = 0xB0;
+const SPURIOUS_INTERRUPT: usize = 0xF0;
+const LVT_TIMER: usize = 0x320;
+const TIMER_INITIAL_COUNT: usize = 0x380;
+const TIMER_CURRENT_COUNT: usize = 0x390;
+const TIMER_DIVIDE: usize = 0x3E0;
+
+/// Bits in the IA32_APIC_BASE MSR
+const GLOBAL_ENABLE: u64 = 1 << 11;
+const BASE_ADDRESS_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+const SOFTWARE_ENABLE: u32 = 1 << 8;
+const LVT_MASKED: u32 = 1 << 16;
+const TIMER_MODE_PERIODIC: u32 = 0b01 << 17;
+const TIMER_MODE_TSC_DEADLINE: u32 = 0b10 << 17;
+const TIMER_DIVIDE_BY_16: u32 = 0b0011;
+
+/// How long to spend calibrating the timer against the TSC
+const CALIBRATION_MS: u64 = 10;
+
+/// The virtual address of the local APIC's registers, or 0 if it is not enabled
+static BASE: AtomicU64 = AtomicU64::new(0);
+
+/// The rate the timer counts down at, in Hz
+static TIMER_FREQUENCY: AtomicU64 = AtomicU64::new(0);
+
+static TSC_DEADLINE: AtomicBool = AtomicBool::new(false);
+
+/// The local APIC timer, as a clock event device
+pub static LOCAL_APIC_TIMER: LocalApicTimer = LocalApicTimer;
+
+/// Enables the local APIC. Returns false if this CPU does not have one.
+/// Must be called after the frame allocator is initialized, as this maps its registers.
+pub fn init() -> bool {
+    if !cpuid::has_apic() {
+        return false;
+    }
+
+    // SAFETY: Every CPU with an APIC has the APIC base MSR, and enabling it does not move it.
+    let base = unsafe {
+        let apic_base = IA32_APIC_BASE.read();
+        IA32_APIC_BASE.write(apic_base | GLOBAL_ENABLE);
+        apic_base & BASE_ADDRESS_MASK
+    };
+
+    let registers = map_physical(PhysicalAddress::new(base), PAGE_SIZE, MMIO_FLAGS);
+    BASE.store(registers.as_u64(), Ordering::SeqCst);
+
+    write(SPURIOUS_INTERRUPT, SPURIOUS_VECTOR as u32 | SOFTWARE_ENABLE);
+    write(LVT_TIMER, LVT_MASKED);
+
+    true
+}
+
+pub fn is_enabled() -> bool {
+    BASE.load(Ordering::Relaxed) != 0
+}
+
+/// Returns the ID of the current CPU's local APIC
+pub fn id() -> u8 {
+    (read(ID) >> 24) as u8
+}
+
+/// Signals the end of an interrupt raised by the local APIC
+pub fn end_of_interrupt() {
+    write(END_OF_INTERRUPT, 0);
+}
+
+/// Measures the frequency of the timer against the TSC, which must already be calibrated.
+/// Returns false if the timer cannot be used.
+pub fn init_timer() -> bool {
+    let tsc_frequency = match tsc_frequency() {
+        Some(frequency) if is_enabled() => frequency,
+        _ => return false,
+    };
+
+    write(TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+    write(LVT_TIMER, LVT_MASKED);
+    write(TIMER_INITIAL_COUNT, u32::MAX);
+
+    let start = rdtsc();
+    let wait = tsc_frequency * CALIBRATION_MS / 1000;
+    while rdtsc() - start < wait {
+        core::hint::spin_loop();
+    }
+
+    let elapsed = (u32::MAX - read(TIMER_CURRENT_COUNT)) as u64;
+    write(TIMER_INITIAL_COUNT, 0);
+
+    if elapsed == 0 {
+        return false;
+    }
+
+    TIMER_FREQUENCY.store(elapsed * 1000 / CALIBRATION_MS, Ordering::SeqCst);
+    TSC_DEADLINE.store(cpuid::has_tsc_deadline(), Ordering::SeqCst);
+
+    true
+}
+
+/// Called by the timer interrupt stub in the IDT
+pub(super) fn timer_interrupt() {
+    timer::handle_event();
+    end_of_interrupt();
+}
+
+fn read(offset: usize) -> u32 {
+    let base = BASE.load(Ordering::Relaxed);
+    assert!(base != 0, "Local APIC used before it was initialized!");
+
+    // SAFETY: The registers are mapped by `init`, and are always 16-byte aligned.
+    unsafe { ptr::read_volatile((base as usize + offset) as *const u32) }
+}
+
+fn write(offset: usize, value: u32) {
+    let base = BASE.load(Ordering::Relaxed);
+    assert!(base != 0, "Local APIC used before it was initialized!");
+
+    // SAFETY: The registers are mapped by `init`, and are always 16-byte aligned.
+    unsafe { ptr::write_volatile((base as usize + offset) as *mut u32, value) }
+}
+
+/// Converts nanoseconds into timer ticks, clamped to what the counter can hold. If the delay
+/// is clamped the timer fires early, and the timer subsystem simply reprograms it.
+fn ns_to_ticks(ns: u64) -> u32 {
+    let frequency = TIMER_FREQUENCY.load(Ordering::Relaxed) as u128;
+    let ticks = ns as u128 * frequency / NANOS_PER_SEC as u128;
+    ticks.max(1).min(u32::MAX as u128) as u32
+}
+
+pub struct LocalApicTimer;
+
+impl ClockEvent for LocalApicTimer {
+    fn name(&self) -> &'static str {
+        if TSC_DEADLINE.load(Ordering::Relaxed) {
+            "lapic-tsc-deadline"
+        } else {
+            "lapic"
+        }
+    }
+
+    fn rating(&self) -> u32 {
+        300
+    }
+
+    fn features(&self) -> EventFeatures {
+        EventFeatures::ONE_SHOT | EventFeatures::PERIODIC
+    }
+
+    fn set_periodic(&self, period_ns: u64) {
+        write(TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+        write(LVT_TIMER, TIMER_VECTOR as u32 | TIMER_MODE_PERIODIC);
+        write(TIMER_INITIAL_COUNT, ns_to_ticks(period_ns));
+    }
+
+    fn set_oneshot(&self, delta_ns: u64) -> Result<(), &'static str> {
+        if TSC_DEADLINE.load(Ordering::Relaxed) {
+            let tsc_frequency = tsc_frequency().unwrap_or(0) as u128;
+            let delta = delta_ns as u128 * tsc_frequency / NANOS_PER_SEC as u128;
+
+            write(LVT_TIMER, TIMER_VECTOR as u32 | TIMER_MODE_TSC_DEADLINE);
+
+            // SAFETY: The CPU supports TSC-deadline mode, so it has this MSR.
+            unsafe { IA32_TSC_DEADLINE.write(rdtsc() + delta.max(1) as u64) };
+        } else {
+            write(TIMER_DIVIDE, TIMER_DIVIDE_BY_16);
+            write(LVT_TIMER, TIMER_VECTOR as u32);
+            write(TIMER_INITIAL_COUNT, ns_to_ticks(delta_ns));
+        }
+        Ok(())
+    }
+
+    fn stop(&self) {
+        write(LVT_TIMER, LVT_MASKED);
+        write(TIMER_INITIAL_COUNT, 0);
+    }
+}
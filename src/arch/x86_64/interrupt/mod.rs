@@ -1,20 +1,27 @@
 mod handler;
 pub mod idt;
+pub mod irq;
+pub mod lapic;
+pub mod msi;
+pub mod pic;
 
 use lazy_static::lazy_static;
 
 use super::gdt::DOUBLE_FAULT_STACK_INDEX;
-use crate::println;
-use handler::exception;
+use crate::debug;
+use handler::{exception, irq as irq_handler, lapic as lapic_handler, msi as msi_handler};
 use idt::{Descriptor, Idt};
 
+pub use handler::{InterruptStackFrame, SavedRegisters};
+
 lazy_static! {
     pub static ref IDT: Idt = {
-        println!("Making idt...");
+        debug!("Making idt...");
         let mut idt = Idt::new();
 
         // TODO: finish filling up whole IDT with handlers
         idt.div_by_zero = Descriptor::interrupt(exception::div_by_zero);
+        idt.debug = Descriptor::interrupt(exception::debug);
         idt.breakpoint = Descriptor::interrupt(exception::breakpoint);
         idt.invalid_opcode = Descriptor::interrupt(exception::invalid_opcode);
 
@@ -30,6 +37,40 @@ lazy_static! {
         idt.general_protection_fault = Descriptor::interrupt(exception::general_protection_fault);
         idt.page_fault = Descriptor::interrupt(exception::page_fault);
 
+        // hardware IRQs come right after the exceptions
+        let irq_start = (irq::IRQ_BASE - 32) as usize;
+        for (index, handler) in irq_handler::HANDLERS.iter().enumerate() {
+            idt.descriptors[irq_start + index] = Descriptor::interrupt(*handler);
+        }
+
+        idt.descriptors[(lapic::TIMER_VECTOR - 32) as usize] =
+            Descriptor::interrupt(lapic_handler::timer);
+        idt.descriptors[(lapic::SPURIOUS_VECTOR - 32) as usize] =
+            Descriptor::interrupt(lapic_handler::spurious);
+
+        // message signalled interrupts get a pool of their own, handed out to devices
+        let msi_start = (msi::MSI_BASE - 32) as usize;
+        for (index, handler) in msi_handler::HANDLERS.iter().enumerate() {
+            idt.descriptors[msi_start + index] = Descriptor::interrupt(*handler);
+        }
+
         idt
     };
 }
+
+#[cfg(feature = "kernel-test")]
+mod kernel_test {
+    use juntos_macros::kernel_test;
+
+    #[kernel_test]
+    fn breakpoint_returns() {
+        // SAFETY: The breakpoint handler returns straight back here.
+        unsafe { asm!("int3") };
+    }
+
+    #[kernel_test(should_panic)]
+    fn page_fault_panics() {
+        // SAFETY: This is expected to fault, as nothing is mapped here.
+        unsafe { core::ptr::read_volatile(0xFFFF_DEAD_0000_0000 as *const u64) };
+    }
+}
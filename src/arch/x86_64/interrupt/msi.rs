@@ -0,0 +1,149 @@
+//! Vectors for message signalled interrupts (MSI and MSI-X).
+//! A device raises these by writing a message to the local APIC, rather than through an IRQ
+//! line. The IDT has a stub for every vector in the pool, which looks up the handler here.
+
+use super::lapic;
+use crate::sync::IrqSafeMutex;
+
+/// The first vector of the pool, after the legacy IRQs and the local APIC timer. Aligned so that
+/// blocks of up to `NUM_VECTORS` vectors can be allocated for multiple message MSI.
+pub const MSI_BASE: u8 = 64;
+
+/// The number of vectors in the pool
+pub const NUM_VECTORS: usize = 32;
+
+/// The address range messages are written to, where bits 12-19 are the destination APIC ID
+const MESSAGE_ADDRESS: u64 = 0xFEE0_0000;
+const DESTINATION_SHIFT: u64 = 12;
+
+static VECTORS: IrqSafeMutex<Vectors> = IrqSafeMutex::new(Vectors {
+    allocated: 0,
+    handlers: [None; NUM_VECTORS],
+});
+
+struct Vectors {
+    /// A bit for each vector in the pool, set if it is allocated
+    allocated: u32,
+    handlers: [Option<fn()>; NUM_VECTORS],
+}
+
+/// What a device must write, and where, to raise a vector
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Message {
+    pub address: u64,
+    pub data: u32,
+}
+
+impl Message {
+    /// The message raising `vector` on the local APIC with ID `apic_id`, as a fixed, edge
+    /// triggered interrupt
+    fn new(vector: u8, apic_id: u8) -> Message {
+        Message {
+            address: MESSAGE_ADDRESS | (apic_id as u64) << DESTINATION_SHIFT,
+            data: vector as u32,
+        }
+    }
+}
+
+/// Finds the first free block of `count` vectors, aligned to `count`, which must be a power of 2
+fn find_block(allocated: u32, count: usize) -> Option<usize> {
+    let mask = match count {
+        NUM_VECTORS => u32::MAX,
+        _ => (1 << count) - 1,
+    };
+    (0..NUM_VECTORS)
+        .step_by(count)
+        .find(|start| allocated & mask << start == 0)
+}
+
+/// Allocates a block of `count` vectors, aligned to `count`, as multiple message MSI needs.
+/// Returns the first vector, or `None` if there isn't a big enough block free.
+pub fn allocate(count: usize) -> Option<u8> {
+    assert!(
+        count.is_power_of_two() && count <= NUM_VECTORS,
+        "Invalid MSI vector count!"
+    );
+
+    let mut vectors = VECTORS.lock();
+    let start = find_block(vectors.allocated, count)?;
+    for index in start..start + count {
+        vectors.allocated |= 1 << index;
+    }
+    Some(MSI_BASE + start as u8)
+}
+
+/// Frees `vector` and removes its handler. The device must no longer raise it.
+pub fn free(vector: u8) {
+    let index = index(vector);
+
+    let mut vectors = VECTORS.lock();
+    vectors.allocated &= !(1 << index);
+    vectors.handlers[index] = None;
+}
+
+/// Registers `handler` to be called whenever the allocated `vector` is raised.
+/// Handlers run with interrupts disabled, and do not need to signal the end of the interrupt.
+pub fn register(vector: u8, handler: fn()) {
+    let index = index(vector);
+
+    let mut vectors = VECTORS.lock();
+    assert!(
+        vectors.allocated & 1 << index != 0,
+        "MSI vector isn't allocated!"
+    );
+    vectors.handlers[index] = Some(handler);
+}
+
+/// The message raising `vector` on this CPU. The local APIC must be enabled.
+pub fn message(vector: u8) -> Message {
+    Message::new(vector, lapic::id())
+}
+
+fn index(vector: u8) -> usize {
+    assert!(
+        (MSI_BASE..MSI_BASE + NUM_VECTORS as u8).contains(&vector),
+        "Invalid MSI vector!"
+    );
+    (vector - MSI_BASE) as usize
+}
+
+/// Called by the MSI stubs in the IDT, with the index of the vector in the pool
+pub(super) fn dispatch(index: u8) {
+    // copy the handler out, so the handler itself is free to allocate or free vectors
+    let handler = VECTORS.lock().handlers[index as usize];
+    if let Some(handler) = handler {
+        handler();
+    }
+
+    lapic::end_of_interrupt();
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn find_aligned_blocks() {
+        assert_eq!(find_block(0, 1), Some(0));
+        assert_eq!(find_block(0b1, 1), Some(1));
+        // blocks are aligned to their size
+        assert_eq!(find_block(0b1, 2), Some(2));
+        assert_eq!(find_block(0b0100, 4), Some(4));
+        assert_eq!(find_block(0x0000_00FF, 8), Some(8));
+        assert_eq!(find_block(0, NUM_VECTORS), Some(0));
+        assert_eq!(find_block(1 << 31, NUM_VECTORS), None);
+        assert_eq!(find_block(0x7FFF_FFFF, 1), Some(31));
+        assert_eq!(find_block(u32::MAX, 1), None);
+    }
+
+    #[test]
+    fn messages() {
+        assert_eq!(
+            Message::new(MSI_BASE + 3, 2),
+            Message {
+                address: 0xFEE0_2000,
+                data: 67,
+            }
+        );
+    }
+}
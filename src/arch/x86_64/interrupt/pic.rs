@@ -0,0 +1,163 @@
+//! Driver for the legacy 8259 Programmable Interrupt Controllers.
+//! There are two of them chained together, the secondary PIC raising its interrupts through
+//! IRQ2 of the primary one.
+
+use crate::arch::instructions::port::{io_wait, Port};
+use crate::sync::IrqSafeMutex;
+
+/// Initialization command word 1: start initialization, and expect ICW4
+const ICW1_INIT: u8 = 0x11;
+
+/// Initialization command word 4: use 8086 mode
+const ICW4_8086: u8 = 0x01;
+
+/// Signals the end of an interrupt
+const COMMAND_EOI: u8 = 0x20;
+
+/// Makes the next read from the command port return the In-Service Register
+const COMMAND_READ_ISR: u8 = 0x0B;
+
+/// The IRQ on the primary PIC that the secondary is chained to
+const CASCADE_IRQ: u8 = 2;
+
+static PICS: IrqSafeMutex<ChainedPics> = IrqSafeMutex::new(ChainedPics {
+    primary: Pic::new(0x20, 0x21),
+    secondary: Pic::new(0xA0, 0xA1),
+});
+
+struct Pic {
+    command: Port<u8>,
+    data: Port<u8>,
+}
+
+impl Pic {
+    const fn new(command: u16, data: u16) -> Pic {
+        Pic {
+            command: Port::new(command),
+            data: Port::new(data),
+        }
+    }
+
+    unsafe fn in_service(&mut self) -> u8 {
+        self.command.write(COMMAND_READ_ISR);
+        self.command.read()
+    }
+}
+
+struct ChainedPics {
+    primary: Pic,
+    secondary: Pic,
+}
+
+/// Remaps the PICs so IRQs 0-15 raise vectors `offset` to `offset + 15`, and masks every IRQ.
+/// This must be done before interrupts are enabled, as by default the PICs raise vectors that
+/// overlap with CPU exceptions.
+///
+/// # Safety
+/// The caller must ensure there are IDT entries for the new vectors before unmasking any IRQs.
+pub unsafe fn init(offset: u8) {
+    let mut pics = PICS.lock();
+    let ChainedPics { primary, secondary } = &mut *pics;
+
+    primary.command.write(ICW1_INIT);
+    io_wait();
+    secondary.command.write(ICW1_INIT);
+    io_wait();
+
+    // ICW2: vector offsets
+    primary.data.write(offset);
+    io_wait();
+    secondary.data.write(offset + 8);
+    io_wait();
+
+    // ICW3: tell the primary which IRQ the secondary is on, and tell the secondary its identity
+    primary.data.write(1 << CASCADE_IRQ);
+    io_wait();
+    secondary.data.write(CASCADE_IRQ);
+    io_wait();
+
+    primary.data.write(ICW4_8086);
+    io_wait();
+    secondary.data.write(ICW4_8086);
+    io_wait();
+
+    // mask everything but the cascade, drivers unmask what they need
+    primary.data.write(!(1 << CASCADE_IRQ));
+    secondary.data.write(0xFF);
+}
+
+/// Masks every IRQ on both PICs. Used when the PICs are replaced by another interrupt controller.
+pub fn disable() {
+    let mut pics = PICS.lock();
+
+    // SAFETY: Masking interrupts cannot violate memory safety.
+    unsafe {
+        pics.primary.data.write(0xFF);
+        pics.secondary.data.write(0xFF);
+    }
+}
+
+pub fn mask(irq: u8) {
+    set_masked(irq, true);
+}
+
+pub fn unmask(irq: u8) {
+    set_masked(irq, false);
+}
+
+fn set_masked(irq: u8, masked: bool) {
+    let mut pics = PICS.lock();
+    let (pic, line) = if irq < 8 {
+        (&mut pics.primary, irq)
+    } else {
+        (&mut pics.secondary, irq - 8)
+    };
+
+    // SAFETY: Reading and writing the interrupt mask register has no side effects other than
+    //         masking interrupts.
+    unsafe {
+        let current = pic.data.read();
+        let mask = if masked {
+            current | (1 << line)
+        } else {
+            current & !(1 << line)
+        };
+        pic.data.write(mask);
+    }
+}
+
+/// Returns true if `irq` is a spurious interrupt, i.e. it was raised but is not actually in
+/// service. Only IRQ7 and IRQ15 can be spurious. A spurious IRQ15 still needs the primary PIC
+/// to be acknowledged, as it did see the cascade IRQ.
+pub fn is_spurious(irq: u8) -> bool {
+    if irq != 7 && irq != 15 {
+        return false;
+    }
+
+    let mut pics = PICS.lock();
+
+    // SAFETY: Reading the ISR has no side effects.
+    unsafe {
+        if irq == 7 {
+            pics.primary.in_service() & (1 << 7) == 0
+        } else if pics.secondary.in_service() & (1 << 7) == 0 {
+            pics.primary.command.write(COMMAND_EOI);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Signals the end of an interrupt for `irq`, allowing the PICs to raise it again.
+pub fn end_of_interrupt(irq: u8) {
+    let mut pics = PICS.lock();
+
+    // SAFETY: This must only be called at the end of an interrupt handler, which it is.
+    unsafe {
+        if irq >= 8 {
+            pics.secondary.command.write(COMMAND_EOI);
+        }
+        pics.primary.command.write(COMMAND_EOI);
+    }
+}
@@ -1,9 +1,13 @@
+pub mod device;
 pub mod gdt;
 pub mod instructions;
 pub mod interrupt;
 pub mod paging;
+pub mod watchpoint;
 
-use crate::BootInfo;
+use crate::cmdline::Param;
+use crate::KernelInfo;
+use crate::{info, warn};
 use gdt::GDT;
 use interrupt::IDT;
 use paging::{Page, VirtualAddress, PAGE_SIZE, PAGE_TABLE};
@@ -18,10 +22,14 @@ pub enum PriviledgeLevel {
     RingThree = 3,
 }
 
-pub fn arch_init(stack_info: &BootInfo) {
+pub fn arch_init(stack_info: &KernelInfo) {
     unsafe { GDT.load() };
     unsafe { IDT.load() };
 
+    // SAFETY: The IDT we just loaded has handlers for every IRQ vector.
+    unsafe { interrupt::pic::init(interrupt::irq::IRQ_BASE) };
+    device::serial::init_receive();
+
     // set up a guard page at then end of the stack
     let mut pt = PAGE_TABLE.lock();
     // TODO: refactor this probably.
@@ -36,3 +44,42 @@ pub fn arch_init(stack_info: &BootInfo) {
         mapper.unmap(guard_page).expect("Issue mapping guard page");
     });
 }
+
+pub static NOAPIC_PARAM: Param<bool> = Param::new(
+    "noapic",
+    "don't use the local APIC, so only the legacy PIC and timers are used",
+    false,
+);
+
+/// Architecture specific initialization that needs the frame allocator, i.e. to map device
+/// registers.
+pub fn arch_late_init() {
+    let cpu = instructions::cpuid::CpuInfo::read();
+    info!("CPU: {} ({})", cpu.brand(), cpu.vendor());
+
+    if NOAPIC_PARAM.get() {
+        info!("Local APIC disabled on the command line");
+    } else if !interrupt::lapic::init() {
+        warn!("No local APIC found, falling back to legacy timers");
+    }
+}
+
+#[cfg(feature = "kernel-test")]
+mod kernel_test {
+    use core::ptr;
+    use juntos_macros::kernel_test;
+
+    #[kernel_test(should_panic)]
+    fn stack_overflow_hits_guard_page() {
+        #[allow(unconditional_recursion)]
+        fn overflow(depth: u64) -> u64 {
+            let frame = [depth; 64];
+
+            // SAFETY: Reading our own local, volatile only so the recursion can't be optimized
+            //         out.
+            1 + overflow(unsafe { ptr::read_volatile(&frame[63]) } + 1)
+        }
+
+        overflow(0);
+    }
+}
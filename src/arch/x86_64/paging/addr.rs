@@ -10,14 +10,19 @@ pub struct VirtualAddress(u64);
 #[allow(dead_code)]
 impl VirtualAddress {
     pub fn new(raw: u64) -> VirtualAddress {
-        // ensure canonical by making sure most sig 16 bits are all 0 or 1
         debug_assert!(
-            raw.leading_zeros() > 16 || raw.leading_ones() > 16,
+            VirtualAddress::is_canonical(raw),
             "Attempt to create non-canonical virtual address!"
         );
         VirtualAddress(raw)
     }
 
+    /// Returns true if `raw` is a canonical address, i.e. the most significant 16 bits are all
+    /// copies of bit 47
+    pub fn is_canonical(raw: u64) -> bool {
+        raw.leading_zeros() > 16 || raw.leading_ones() > 16
+    }
+
     pub fn new_truncate(addr: u64) -> VirtualAddress {
         VirtualAddress::new((((addr << 16) as isize) >> 16) as u64)
     }
@@ -12,6 +12,18 @@ impl<'a> Mapper<'a> {
     }
 
     pub fn map<A, B>(&mut self, page: Page, frame: Frame<B>, alloc: A)
+    where
+        A: FrameAllocator,
+        B: FrameAllocator,
+    {
+        // TODO: should actually set flags and stuff based on the frame itself.
+        //       for now, this is fine
+        self.map_with_flags(page, frame, Flags::PRESENT | Flags::WRITE, alloc)
+    }
+
+    /// Maps a page to a given frame with the given entry flags, allocating any page tables
+    /// along the way.
+    pub fn map_with_flags<A, B>(&mut self, page: Page, frame: Frame<B>, flags: Flags, alloc: A)
     where
         A: FrameAllocator,
         B: FrameAllocator,
@@ -27,9 +39,7 @@ impl<'a> Mapper<'a> {
             let l3_table = self.page_table.create_table(l4_idx, alloc);
             let l2_table = l3_table.create_table(l3_idx, alloc);
             let l1_table = l2_table.create_table(l2_idx, alloc);
-            // TODO: should actually set flags and stuff based on the frame itself.
-            //       for now, this is fine
-            l1_table[l1_idx] = Entry::new(&frame, Flags::PRESENT | Flags::WRITE);
+            l1_table[l1_idx] = Entry::new(&frame, flags);
         }
 
         // TODO: Should formalize this better
@@ -3,22 +3,36 @@ mod mapper;
 mod table;
 
 use core::ptr::Unique;
+use core::sync::atomic::{AtomicU64, Ordering};
 use lazy_static::lazy_static;
-use spin::Mutex;
 
 use crate::arch::instructions::tlb;
+use crate::memory::{BootstrapAllocator, Frame, FrameAllocator};
+use crate::sync::IrqSafeMutex;
 pub use addr::*;
 use mapper::*;
+pub use table::Flags as EntryFlags;
 use table::*;
 
 pub const PAGE_SIZE: usize = 4096;
 
 const PAGE_TABLE_RAW: *mut RecursivePageTable = 0xFFFF_FFFF_FFFF_F000 as *mut RecursivePageTable;
 
+/// Flags for mapping memory-mapped device registers, which must not be cached
+pub const MMIO_FLAGS: Flags =
+    Flags::from_bits_truncate(Flags::PRESENT.bits() | Flags::WRITE.bits() | Flags::NO_CACHE.bits());
+
+/// The virtual window that `map_physical` places mappings in. This is the 508th L4 entry, well
+/// clear of both the kernel and the recursive mapping.
+const PHYSICAL_WINDOW_START: u64 = 0xFFFF_FE00_0000_0000;
+const PHYSICAL_WINDOW_END: u64 = 0xFFFF_FE80_0000_0000;
+
+static NEXT_WINDOW_ADDR: AtomicU64 = AtomicU64::new(PHYSICAL_WINDOW_START);
+
 lazy_static! {
-    // A referene to the current page table, protected by a Mutex
-    pub static ref PAGE_TABLE: Mutex<ActivePageTable> = {
-        Mutex::new(ActivePageTable {
+    // A referene to the current page table, protected by an IrqSafeMutex
+    pub static ref PAGE_TABLE: IrqSafeMutex<ActivePageTable> = {
+        IrqSafeMutex::new(ActivePageTable {
             page_table: Unique::new(PAGE_TABLE_RAW).unwrap(),
         })
     };
@@ -82,6 +96,72 @@ impl ActivePageTable {
     }
 }
 
+/// Maps `size` bytes of physical memory starting at `addr` (i.e. device registers or firmware
+/// tables) into the kernel's address space, returning the virtual address `addr` is now at.
+/// The mapping is permanent, and requires the frame allocator to be initialized.
+pub fn map_physical(addr: PhysicalAddress, size: usize, flags: Flags) -> VirtualAddress {
+    let page_size = PAGE_SIZE as u64;
+    let start = addr.as_u64() & !(page_size - 1);
+    let end = (addr.as_u64() + size as u64 + page_size - 1) & !(page_size - 1);
+    let length = (end - start).max(page_size);
+
+    let virtual_start = NEXT_WINDOW_ADDR.fetch_add(length, Ordering::SeqCst);
+    assert!(
+        virtual_start + length <= PHYSICAL_WINDOW_END,
+        "Ran out of space to map physical memory!"
+    );
+
+    let alloc = BootstrapAllocator::get();
+    PAGE_TABLE.lock().modify(|mut mapper| {
+        for offset in (0..length).step_by(PAGE_SIZE) {
+            let page = Page::containing(VirtualAddress::new(virtual_start + offset));
+            let frame = Frame::<BootstrapAllocator>::containing((start + offset) as usize);
+            mapper.map_with_flags(page, frame, flags, alloc);
+        }
+    });
+
+    VirtualAddress::new(virtual_start + (addr.as_u64() - start))
+}
+
+/// Returns true if `addr` is mapped in the active page table. This doesn't take the page table
+/// lock (so it can be used while reporting a fault), but may race with anyone modifying it.
+pub fn is_mapped(addr: VirtualAddress) -> bool {
+    let page = Page::containing(addr);
+
+    // SAFETY: PAGE_TABLE_RAW is the active, recursively mapped L4 table, and we only read it.
+    unsafe {
+        (*PAGE_TABLE_RAW)
+            .get_table(page.level4_page_number())
+            .and_then(|l3_table| l3_table.get_table(page.level3_page_number()))
+            .and_then(|l2_table| l2_table.get_table(page.level2_page_number()))
+            .map_or(false, |l1_table| {
+                l1_table[page.level1_page_number()].is_present()
+            })
+    }
+}
+
+/// Returns true if every byte in `[addr, addr + len)` is mapped in the active page table, and so
+/// can be accessed without faulting. Like `is_mapped`, this doesn't take the page table lock.
+pub fn is_range_mapped(addr: u64, len: usize) -> bool {
+    if len == 0 {
+        return true;
+    }
+
+    let last = match addr.checked_add(len as u64 - 1) {
+        Some(last) => last,
+        None => return false,
+    };
+    if !VirtualAddress::is_canonical(addr) || !VirtualAddress::is_canonical(last) {
+        return false;
+    }
+
+    let page_size = PAGE_SIZE as u64;
+    let first_page = addr & !(page_size - 1);
+    (first_page..=last)
+        .step_by(PAGE_SIZE)
+        .all(|page| VirtualAddress::is_canonical(page) && is_mapped(VirtualAddress::new(page)))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -109,3 +189,28 @@ mod test {
         );
     }
 }
+
+#[cfg(feature = "kernel-test")]
+mod kernel_test {
+    use super::*;
+    use core::ptr;
+    use juntos_macros::kernel_test;
+
+    #[kernel_test]
+    fn map_physical_aliases_frame() {
+        let frame = BootstrapAllocator::get()
+            .alloc()
+            .expect("Out of memory for test frame!");
+        let flags = Flags::PRESENT | Flags::WRITE;
+
+        let first = map_physical(frame.addr(), PAGE_SIZE, flags);
+        let second = map_physical(frame.addr(), PAGE_SIZE, flags);
+        assert_ne!(first.as_u64(), second.as_u64());
+
+        // SAFETY: Both addresses were just mapped to the frame we own.
+        unsafe {
+            ptr::write_volatile(first.as_ptr_mut::<u64>(), 0xDEAD_BEEF);
+            assert_eq!(ptr::read_volatile(second.as_ptr::<u64>()), 0xDEAD_BEEF);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+//! Hardware breakpoints and watchpoints, which trap into the `debug` (#DB) handler when an address
+//! is executed, written or accessed. They are useful for finding what is corrupting a structure,
+//! i.e. a `FixedBitmap` or a page table, without having to step through everything that touches
+//! it:
+//!
+//! ```ignore
+//! let id = watchpoint::watch(&bitmap.bits[3], Condition::Write)?;
+//! ...
+//! watchpoint::clear(id);
+//! ```
+//!
+//! There are only four, one for each of DR0-DR3, which are shared with the GDB stub. They are
+//! only set on the CPU that sets them.
+
+use core::fmt::{self, Display, Formatter};
+use core::mem::size_of;
+use core::ptr;
+
+use super::instructions::registers::debug::{self, Dr6Flags, NUM_BREAKPOINTS};
+use super::interrupt::InterruptStackFrame;
+use super::paging::{is_range_mapped, VirtualAddress};
+use crate::backtrace::Address;
+use crate::sync::IrqSafeMutex;
+
+pub use super::instructions::registers::debug::{Condition, Size};
+
+static WATCHPOINTS: IrqSafeMutex<[Option<Watchpoint>; NUM_BREAKPOINTS]> =
+    IrqSafeMutex::new([None; NUM_BREAKPOINTS]);
+
+/// Who set a watchpoint, which decides who handles it being hit
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Owner {
+    /// Hits are reported by the `debug` handler, and execution carries on
+    Kernel,
+    /// Hits stop in the GDB stub
+    Gdb,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Watchpoint {
+    pub addr: u64,
+    pub condition: Condition,
+    pub size: Size,
+    pub owner: Owner,
+}
+
+impl Watchpoint {
+    pub fn new(addr: u64, condition: Condition, size: Size, owner: Owner) -> Watchpoint {
+        Watchpoint {
+            addr,
+            condition,
+            size,
+            owner,
+        }
+    }
+
+    fn validate(&self) -> Result<(), &'static str> {
+        if !VirtualAddress::is_canonical(self.addr) {
+            Err("Watchpoint address is not canonical")
+        } else if self.addr % self.size.bytes() as u64 != 0 {
+            Err("Watchpoint address is not aligned to its size")
+        } else if self.condition == Condition::Execute && self.size != Size::One {
+            Err("Execute breakpoints must be one byte")
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A watchpoint that has been set, identified by the debug register it uses
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct WatchpointId(usize);
+
+impl WatchpointId {
+    pub fn index(&self) -> usize {
+        self.0
+    }
+}
+
+/// Sets a watchpoint in the first free debug register
+pub fn set(watchpoint: Watchpoint) -> Result<WatchpointId, &'static str> {
+    watchpoint.validate()?;
+
+    let mut watchpoints = WATCHPOINTS.lock();
+    let index = watchpoints
+        .iter()
+        .position(|slot| slot.is_none())
+        .ok_or("All hardware breakpoints are in use")?;
+
+    // SAFETY: Hitting the watchpoint only raises a debug exception, which we handle.
+    unsafe {
+        debug::set_address(index, watchpoint.addr);
+        debug::set_dr7(debug::dr7_enable(
+            debug::dr7(),
+            index,
+            watchpoint.condition,
+            watchpoint.size,
+        ));
+    }
+    watchpoints[index] = Some(watchpoint);
+    Ok(WatchpointId(index))
+}
+
+/// Watches `value` for `condition`, which must be 1, 2, 4 or 8 bytes
+pub fn watch<T>(value: &T, condition: Condition) -> Result<WatchpointId, &'static str> {
+    let size =
+        Size::from_bytes(size_of::<T>()).ok_or("Watched values must be 1, 2, 4 or 8 bytes")?;
+    set(Watchpoint::new(
+        value as *const T as u64,
+        condition,
+        size,
+        Owner::Kernel,
+    ))
+}
+
+/// Removes a watchpoint, freeing its debug register
+pub fn clear(id: WatchpointId) {
+    let mut watchpoints = WATCHPOINTS.lock();
+    // SAFETY: This only disables a watchpoint, and then forgets its address.
+    unsafe {
+        debug::set_dr7(debug::dr7_disable(debug::dr7(), id.0));
+        debug::set_address(id.0, 0);
+    }
+    watchpoints[id.0] = None;
+}
+
+pub fn get(id: WatchpointId) -> Option<Watchpoint> {
+    WATCHPOINTS.lock()[id.0]
+}
+
+/// Returns the first watchpoint that caused a debug exception with `dr6`. Breakpoints reported
+/// for free debug registers are skipped.
+pub fn hit(dr6: Dr6Flags) -> Option<(WatchpointId, Watchpoint)> {
+    let watchpoints = WATCHPOINTS.lock();
+    dr6.breakpoints_hit()
+        .find_map(|index| watchpoints[index].map(|watchpoint| (WatchpointId(index), watchpoint)))
+}
+
+/// A watchpoint being hit, as reported by the `debug` handler
+pub struct WatchpointHit<'a> {
+    id: WatchpointId,
+    watchpoint: Watchpoint,
+    dr6: Dr6Flags,
+    stack_frame: &'a InterruptStackFrame,
+}
+
+impl<'a> WatchpointHit<'a> {
+    pub fn new(
+        id: WatchpointId,
+        watchpoint: Watchpoint,
+        dr6: Dr6Flags,
+        stack_frame: &'a InterruptStackFrame,
+    ) -> WatchpointHit<'a> {
+        WatchpointHit {
+            id,
+            watchpoint,
+            dr6,
+            stack_frame,
+        }
+    }
+
+    /// Reads the watched value, if it is still mapped
+    fn value(&self) -> Option<u64> {
+        let Watchpoint { addr, size, .. } = self.watchpoint;
+        if !is_range_mapped(addr, size.bytes()) {
+            return None;
+        }
+
+        // SAFETY: We just checked it is mapped, and watchpoints are aligned to their size.
+        let value = unsafe {
+            match size {
+                Size::One => ptr::read_volatile(addr as *const u8) as u64,
+                Size::Two => ptr::read_volatile(addr as *const u16) as u64,
+                Size::Four => ptr::read_volatile(addr as *const u32) as u64,
+                Size::Eight => ptr::read_volatile(addr as *const u64),
+            }
+        };
+        Some(value)
+    }
+}
+
+impl<'a> Display for WatchpointHit<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let watchpoint = &self.watchpoint;
+        let rip = Address::new(self.stack_frame.instruction_pointer());
+
+        if watchpoint.condition == Condition::Execute {
+            return write!(
+                f,
+                "BREAKPOINT {} at {} (dr6: {:?})",
+                self.id.0, rip, self.dr6
+            );
+        }
+
+        let access = if watchpoint.condition == Condition::Write {
+            "write"
+        } else {
+            "access"
+        };
+        writeln!(
+            f,
+            "WATCHPOINT {}: {} of {} bytes at {:#x} (dr6: {:?})",
+            self.id.0,
+            access,
+            watchpoint.size.bytes(),
+            watchpoint.addr,
+            self.dr6
+        )?;
+        // data watchpoints trap after the access, so this is the instruction after it
+        write!(f, "  after: {}", rip)?;
+        match self.value() {
+            Some(value) => write!(f, "\n  value: {:#x}", value),
+            None => write!(f, "\n  value: <not mapped>"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn validate_watchpoints() {
+        let watchpoint =
+            |addr, condition, size| Watchpoint::new(addr, condition, size, Owner::Kernel);
+
+        assert!(
+            watchpoint(0xFFFF_8000_0000_1000, Condition::Write, Size::Eight)
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            watchpoint(0xFFFF_8000_0000_1004, Condition::Write, Size::Eight)
+                .validate()
+                .is_err()
+        );
+        assert!(
+            watchpoint(0x0000_8000_0000_0000, Condition::ReadWrite, Size::One)
+                .validate()
+                .is_err()
+        );
+        assert!(
+            watchpoint(0xFFFF_8000_0000_1000, Condition::Execute, Size::Four)
+                .validate()
+                .is_err()
+        );
+    }
+}
+
+#[cfg(feature = "kernel-test")]
+mod kernel_test {
+    use super::*;
+    use juntos_macros::kernel_test;
+
+    static mut WATCHED: [u64; NUM_BREAKPOINTS + 1] = [0; NUM_BREAKPOINTS + 1];
+
+    #[kernel_test]
+    fn uses_every_debug_register() {
+        // SAFETY: Only this test uses WATCHED.
+        let watched = unsafe { &mut WATCHED };
+        let mut ids = [None; NUM_BREAKPOINTS];
+        for (id, value) in ids.iter_mut().zip(watched.iter()) {
+            *id = Some(watch(value, Condition::Write).unwrap());
+        }
+        assert!(watch(&watched[NUM_BREAKPOINTS], Condition::Write).is_err());
+
+        // hits are reported, and execution carries on
+        unsafe { ptr::write_volatile(&mut watched[0], 1) };
+        assert_eq!(watched[0], 1);
+
+        for id in ids.iter().flatten() {
+            clear(*id);
+        }
+        let id = watch(&watched[NUM_BREAKPOINTS], Condition::Write).unwrap();
+        assert_eq!(id.index(), 0);
+        clear(id);
+    }
+
+    #[kernel_test]
+    fn skips_hits_on_free_registers() {
+        // SAFETY: Only this test uses WATCHED.
+        let watched = unsafe { &mut WATCHED };
+        let first = watch(&watched[0], Condition::Write).unwrap();
+        let second = watch(&watched[1], Condition::Write).unwrap();
+        clear(first);
+
+        let dr6 = Dr6Flags::BREAKPOINT_0 | Dr6Flags::BREAKPOINT_1;
+        assert_eq!(hit(dr6).map(|(id, _)| id), Some(second));
+        clear(second);
+        assert_eq!(hit(dr6), None);
+    }
+}
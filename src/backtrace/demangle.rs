@@ -0,0 +1,162 @@
+use core::fmt::{self, Display, Formatter, Write};
+
+/// Displays a symbol name demangled, if it uses Rust's legacy mangling scheme (i.e.
+/// `_ZN4core9panicking5panic17h0123456789abcdefE` becomes `core::panicking::panic`). Any
+/// other name is displayed as is.
+pub struct Demangle<'a>(pub &'a str);
+
+impl<'a> Display for Demangle<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match path_segments(self.0) {
+            Some(segments) => {
+                for (i, segment) in segments.enumerate() {
+                    if i != 0 {
+                        f.write_str("::")?;
+                    }
+                    write_unescaped(f, segment)?;
+                }
+                Ok(())
+            }
+            None => f.write_str(self.0),
+        }
+    }
+}
+
+/// Returns the path segments of a legacy mangled name, without the trailing hash
+fn path_segments(name: &str) -> Option<impl Iterator<Item = &str>> {
+    let segments = Segments {
+        rest: name.strip_prefix("_ZN")?.strip_suffix('E')?,
+    };
+
+    // check the whole name is well formed first, so we never display half of it
+    let mut check = segments.clone();
+    while check.next().is_some() {}
+    if !check.rest.is_empty() {
+        return None;
+    }
+
+    Some(segments.filter(|segment| !is_hash(segment)))
+}
+
+/// The length-prefixed segments of a mangled path. Stops at the first malformed segment.
+#[derive(Clone)]
+struct Segments<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Iterator for Segments<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let (segment, rest) = split_segment(self.rest)?;
+        if segment.is_empty() {
+            return None;
+        }
+        self.rest = rest;
+        Some(segment)
+    }
+}
+
+/// Splits a length-prefixed segment off the front of `s`
+fn split_segment(s: &str) -> Option<(&str, &str)> {
+    let digits = s.bytes().take_while(|b| b.is_ascii_digit()).count();
+    let len: usize = s[..digits].parse().ok()?;
+    let rest = &s[digits..];
+    if len > rest.len() || !rest.is_char_boundary(len) {
+        return None;
+    }
+    Some(rest.split_at(len))
+}
+
+fn is_hash(segment: &str) -> bool {
+    segment.len() == 17
+        && segment.starts_with('h')
+        && segment[1..].bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+/// Writes a segment, replacing the escapes the compiler uses for characters that aren't
+/// allowed in symbol names
+fn write_unescaped(f: &mut Formatter<'_>, mut segment: &str) -> fmt::Result {
+    // a leading underscore is added to segments that would otherwise start with an escape
+    if segment.starts_with("_$") {
+        segment = &segment[1..];
+    }
+
+    while !segment.is_empty() {
+        if let Some(rest) = segment.strip_prefix("..") {
+            f.write_str("::")?;
+            segment = rest;
+        } else if let Some(rest) = segment.strip_prefix('$') {
+            match rest.find('$') {
+                Some(end) => {
+                    let escape = &rest[..end];
+                    match unescape(escape) {
+                        Some(c) => f.write_char(c)?,
+                        None => write!(f, "${}$", escape)?,
+                    }
+                    segment = &rest[end + 1..];
+                }
+                None => {
+                    f.write_str(segment)?;
+                    break;
+                }
+            }
+        } else {
+            let end = segment[1..]
+                .find(&['$', '.'][..])
+                .map_or(segment.len(), |end| end + 1);
+            f.write_str(&segment[..end])?;
+            segment = &segment[end..];
+        }
+    }
+
+    Ok(())
+}
+
+fn unescape(escape: &str) -> Option<char> {
+    let c = match escape {
+        "SP" => '@',
+        "BP" => '*',
+        "RF" => '&',
+        "LT" => '<',
+        "GT" => '>',
+        "LP" => '(',
+        "RP" => ')',
+        "C" => ',',
+        _ => {
+            let code = u32::from_str_radix(escape.strip_prefix('u')?, 16).ok()?;
+            return core::char::from_u32(code);
+        }
+    };
+    Some(c)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn demangle_legacy_names() {
+        assert_eq!(
+            format!(
+                "{}",
+                Demangle("_ZN4core9panicking5panic17h0123456789abcdefE")
+            ),
+            "core::panicking::panic"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                Demangle("_ZN56_$LT$juntos..vga..Writer$u20$as$u20$core..fmt..Write$GT$9write_str17h00000000000000ffE")
+            ),
+            "<juntos::vga::Writer as core::fmt::Write>::write_str"
+        );
+    }
+
+    #[test]
+    fn other_names_unchanged() {
+        assert_eq!(format!("{}", Demangle("kernel_main")), "kernel_main");
+        assert_eq!(format!("{}", Demangle("_ZN4core")), "_ZN4core");
+        assert_eq!(format!("{}", Demangle("_ZN99coreE")), "_ZN99coreE");
+    }
+}
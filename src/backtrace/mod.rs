@@ -0,0 +1,269 @@
+//! Symbolized stack traces.
+//!
+//! The kernel is built with frame pointers, so every stack frame begins with the caller's saved
+//! `rbp` followed by the return address, forming a linked list up the stack. Return addresses
+//! are resolved to function names with the ELF symbol table that the bootloader loads along
+//! with the kernel.
+
+mod demangle;
+mod symbols;
+
+pub use demangle::Demangle;
+pub use symbols::{Symbol, SymbolTable};
+
+use core::fmt::{self, Display, Formatter};
+use core::mem::size_of;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::arch::instructions::registers;
+use crate::arch::paging::{is_range_mapped, map_physical, EntryFlags, PhysicalAddress};
+use crate::multiboot::tag::elf_symbols::{ElfSymbols, SectionType};
+use crate::println;
+
+/// The most frames printed, in case the stack is corrupted into a loop
+const MAX_DEPTH: usize = 64;
+
+/// Frame pointers below the kernel's half of the address space can only be garbage
+const KERNEL_SPACE_START: usize = 0xFFFF_8000_0000_0000;
+
+/// Where the symbol and string tables are mapped, set by `init`. Zero until then.
+static SYMTAB_ADDR: AtomicUsize = AtomicUsize::new(0);
+static SYMTAB_SIZE: AtomicUsize = AtomicUsize::new(0);
+static STRTAB_ADDR: AtomicUsize = AtomicUsize::new(0);
+static STRTAB_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+/// Where the bootloader loaded the kernel's symbol and string tables in physical memory
+#[derive(Debug, Copy, Clone)]
+pub struct SymbolSections {
+    symtab: PhysicalAddress,
+    symtab_size: usize,
+    strtab: PhysicalAddress,
+    strtab_size: usize,
+}
+
+impl SymbolSections {
+    /// Finds the symbol table in the kernel's section headers, along with its string table.
+    /// Returns None if the kernel was stripped.
+    pub fn find(elf_symbols: &ElfSymbols) -> Option<SymbolSections> {
+        let symtab = elf_symbols
+            .sections()
+            .find(|section| section.section_type() == SectionType::SymbolTable)?;
+        let strtab = elf_symbols.section(symtab.link())?;
+
+        // sections the bootloader didn't load have no address
+        if symtab.addr() == 0 || strtab.addr() == 0 {
+            return None;
+        }
+
+        Some(SymbolSections {
+            symtab: PhysicalAddress::new(symtab.addr()),
+            symtab_size: symtab.size() as usize,
+            strtab: PhysicalAddress::new(strtab.addr()),
+            strtab_size: strtab.size() as usize,
+        })
+    }
+
+    /// Returns the end of whichever table was loaded higher in memory
+    pub fn end(&self) -> PhysicalAddress {
+        let symtab_end = self.symtab.add(self.symtab_size as u64);
+        let strtab_end = self.strtab.add(self.strtab_size as u64);
+        PhysicalAddress::new(symtab_end.as_u64().max(strtab_end.as_u64()))
+    }
+}
+
+/// Maps the symbol tables, so backtraces can be symbolized. The frame allocator must be
+/// initialized, and must not have been given the tables' memory.
+pub fn init(sections: &SymbolSections) {
+    let flags = EntryFlags::PRESENT | EntryFlags::NO_EXECUTE;
+    let symtab = map_physical(sections.symtab, sections.symtab_size, flags);
+    let strtab = map_physical(sections.strtab, sections.strtab_size, flags);
+
+    SYMTAB_SIZE.store(sections.symtab_size, Ordering::SeqCst);
+    STRTAB_ADDR.store(strtab.as_usize(), Ordering::SeqCst);
+    STRTAB_SIZE.store(sections.strtab_size, Ordering::SeqCst);
+    // stored last, as a non-zero address marks the table as ready
+    SYMTAB_ADDR.store(symtab.as_usize(), Ordering::SeqCst);
+}
+
+/// Returns the kernel's symbol table, if `init` found one
+pub fn symbol_table() -> Option<SymbolTable<'static>> {
+    let symtab = SYMTAB_ADDR.load(Ordering::SeqCst);
+    if symtab == 0 {
+        return None;
+    }
+
+    // SAFETY: `init` mapped both tables permanently, and stored their addresses and sizes
+    //         before marking them ready.
+    unsafe {
+        Some(SymbolTable::from_raw(
+            symtab as *const u8,
+            SYMTAB_SIZE.load(Ordering::SeqCst),
+            STRTAB_ADDR.load(Ordering::SeqCst) as *const u8,
+            STRTAB_SIZE.load(Ordering::SeqCst),
+        ))
+    }
+}
+
+/// Iterates over the return addresses on the stack, walking the frame pointer chain.
+pub struct Frames {
+    frame_pointer: usize,
+    depth: usize,
+}
+
+impl Frames {
+    /// Starts walking from the frame `frame_pointer` points to
+    pub fn new(frame_pointer: usize) -> Frames {
+        Frames {
+            frame_pointer,
+            depth: 0,
+        }
+    }
+}
+
+impl Iterator for Frames {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        // the boot code clears rbp before calling into rust, which ends the chain
+        if self.depth >= MAX_DEPTH
+            || self.frame_pointer < KERNEL_SPACE_START
+            || self.frame_pointer % 8 != 0
+            || !is_range_mapped(self.frame_pointer as u64, 2 * size_of::<usize>())
+        {
+            return None;
+        }
+
+        // SAFETY: Every frame starts with the caller's rbp and the return address, so this is
+        //         safe as long as the stack isn't corrupted. We can't check that, but we do
+        //         stop at any frame pointer that can't be on a kernel stack, or isn't mapped.
+        let (caller_frame, return_address) = unsafe {
+            let frame = self.frame_pointer as *const usize;
+            (*frame, *frame.add(1))
+        };
+
+        if return_address == 0 {
+            return None;
+        }
+
+        self.frame_pointer = caller_frame;
+        self.depth += 1;
+        Some(return_address)
+    }
+}
+
+/// Displays a code address along with the function it is in, i.e.
+/// `0xffff800000112345 <juntos::kernel_main+0x45>`
+#[derive(Debug, Copy, Clone)]
+pub struct Address {
+    addr: usize,
+    is_return_address: bool,
+}
+
+impl Address {
+    pub fn new(addr: usize) -> Address {
+        Address {
+            addr,
+            is_return_address: false,
+        }
+    }
+
+    /// A return address points just past the call, which may be past the end of the calling
+    /// function (i.e. when calling a function that never returns), so it is looked up as the
+    /// call instruction instead.
+    pub fn return_address(addr: usize) -> Address {
+        Address {
+            addr,
+            is_return_address: true,
+        }
+    }
+}
+
+impl Display for Address {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#018x}", self.addr)?;
+
+        let adjust = self.is_return_address as u64;
+        let symbol = symbol_table().and_then(|table| table.lookup(self.addr as u64 - adjust));
+        if let Some(symbol) = symbol {
+            write!(
+                f,
+                " <{}+{:#x}>",
+                Demangle(symbol.name),
+                symbol.offset + adjust
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Prints a backtrace of the caller's stack
+#[inline(always)]
+pub fn print() {
+    print_from(registers::frame_pointer());
+}
+
+/// Prints a backtrace of the stack, starting from the frame `frame_pointer` points to
+pub fn print_from(frame_pointer: usize) {
+    println!("{}", Backtrace::new(frame_pointer));
+}
+
+/// Displays every return address on a stack, one per line
+#[derive(Debug, Copy, Clone)]
+pub struct Backtrace {
+    instruction_pointer: Option<usize>,
+    frame_pointer: usize,
+}
+
+impl Backtrace {
+    /// The stack from the frame `frame_pointer` points to
+    pub fn new(frame_pointer: usize) -> Backtrace {
+        Backtrace {
+            instruction_pointer: None,
+            frame_pointer,
+        }
+    }
+
+    /// The stack of interrupted code, where `instruction_pointer` is where it was interrupted,
+    /// which is shown as the first frame
+    pub fn interrupted(instruction_pointer: usize, frame_pointer: usize) -> Backtrace {
+        Backtrace {
+            instruction_pointer: Some(instruction_pointer),
+            frame_pointer,
+        }
+    }
+}
+
+impl Display for Backtrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "backtrace:")?;
+        let interrupted = self.instruction_pointer.map(Address::new);
+        let callers = Frames::new(self.frame_pointer).map(Address::return_address);
+        for (i, address) in interrupted.into_iter().chain(callers).enumerate() {
+            write!(f, "\n  {:>2}: {}", i, address)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "kernel-test")]
+mod kernel_test {
+    use super::*;
+    use juntos_macros::kernel_test;
+
+    #[kernel_test]
+    fn symbolizes_kernel_functions() {
+        let table = symbol_table().expect("No kernel symbols");
+        let symbol = table
+            .lookup(crate::kernel_main as usize as u64 + 1)
+            .unwrap();
+        assert_eq!(symbol.name, "kernel_main");
+        assert_eq!(symbol.offset, 1);
+    }
+
+    #[kernel_test]
+    fn walks_frame_pointers() {
+        // at least this test, the harness, and kernel_main are on the stack
+        assert!(Frames::new(registers::frame_pointer()).count() >= 3);
+    }
+}
@@ -0,0 +1,137 @@
+use core::mem::size_of;
+use core::{slice, str};
+
+/// The symbol type of a function, in the low nibble of `Elf64Sym::info`
+const STT_FUNC: u8 = 2;
+
+/// An entry in an ELF64 `.symtab` section
+#[allow(dead_code)]
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+struct Elf64Sym {
+    /// The offset (in bytes) in the string table to find the symbol name string
+    name_offset: u32,
+    info: u8,
+    other: u8,
+    section_index: u16,
+    value: u64,
+    size: u64,
+}
+
+impl Elf64Sym {
+    fn is_function(&self) -> bool {
+        self.info & 0xF == STT_FUNC
+    }
+
+    /// Returns true if `addr` could be within this symbol. Symbols without a size are assumed
+    /// to extend until the next one.
+    fn may_contain(&self, addr: u64) -> bool {
+        self.value <= addr && (self.size == 0 || addr - self.value < self.size)
+    }
+}
+
+/// A resolved function name, and how far into the function an address was
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Symbol<'a> {
+    pub name: &'a str,
+    pub offset: u64,
+}
+
+/// The kernel's `.symtab` section, along with the `.strtab` section its names are in
+#[derive(Copy, Clone)]
+pub struct SymbolTable<'a> {
+    symbols: &'a [Elf64Sym],
+    strings: &'a [u8],
+}
+
+impl<'a> SymbolTable<'a> {
+    /// # Safety
+    /// `symtab` must point to `symtab_size` bytes of ELF64 symbols, and `strtab` to
+    /// `strtab_size` bytes of the string table they refer to. Both must stay valid for `'a`.
+    pub unsafe fn from_raw(
+        symtab: *const u8,
+        symtab_size: usize,
+        strtab: *const u8,
+        strtab_size: usize,
+    ) -> SymbolTable<'a> {
+        SymbolTable {
+            symbols: slice::from_raw_parts(
+                symtab as *const Elf64Sym,
+                symtab_size / size_of::<Elf64Sym>(),
+            ),
+            strings: slice::from_raw_parts(strtab, strtab_size),
+        }
+    }
+
+    /// Returns the function `addr` is in, if there is one
+    pub fn lookup(&self, addr: u64) -> Option<Symbol<'a>> {
+        let symbol = self
+            .symbols
+            .iter()
+            .filter(|symbol| symbol.is_function() && symbol.may_contain(addr))
+            .max_by_key(|symbol| symbol.value)?;
+
+        Some(Symbol {
+            name: self.name(symbol),
+            offset: addr - symbol.value,
+        })
+    }
+
+    fn name(&self, symbol: &Elf64Sym) -> &'a str {
+        let start = (symbol.name_offset as usize).min(self.strings.len());
+        let bytes = &self.strings[start..];
+        let len = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+        str::from_utf8(&bytes[..len]).unwrap_or("<invalid>")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const STRINGS: &[u8] = b"\0first\0second\0data\0";
+
+    fn function(name_offset: u32, value: u64, size: u64) -> Elf64Sym {
+        Elf64Sym {
+            name_offset,
+            info: STT_FUNC,
+            other: 0,
+            section_index: 1,
+            value,
+            size,
+        }
+    }
+
+    fn table(symbols: &[Elf64Sym]) -> SymbolTable<'_> {
+        SymbolTable {
+            symbols,
+            strings: STRINGS,
+        }
+    }
+
+    #[test]
+    fn lookup_finds_containing_function() {
+        let symbols = [function(1, 0x1000, 0x100), function(7, 0x1100, 0x80)];
+        let table = table(&symbols);
+
+        let symbol = table.lookup(0x1010).unwrap();
+        assert_eq!(symbol.name, "first");
+        assert_eq!(symbol.offset, 0x10);
+
+        assert_eq!(table.lookup(0x1100).unwrap().name, "second");
+        assert_eq!(table.lookup(0x0FFF), None);
+        assert_eq!(table.lookup(0x1180), None);
+    }
+
+    #[test]
+    fn lookup_ignores_non_functions() {
+        let mut data = function(14, 0x1000, 0x1000);
+        data.info = 1;
+        let symbols = [data, function(1, 0x800, 0)];
+
+        // sizeless symbols extend until the next one
+        let symbol = table(&symbols).lookup(0x1200).unwrap();
+        assert_eq!(symbol.name, "first");
+        assert_eq!(symbol.offset, 0xA00);
+    }
+}
@@ -0,0 +1,353 @@
+//! A copy of everything the kernel uses from the multiboot2 info.
+//!
+//! The bootloader leaves its info in memory the frame allocator is given, so it is copied into the
+//! kernel's own memory before the allocator starts. Nothing reads the original afterwards, and its
+//! pages can be handed out like any other.
+
+use core::fmt::{self, Debug, Formatter};
+use core::str;
+use spin::Once;
+
+use crate::acpi::{Rsdp, Xsdp};
+use crate::backtrace::SymbolSections;
+use crate::multiboot::tag::framebuffer::{FramebufferType, PaletteColor, RgbLayout};
+use crate::multiboot::tag::memory_map::{Entry, EntryType};
+use crate::multiboot::tag::FramebufferInfo;
+use crate::multiboot::Multiboot2Info;
+use crate::smbios::EntryPoint;
+use crate::warn;
+
+/// The most memory map entries that are kept, any more are ignored
+const MAX_MEMORY_AREAS: usize = 64;
+
+/// The longest command line kept, anything after is dropped
+const MAX_COMMAND_LINE: usize = 1024;
+
+/// The longest bootloader name kept, anything after is dropped
+const MAX_BOOTLOADER_NAME: usize = 64;
+
+/// The most modules that are kept, any more are ignored
+const MAX_MODULES: usize = 8;
+
+/// The longest module command line kept, anything after is dropped
+const MAX_MODULE_COMMAND_LINE: usize = 128;
+
+/// An indexed framebuffer has at most 256 colors
+const MAX_PALETTE_COLORS: usize = 256;
+
+static BOOT_INFO: Once<BootInfo> = Once::new();
+
+pub struct BootInfo {
+    memory_areas: [Entry; MAX_MEMORY_AREAS],
+    memory_areas_len: usize,
+    command_line: [u8; MAX_COMMAND_LINE],
+    command_line_len: usize,
+    bootloader_name: [u8; MAX_BOOTLOADER_NAME],
+    bootloader_name_len: usize,
+    modules: [Module; MAX_MODULES],
+    modules_len: usize,
+    framebuffer: Option<Framebuffer>,
+    rsdp: Option<Rsdp>,
+    xsdp: Option<Xsdp>,
+    symbol_sections: Option<SymbolSections>,
+    efi_system_table: Option<u64>,
+    smbios_entry_point: Option<EntryPoint>,
+}
+
+impl BootInfo {
+    fn new(multiboot_info: &Multiboot2Info) -> BootInfo {
+        let mut memory_areas = [Entry::new(0, 0, EntryType::Reserved); MAX_MEMORY_AREAS];
+        let mut memory_areas_len = 0;
+        for entry in multiboot_info.memory_areas().into_iter().flatten() {
+            if memory_areas_len == MAX_MEMORY_AREAS {
+                warn!("Ignoring memory map entry {:x?}, too many entries", entry);
+                continue;
+            }
+            memory_areas[memory_areas_len] = entry;
+            memory_areas_len += 1;
+        }
+
+        let mut command_line = [0; MAX_COMMAND_LINE];
+        let command_line_len = copy_str(
+            multiboot_info.command_line().unwrap_or(""),
+            &mut command_line,
+        );
+        let mut bootloader_name = [0; MAX_BOOTLOADER_NAME];
+        let bootloader_name_len = copy_str(
+            multiboot_info.bootloader_name().unwrap_or(""),
+            &mut bootloader_name,
+        );
+
+        let mut modules = [Module::empty(); MAX_MODULES];
+        let mut modules_len = 0;
+        for module in multiboot_info.modules() {
+            if modules_len == MAX_MODULES {
+                warn!(
+                    "Ignoring boot module {:?}, too many modules",
+                    module.string()
+                );
+                continue;
+            }
+            modules[modules_len] =
+                Module::new(module.start_addr(), module.end_addr(), module.string());
+            modules_len += 1;
+        }
+
+        BootInfo {
+            memory_areas,
+            memory_areas_len,
+            command_line,
+            command_line_len,
+            bootloader_name,
+            bootloader_name_len,
+            modules,
+            modules_len,
+            framebuffer: multiboot_info.framebuffer_info().map(Framebuffer::new),
+            rsdp: multiboot_info.acpi_old_rsdp().copied(),
+            xsdp: multiboot_info.acpi_new_rsdp().copied(),
+            symbol_sections: multiboot_info.elf_symbols().and_then(SymbolSections::find),
+            efi_system_table: multiboot_info.efi_system_table(),
+            smbios_entry_point: multiboot_info
+                .smbios_tables()
+                .and_then(|smbios| EntryPoint::parse(smbios.tables())),
+        }
+    }
+
+    /// The memory map, from the UEFI firmware if the bootloader passed its map
+    pub fn memory_areas(&self) -> &[Entry] {
+        &self.memory_areas[..self.memory_areas_len]
+    }
+
+    /// The command line the kernel was booted with, or an empty string if there wasn't one
+    pub fn command_line(&self) -> &str {
+        // SAFETY: This was copied from a str, and cut at a char boundary.
+        unsafe { str::from_utf8_unchecked(&self.command_line[..self.command_line_len]) }
+    }
+
+    /// The name of the bootloader, if it gave one
+    pub fn bootloader_name(&self) -> Option<&str> {
+        // SAFETY: This was copied from a str, and cut at a char boundary.
+        let name =
+            unsafe { str::from_utf8_unchecked(&self.bootloader_name[..self.bootloader_name_len]) };
+        Some(name).filter(|name| !name.is_empty())
+    }
+
+    /// Every module the bootloader loaded along with the kernel
+    pub fn modules(&self) -> &[Module] {
+        &self.modules[..self.modules_len]
+    }
+
+    pub fn framebuffer(&self) -> Option<&Framebuffer> {
+        self.framebuffer.as_ref()
+    }
+
+    /// The copy of the ACPI 1.0 RSDP, if the bootloader passed a valid one
+    pub fn rsdp(&self) -> Option<&Rsdp> {
+        self.rsdp.as_ref()
+    }
+
+    /// The copy of the ACPI 2.0+ RSDP, if the bootloader passed a valid one
+    pub fn xsdp(&self) -> Option<&Xsdp> {
+        self.xsdp.as_ref()
+    }
+
+    /// Where the kernel's symbol tables were loaded, if it wasn't stripped
+    pub fn symbol_sections(&self) -> Option<&SymbolSections> {
+        self.symbol_sections.as_ref()
+    }
+
+    /// The physical address of the UEFI system table, if we were booted from UEFI
+    pub fn efi_system_table(&self) -> Option<u64> {
+        self.efi_system_table
+    }
+
+    /// The copy of the SMBIOS entry point, if the bootloader passed a valid one
+    pub fn smbios_entry_point(&self) -> Option<&EntryPoint> {
+        self.smbios_entry_point.as_ref()
+    }
+}
+
+/// A module the bootloader loaded, i.e. an initrd
+#[derive(Debug, Copy, Clone)]
+pub struct Module {
+    start_addr: u64,
+    end_addr: u64,
+    command_line: [u8; MAX_MODULE_COMMAND_LINE],
+    command_line_len: usize,
+}
+
+impl Module {
+    fn empty() -> Module {
+        Module::new(0, 0, "")
+    }
+
+    fn new(start_addr: u64, end_addr: u64, string: &str) -> Module {
+        let mut command_line = [0; MAX_MODULE_COMMAND_LINE];
+        let command_line_len = copy_str(string, &mut command_line);
+        Module {
+            start_addr,
+            end_addr,
+            command_line,
+            command_line_len,
+        }
+    }
+
+    /// The physical address the module was loaded at
+    pub fn start_addr(&self) -> u64 {
+        self.start_addr
+    }
+
+    /// The physical address just past the end of the module
+    pub fn end_addr(&self) -> u64 {
+        self.end_addr
+    }
+
+    pub fn size(&self) -> usize {
+        (self.end_addr - self.start_addr) as usize
+    }
+
+    /// The command line the module was loaded with, i.e. `initrd` for
+    /// `module2 /boot/initrd initrd`
+    pub fn string(&self) -> &str {
+        // SAFETY: This was copied from a str, and cut at a char boundary.
+        unsafe { str::from_utf8_unchecked(&self.command_line[..self.command_line_len]) }
+    }
+}
+
+/// The framebuffer the bootloader set up
+pub struct Framebuffer {
+    addr: u64,
+    pitch: u32,
+    width: u32,
+    height: u32,
+    bpp: u8,
+    color_info: ColorInfo,
+    palette: [PaletteColor; MAX_PALETTE_COLORS],
+    palette_len: usize,
+}
+
+/// The framebuffer type, without the palette
+#[derive(Debug, Copy, Clone)]
+enum ColorInfo {
+    Indexed,
+    Rgb(RgbLayout),
+    EgaText,
+    Unknown(u8),
+}
+
+impl Framebuffer {
+    fn new(info: &FramebufferInfo) -> Framebuffer {
+        let mut palette = [PaletteColor {
+            red: 0,
+            green: 0,
+            blue: 0,
+        }; MAX_PALETTE_COLORS];
+        let mut palette_len = 0;
+        let color_info = match info.framebuffer_type() {
+            FramebufferType::Indexed(colors) => {
+                palette_len = colors.len().min(MAX_PALETTE_COLORS);
+                palette[..palette_len].copy_from_slice(&colors[..palette_len]);
+                ColorInfo::Indexed
+            }
+            FramebufferType::Rgb(layout) => ColorInfo::Rgb(layout),
+            FramebufferType::EgaText => ColorInfo::EgaText,
+            FramebufferType::Unknown(framebuffer_type) => ColorInfo::Unknown(framebuffer_type),
+        };
+
+        Framebuffer {
+            addr: info.addr(),
+            pitch: info.pitch(),
+            width: info.width(),
+            height: info.height(),
+            bpp: info.bpp(),
+            color_info,
+            palette,
+            palette_len,
+        }
+    }
+
+    /// The physical address of the framebuffer
+    pub fn addr(&self) -> u64 {
+        self.addr
+    }
+
+    /// The number of bytes in each row
+    pub fn pitch(&self) -> u32 {
+        self.pitch
+    }
+
+    /// The width in pixels, or characters in text mode
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// The height in pixels, or characters in text mode
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Bits per pixel
+    pub fn bpp(&self) -> u8 {
+        self.bpp
+    }
+
+    /// The size of the framebuffer in bytes
+    pub fn size(&self) -> usize {
+        self.pitch as usize * self.height as usize
+    }
+
+    pub fn framebuffer_type(&self) -> FramebufferType<'_> {
+        match self.color_info {
+            ColorInfo::Indexed => FramebufferType::Indexed(&self.palette[..self.palette_len]),
+            ColorInfo::Rgb(layout) => FramebufferType::Rgb(layout),
+            ColorInfo::EgaText => FramebufferType::EgaText,
+            ColorInfo::Unknown(framebuffer_type) => FramebufferType::Unknown(framebuffer_type),
+        }
+    }
+}
+
+impl Debug for Framebuffer {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Framebuffer")
+            .field("addr", &self.addr)
+            .field("pitch", &self.pitch)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("bpp", &self.bpp)
+            .field("framebuffer_type", &self.framebuffer_type())
+            .finish()
+    }
+}
+
+/// Copies as much of `string` as fits into `buffer`, cut at a char boundary, returning the
+/// number of bytes copied
+fn copy_str(string: &str, buffer: &mut [u8]) -> usize {
+    let mut len = string.len().min(buffer.len());
+    while !string.is_char_boundary(len) {
+        len -= 1;
+    }
+    buffer[..len].copy_from_slice(&string.as_bytes()[..len]);
+    len
+}
+
+/// Copies what the kernel uses from the multiboot2 info. Once this returns, the info can be
+/// overwritten.
+pub fn init(multiboot_info: &Multiboot2Info) -> &'static BootInfo {
+    BOOT_INFO.call_once(|| BootInfo::new(multiboot_info))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn copy_str_cuts_at_char_boundary() {
+        let mut buffer = [0; 4];
+        assert_eq!(copy_str("ab", &mut buffer), 2);
+        assert_eq!(&buffer[..2], b"ab");
+        // 'é' is 2 bytes, and would be split by the end of the buffer
+        assert_eq!(copy_str("abcé", &mut buffer), 3);
+        assert_eq!(&buffer[..3], b"abc");
+        assert_eq!(copy_str("", &mut buffer), 0);
+    }
+}
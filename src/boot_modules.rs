@@ -0,0 +1,90 @@
+//! Files the bootloader loads along with the kernel (with GRUB's `module2`), i.e. an initrd.
+//!
+//! Each module is kept out of the frame allocator, and mapped read-only into the kernel's address
+//! space, so it can be used for as long as the kernel runs.
+
+use spin::Once;
+
+use crate::arch::paging::{map_physical, EntryFlags, PhysicalAddress};
+use crate::boot_info::Module;
+use crate::{info, warn};
+
+/// The most modules that are kept, any more are ignored
+const MAX_MODULES: usize = 8;
+
+/// The module command line that marks the initrd
+const INITRD_COMMAND_LINE: &str = "initrd";
+
+static MODULES: Once<[Option<BootModule>; MAX_MODULES]> = Once::new();
+
+pub struct BootModule {
+    data: &'static [u8],
+    command_line: &'static str,
+}
+
+impl BootModule {
+    /// Maps a module into the kernel's address space. The frame allocator must be initialized.
+    fn map(module: &'static Module) -> BootModule {
+        let start = map_physical(
+            PhysicalAddress::new(module.start_addr()),
+            module.size(),
+            EntryFlags::PRESENT | EntryFlags::NO_EXECUTE,
+        );
+        // SAFETY: We just mapped the module here, and the mapping is permanent. Its memory is
+        //         reserved, so it will never be written.
+        let data = unsafe { core::slice::from_raw_parts(start.as_ptr::<u8>(), module.size()) };
+
+        BootModule {
+            data,
+            command_line: module.string(),
+        }
+    }
+
+    pub fn data(&self) -> &'static [u8] {
+        self.data
+    }
+
+    /// The command line the module was loaded with
+    pub fn command_line(&self) -> &'static str {
+        self.command_line
+    }
+}
+
+/// Maps every module the bootloader loaded. Their memory must already be reserved, and the frame
+/// allocator initialized.
+pub fn init(modules: &'static [Module]) {
+    MODULES.call_once(|| {
+        let mut mapped: [Option<BootModule>; MAX_MODULES] = Default::default();
+        for (index, module) in modules.iter().enumerate() {
+            if index >= MAX_MODULES {
+                warn!(
+                    "Ignoring boot module {:?}, too many modules",
+                    module.string()
+                );
+                continue;
+            }
+
+            info!(
+                "Boot module {:?} at {:#x}-{:#x}",
+                module.string(),
+                module.start_addr(),
+                module.end_addr()
+            );
+            mapped[index] = Some(BootModule::map(module));
+        }
+        mapped
+    });
+}
+
+/// Returns every module the bootloader loaded
+pub fn modules() -> impl Iterator<Item = &'static BootModule> {
+    MODULES.r#try().into_iter().flatten().flatten()
+}
+
+/// Returns the initrd, which is the module loaded with the command line `initrd`, or otherwise
+/// the first module
+pub fn initrd() -> Option<&'static BootModule> {
+    modules()
+        .find(|module| module.command_line() == INITRD_COMMAND_LINE)
+        .or_else(|| modules().next())
+}
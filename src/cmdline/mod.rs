@@ -0,0 +1,233 @@
+//! The kernel command line, i.e. `log=debug console=serial mem=512M noapic`.
+//!
+//! Options are separated by whitespace, and are either `key=value` or a bare flag. Values can be
+//! quoted to include whitespace (`init="/bin/sh -x"`). Subsystems declare the options they take
+//! as static `Param`s with a default, which are listed in `PARAMS` so that options nobody takes
+//! can be reported.
+
+mod value;
+
+use spin::Once;
+
+use crate::warn;
+pub use value::{ParamValue, Size};
+
+/// Every option the kernel takes
+static PARAMS: &[&dyn Declared] = &[
+    &crate::klog::FILTERS_PARAM,
+    &crate::CONSOLE_PARAM,
+    &crate::MEM_PARAM,
+    &crate::INIT_PARAM,
+    &crate::arch::NOAPIC_PARAM,
+    &crate::power::PANIC_REBOOT_PARAM,
+    &crate::smbios::HWINFO_PARAM,
+    &crate::gdb::GDB_PARAM,
+];
+
+/// The command line, from the copy of the boot info
+static COMMAND_LINE: Once<&'static str> = Once::new();
+
+/// Keeps the command line the kernel was booted with. Until this is called, every `Param` is
+/// its default.
+pub fn init(command_line: &'static str) {
+    COMMAND_LINE.call_once(|| command_line);
+}
+
+/// Returns the command line the kernel was booted with
+pub fn command_line() -> &'static str {
+    COMMAND_LINE.r#try().copied().unwrap_or("")
+}
+
+/// A single option on the command line
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Arg<'a> {
+    pub key: &'a str,
+    /// `None` for a bare flag
+    pub value: Option<&'a str>,
+}
+
+impl<'a> Arg<'a> {
+    fn parse(arg: &'a str) -> Arg<'a> {
+        let (key, value) = match arg.find('=') {
+            Some(index) => (&arg[..index], Some(&arg[index + 1..])),
+            None => (arg, None),
+        };
+
+        let value = value.map(|value| {
+            if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                &value[1..value.len() - 1]
+            } else {
+                value
+            }
+        });
+        Arg { key, value }
+    }
+}
+
+/// An iterator over the options on a command line
+pub struct Args<'a> {
+    rest: &'a str,
+}
+
+impl<'a> Args<'a> {
+    pub fn new(command_line: &'a str) -> Args<'a> {
+        Args { rest: command_line }
+    }
+}
+
+impl<'a> Iterator for Args<'a> {
+    type Item = Arg<'a>;
+
+    fn next(&mut self) -> Option<Arg<'a>> {
+        let rest = self.rest.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+
+        // whitespace only ends an option outside of quotes
+        let mut quoted = false;
+        let end = rest
+            .char_indices()
+            .find(|(_, c)| {
+                if *c == '"' {
+                    quoted = !quoted;
+                }
+                c.is_whitespace() && !quoted
+            })
+            .map_or(rest.len(), |(index, _)| index);
+
+        self.rest = &rest[end..];
+        Some(Arg::parse(&rest[..end]))
+    }
+}
+
+/// A command line option a subsystem takes
+pub struct Param<T> {
+    name: &'static str,
+    description: &'static str,
+    default: T,
+}
+
+impl<T> Param<T> {
+    pub const fn new(name: &'static str, description: &'static str, default: T) -> Param<T> {
+        Param {
+            name,
+            description,
+            default,
+        }
+    }
+}
+
+impl<T: ParamValue> Param<T> {
+    /// Returns the option's value, or its default if it wasn't given or is invalid. If it was
+    /// given more than once, the last one wins.
+    pub fn get(&self) -> T {
+        self.get_from(command_line())
+    }
+
+    fn get_from(&self, command_line: &'static str) -> T {
+        Args::new(command_line)
+            .filter(|arg| arg.key == self.name)
+            .last()
+            .and_then(|arg| T::parse(arg.value))
+            .unwrap_or(self.default)
+    }
+}
+
+/// What the registry needs from a `Param`, whatever its type
+trait Declared: Sync {
+    fn name(&self) -> &'static str;
+    fn description(&self) -> &'static str;
+    fn is_valid(&self, value: Option<&'static str>) -> bool;
+}
+
+impl<T: ParamValue + Sync> Declared for Param<T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn description(&self) -> &'static str {
+        self.description
+    }
+
+    fn is_valid(&self, value: Option<&'static str>) -> bool {
+        T::parse(value).is_some()
+    }
+}
+
+/// Warns about every option on the command line that no subsystem takes, or whose value is
+/// invalid (and so is ignored)
+pub fn report_unknown() {
+    for arg in Args::new(command_line()) {
+        match PARAMS.iter().find(|param| param.name() == arg.key) {
+            Some(param) if !param.is_valid(arg.value) => warn!(
+                "Ignoring invalid value {:?} for `{}` ({})",
+                arg.value.unwrap_or(""),
+                arg.key,
+                param.description()
+            ),
+            Some(_) => {}
+            None => warn!("Unknown command line option `{}`", arg.key),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(command_line: &str) -> impl Iterator<Item = (&str, Option<&str>)> {
+        Args::new(command_line).map(|arg| (arg.key, arg.value))
+    }
+
+    #[test]
+    fn split_options() {
+        assert!(
+            args("  log=debug console=serial mem=512M noapic init=/bin/sh ").eq([
+                ("log", Some("debug")),
+                ("console", Some("serial")),
+                ("mem", Some("512M")),
+                ("noapic", None),
+                ("init", Some("/bin/sh")),
+            ]
+            .iter()
+            .copied())
+        );
+    }
+
+    #[test]
+    fn quoted_values() {
+        assert!(args("init=\"/bin/sh -x\" quiet root=").eq([
+            ("init", Some("/bin/sh -x")),
+            ("quiet", None),
+            ("root", Some(""))
+        ]
+        .iter()
+        .copied()));
+    }
+
+    #[test]
+    fn param_defaults() {
+        let mem = Param::new("mem", "", None::<Size>);
+        assert_eq!(mem.get_from("mem=1G"), Some(Size(1 << 30)));
+        assert_eq!(mem.get_from("mem=1G mem=2G"), Some(Size(2 << 30)));
+        assert_eq!(mem.get_from("mem=lots"), None);
+        assert_eq!(mem.get_from("memory=1G"), None);
+
+        let noapic = Param::new("noapic", "", false);
+        assert!(noapic.get_from("quiet noapic"));
+        assert!(!noapic.get_from("quiet"));
+    }
+
+    #[test]
+    fn documented_options_are_declared() {
+        for (key, value) in args("log=debug console=serial mem=512M noapic init=/bin/sh") {
+            let param = PARAMS.iter().find(|param| param.name() == key);
+            assert!(
+                param.map_or(false, |param| param.is_valid(value)),
+                "{}",
+                key
+            );
+        }
+    }
+}
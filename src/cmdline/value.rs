@@ -0,0 +1,96 @@
+//! Parsing of command line option values into the types subsystems want.
+
+/// A type a command line option can be parsed as. `value` is `None` for a bare flag, and
+/// parsing fails (so the option's default is used) if the value is invalid.
+pub trait ParamValue: Sized + Copy {
+    fn parse(value: Option<&'static str>) -> Option<Self>;
+}
+
+/// A flag, i.e. `noapic`, which can also be given explicitly as `noapic=off`
+impl ParamValue for bool {
+    fn parse(value: Option<&'static str>) -> Option<bool> {
+        match value {
+            None | Some("1") | Some("on") | Some("yes") | Some("true") => Some(true),
+            Some("0") | Some("off") | Some("no") | Some("false") => Some(false),
+            Some(_) => None,
+        }
+    }
+}
+
+impl ParamValue for &'static str {
+    fn parse(value: Option<&'static str>) -> Option<&'static str> {
+        value
+    }
+}
+
+/// A decimal number, or hex with a `0x` prefix
+impl ParamValue for u64 {
+    fn parse(value: Option<&'static str>) -> Option<u64> {
+        let value = value?;
+        match value.strip_prefix("0x") {
+            Some(hex) => u64::from_str_radix(hex, 16).ok(),
+            None => value.parse().ok(),
+        }
+    }
+}
+
+impl ParamValue for usize {
+    fn parse(value: Option<&'static str>) -> Option<usize> {
+        u64::parse(value).map(|value| value as usize)
+    }
+}
+
+/// An option that is `None` unless given, i.e. `mem=`
+impl<T: ParamValue> ParamValue for Option<T> {
+    fn parse(value: Option<&'static str>) -> Option<Option<T>> {
+        T::parse(value).map(Some)
+    }
+}
+
+/// A size in bytes, which can have a `K`, `M`, `G` or `T` suffix, i.e. `mem=512M`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Size(pub u64);
+
+impl ParamValue for Size {
+    fn parse(value: Option<&'static str>) -> Option<Size> {
+        let value = value?;
+        let (number, shift) = match value.as_bytes().last()? {
+            b'K' | b'k' => (&value[..value.len() - 1], 10),
+            b'M' | b'm' => (&value[..value.len() - 1], 20),
+            b'G' | b'g' => (&value[..value.len() - 1], 30),
+            b'T' | b't' => (&value[..value.len() - 1], 40),
+            _ => (value, 0),
+        };
+
+        let number = u64::parse(Some(number))?;
+        if number.leading_zeros() < shift {
+            return None;
+        }
+        Some(Size(number << shift))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_flags() {
+        assert_eq!(bool::parse(None), Some(true));
+        assert_eq!(bool::parse(Some("off")), Some(false));
+        assert_eq!(bool::parse(Some("maybe")), None);
+    }
+
+    #[test]
+    fn parse_numbers_and_sizes() {
+        assert_eq!(u64::parse(Some("42")), Some(42));
+        assert_eq!(u64::parse(Some("0x1000")), Some(0x1000));
+        assert_eq!(u64::parse(None), None);
+
+        assert_eq!(Size::parse(Some("512M")), Some(Size(512 << 20)));
+        assert_eq!(Size::parse(Some("4k")), Some(Size(4096)));
+        assert_eq!(Size::parse(Some("1000")), Some(Size(1000)));
+        assert_eq!(Size::parse(Some("M")), None);
+        assert_eq!(Size::parse(Some("100000000000T")), None);
+    }
+}
@@ -0,0 +1,159 @@
+use core::fmt::{Error, Write};
+
+use super::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use super::Framebuffer;
+
+/// A grid of text drawn on a framebuffer, which scrolls like the `VgaWriter`
+pub struct FramebufferConsole<'a> {
+    framebuffer: Framebuffer<'a>,
+    row: usize,
+    col: usize,
+    rows: usize,
+    cols: usize,
+    foreground: u32,
+    background: u32,
+}
+
+impl<'a> FramebufferConsole<'a> {
+    /// Creates a console drawing in the already encoded `foreground` and `background` colors
+    pub fn new(framebuffer: Framebuffer<'a>, foreground: u32, background: u32) -> Self {
+        let rows = framebuffer.height() / GLYPH_HEIGHT;
+        let cols = framebuffer.width() / GLYPH_WIDTH;
+        assert!(
+            rows > 0 && cols > 0,
+            "framebuffer is too small for the font"
+        );
+
+        FramebufferConsole {
+            framebuffer,
+            row: 0,
+            col: 0,
+            rows,
+            cols,
+            foreground,
+            background,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        let (width, height) = (self.framebuffer.width(), self.framebuffer.height());
+        self.framebuffer
+            .fill_rect(0, 0, width, height, self.background);
+        self.row = 0;
+        self.col = 0;
+    }
+
+    pub fn write_byte(&mut self, byte: u8) {
+        if byte == b'\n' {
+            self.new_line();
+            return;
+        }
+
+        self.draw_glyph(byte);
+        self.col += 1;
+        if self.col == self.cols {
+            self.new_line();
+        }
+    }
+
+    fn draw_glyph(&mut self, byte: u8) {
+        let x = self.col * GLYPH_WIDTH;
+        let y = self.row * GLYPH_HEIGHT;
+        for (glyph_row, bits) in font::glyph(byte).iter().enumerate() {
+            for glyph_col in 0..GLYPH_WIDTH {
+                let pixel = if bits & (0x80 >> glyph_col) != 0 {
+                    self.foreground
+                } else {
+                    self.background
+                };
+                self.framebuffer
+                    .write_pixel(x + glyph_col, y + glyph_row, pixel);
+            }
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.col = 0;
+        self.row += 1;
+        if self.row == self.rows {
+            self.scroll();
+        }
+    }
+
+    fn scroll(&mut self) {
+        self.framebuffer.scroll_up(GLYPH_HEIGHT);
+
+        // blank the last row of text, along with any pixels below it that don't fit a row
+        let width = self.framebuffer.width();
+        let top = (self.rows - 1) * GLYPH_HEIGHT;
+        let height = self.framebuffer.height() - top;
+        self.framebuffer
+            .fill_rect(0, top, width, height, self.background);
+
+        self.row = self.rows - 1;
+    }
+}
+
+impl<'a> Write for FramebufferConsole<'a> {
+    fn write_str(&mut self, string: &str) -> Result<(), Error> {
+        // anything outside of printable ASCII is drawn as the missing glyph
+        for byte in string.bytes() {
+            self.write_byte(byte);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const FOREGROUND: u32 = 0xFF;
+    const BACKGROUND: u32 = 0x00;
+
+    /// A framebuffer of 3 columns and 2 rows of text, with one byte per pixel
+    const WIDTH: usize = 3 * GLYPH_WIDTH;
+    const HEIGHT: usize = 2 * GLYPH_HEIGHT;
+
+    fn check_glyph(memory: &[u8], row: usize, col: usize, character: u8) {
+        for (glyph_row, bits) in font::glyph(character).iter().enumerate() {
+            for glyph_col in 0..GLYPH_WIDTH {
+                let y = row * GLYPH_HEIGHT + glyph_row;
+                let x = col * GLYPH_WIDTH + glyph_col;
+                let expected = if bits & (0x80 >> glyph_col) != 0 {
+                    FOREGROUND
+                } else {
+                    BACKGROUND
+                };
+                assert_eq!(memory[y * WIDTH + x] as u32, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn write_and_wrap() {
+        let mut memory = [0x55; WIDTH * HEIGHT];
+        let framebuffer = Framebuffer::new(&mut memory, WIDTH, HEIGHT, WIDTH, 1);
+        let mut console = FramebufferConsole::new(framebuffer, FOREGROUND, BACKGROUND);
+        console.clear();
+        console.write_str("abcd").unwrap();
+
+        check_glyph(&memory, 0, 0, b'a');
+        check_glyph(&memory, 0, 2, b'c');
+        check_glyph(&memory, 1, 0, b'd');
+        check_glyph(&memory, 1, 1, b' ');
+    }
+
+    #[test]
+    fn scroll() {
+        let mut memory = [0; WIDTH * HEIGHT];
+        let framebuffer = Framebuffer::new(&mut memory, WIDTH, HEIGHT, WIDTH, 1);
+        let mut console = FramebufferConsole::new(framebuffer, FOREGROUND, BACKGROUND);
+        console.write_str("a\nb\nc").unwrap();
+
+        check_glyph(&memory, 0, 0, b'b');
+        check_glyph(&memory, 1, 0, b'c');
+        check_glyph(&memory, 1, 1, b' ');
+    }
+}
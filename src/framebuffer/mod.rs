@@ -0,0 +1,269 @@
+//! A text console on a linear framebuffer, for when the bootloader sets a graphics mode (so the
+//! VGA text buffer isn't shown). Text is drawn with a built-in bitmap font. When there is no
+//! framebuffer, or it is in text mode, printing falls back to the `VgaWriter`.
+
+mod console;
+mod font;
+
+use core::fmt::Write;
+use core::slice;
+
+use crate::arch::paging::{map_physical, EntryFlags, PhysicalAddress};
+use crate::boot_info;
+use crate::klog::Sink;
+use crate::multiboot::tag::framebuffer::{ColorField, FramebufferType, PaletteColor};
+use crate::sync::IrqSafeMutex;
+pub use console::FramebufferConsole;
+
+const FOREGROUND: Rgb = Rgb::new(0xFF, 0xFF, 0xFF);
+const BACKGROUND: Rgb = Rgb::new(0x00, 0x00, 0x00);
+
+/// The console, once `init` finds a framebuffer
+pub static CONSOLE: IrqSafeMutex<Option<FramebufferConsole<'static>>> = IrqSafeMutex::new(None);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Rgb {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+impl Rgb {
+    pub const fn new(red: u8, green: u8, blue: u8) -> Rgb {
+        Rgb { red, green, blue }
+    }
+
+    /// Encodes the color as a `bpp` bit pixel in a framebuffer of `framebuffer_type`, or
+    /// returns `None` if it doesn't hold pixels, or its channels don't fit in a pixel
+    fn encode(&self, framebuffer_type: FramebufferType, bpp: u8) -> Option<u32> {
+        match framebuffer_type {
+            FramebufferType::Rgb(layout) => Some(
+                encode_channel(self.red, layout.red, bpp)?
+                    | encode_channel(self.green, layout.green, bpp)?
+                    | encode_channel(self.blue, layout.blue, bpp)?,
+            ),
+            FramebufferType::Indexed(palette) => self.nearest(palette).map(|index| index as u32),
+            FramebufferType::EgaText | FramebufferType::Unknown(_) => None,
+        }
+    }
+
+    /// Returns the index of the closest color in `palette`
+    fn nearest(&self, palette: &[PaletteColor]) -> Option<usize> {
+        let distance = |color: &PaletteColor| {
+            let diff = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+            diff(self.red, color.red) + diff(self.green, color.green) + diff(self.blue, color.blue)
+        };
+
+        palette
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, color)| distance(color))
+            .map(|(index, _)| index)
+    }
+}
+
+/// Scales an 8 bit channel down to `field`'s size, and moves it into place, or returns `None`
+/// if the field doesn't fit in a `bpp` bit pixel
+fn encode_channel(value: u8, field: ColorField, bpp: u8) -> Option<u32> {
+    if field.size == 0 {
+        return Some(0);
+    }
+    if field.position as u32 + field.size as u32 > bpp.min(32) as u32 {
+        return None;
+    }
+    let size = field.size.min(8);
+    Some(((value as u32) >> (8 - size)) << field.position)
+}
+
+/// A linear framebuffer, where each row of pixels is `pitch` bytes apart
+pub struct Framebuffer<'a> {
+    buffer: &'a mut [u8],
+    width: usize,
+    height: usize,
+    pitch: usize,
+    bytes_per_pixel: usize,
+}
+
+impl<'a> Framebuffer<'a> {
+    fn new(
+        buffer: &'a mut [u8],
+        width: usize,
+        height: usize,
+        pitch: usize,
+        bytes_per_pixel: usize,
+    ) -> Framebuffer<'a> {
+        // ensure the slice is large enough
+        assert!(buffer.len() >= pitch * height);
+        assert!(width * bytes_per_pixel <= pitch);
+
+        Framebuffer {
+            buffer,
+            width,
+            height,
+            pitch,
+            bytes_per_pixel,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Sets a pixel to an already encoded color. Pixels off the screen are ignored.
+    pub fn write_pixel(&mut self, x: usize, y: usize, pixel: u32) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+
+        let offset = y * self.pitch + x * self.bytes_per_pixel;
+        let bytes = pixel.to_le_bytes();
+        self.buffer[offset..offset + self.bytes_per_pixel]
+            .copy_from_slice(&bytes[..self.bytes_per_pixel]);
+    }
+
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, pixel: u32) {
+        for row in y..(y + height).min(self.height) {
+            for col in x..(x + width).min(self.width) {
+                self.write_pixel(col, row, pixel);
+            }
+        }
+    }
+
+    /// Moves everything up by `rows` rows of pixels. The rows uncovered at the bottom are left
+    /// as they were.
+    pub fn scroll_up(&mut self, rows: usize) {
+        let rows = rows.min(self.height);
+        let end = self.height * self.pitch;
+        self.buffer.copy_within(rows * self.pitch..end, 0);
+    }
+}
+
+/// Starts the console on the framebuffer the bootloader set up, returning false if it is in
+/// text mode (or a layout we can't draw to). The frame allocator must be initialized.
+pub fn init(info: &boot_info::Framebuffer) -> bool {
+    if !matches!(info.bpp(), 8 | 15 | 16 | 24 | 32) {
+        return false;
+    }
+
+    let framebuffer_type = info.framebuffer_type();
+    let colors = (
+        FOREGROUND.encode(framebuffer_type, info.bpp()),
+        BACKGROUND.encode(framebuffer_type, info.bpp()),
+    );
+    let (foreground, background) = match colors {
+        (Some(foreground), Some(background)) => (foreground, background),
+        _ => return false,
+    };
+
+    let flags = EntryFlags::PRESENT | EntryFlags::WRITE | EntryFlags::NO_EXECUTE;
+    let start = map_physical(PhysicalAddress::new(info.addr()), info.size(), flags);
+    // SAFETY: We just mapped the framebuffer here, and the mapping is permanent. Nothing else
+    //         uses the framebuffer.
+    let buffer = unsafe { slice::from_raw_parts_mut(start.as_ptr_mut::<u8>(), info.size()) };
+
+    let framebuffer = Framebuffer::new(
+        buffer,
+        info.width() as usize,
+        info.height() as usize,
+        info.pitch() as usize,
+        (info.bpp() as usize + 7) / 8,
+    );
+    let mut console = FramebufferConsole::new(framebuffer, foreground, background);
+    console.clear();
+    *CONSOLE.lock() = Some(console);
+    true
+}
+
+/// The framebuffer console, as a log sink
+pub static FRAMEBUFFER_SINK: FramebufferSink = FramebufferSink;
+
+pub struct FramebufferSink;
+
+impl Sink for FramebufferSink {
+    fn name(&self) -> &'static str {
+        "framebuffer"
+    }
+
+    fn write_str(&self, s: &str) {
+        if let Some(console) = CONSOLE.lock().as_mut() {
+            console.write_str(s).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::multiboot::tag::framebuffer::RgbLayout;
+
+    #[test]
+    fn encode_rgb() {
+        let field = |position, size| ColorField { position, size };
+        let xrgb = FramebufferType::Rgb(RgbLayout {
+            red: field(16, 8),
+            green: field(8, 8),
+            blue: field(0, 8),
+        });
+        assert_eq!(Rgb::new(0x12, 0x34, 0x56).encode(xrgb, 32), Some(0x12_3456));
+        assert_eq!(Rgb::new(0x12, 0x34, 0x56).encode(xrgb, 16), None);
+
+        let rgb565 = FramebufferType::Rgb(RgbLayout {
+            red: field(11, 5),
+            green: field(5, 6),
+            blue: field(0, 5),
+        });
+        assert_eq!(Rgb::new(0xFF, 0xFF, 0xFF).encode(rgb565, 16), Some(0xFFFF));
+        assert_eq!(FOREGROUND.encode(FramebufferType::EgaText, 16), None);
+    }
+
+    #[test]
+    fn encode_out_of_range() {
+        let field = |position, size| ColorField { position, size };
+        let shifted = FramebufferType::Rgb(RgbLayout {
+            red: field(40, 8),
+            green: field(8, 8),
+            blue: field(0, 8),
+        });
+        assert_eq!(FOREGROUND.encode(shifted, 32), None);
+
+        let wide = FramebufferType::Rgb(RgbLayout {
+            red: field(0, 8),
+            green: field(8, 8),
+            blue: field(16, 255),
+        });
+        assert_eq!(FOREGROUND.encode(wide, 32), None);
+    }
+
+    #[test]
+    fn encode_indexed() {
+        let palette = [
+            PaletteColor {
+                red: 0,
+                green: 0,
+                blue: 0,
+            },
+            PaletteColor {
+                red: 0xF0,
+                green: 0xF0,
+                blue: 0xE0,
+            },
+        ];
+        let indexed = FramebufferType::Indexed(&palette);
+        assert_eq!(FOREGROUND.encode(indexed, 8), Some(1));
+        assert_eq!(BACKGROUND.encode(indexed, 8), Some(0));
+    }
+
+    #[test]
+    fn write_pixels() {
+        let mut memory = [0u8; 4 * 3 * 2];
+        let mut framebuffer = Framebuffer::new(&mut memory, 3, 2, 12, 3);
+        framebuffer.write_pixel(1, 1, 0xAA_BBCC);
+        framebuffer.write_pixel(3, 0, 0xFF_FFFF);
+        assert_eq!(&memory[12 + 3..12 + 6], &[0xCC, 0xBB, 0xAA]);
+        assert!(memory[..12].iter().all(|byte| *byte == 0));
+    }
+}
@@ -0,0 +1,639 @@
+//! A stub for the GDB Remote Serial Protocol, so the kernel can be debugged with `gdb` over
+//! COM2 (i.e. attached to a QEMU serial socket with `target remote`, see `make qemu-gdb`),
+//! without needing QEMU's own gdbstub.
+//!
+//! The stub takes over whenever the kernel traps into the debugger, through a breakpoint (#BP)
+//! or a debug exception (#DB) from a single step or hardware breakpoint. It then serves GDB's
+//! requests until told to continue or step. Interrupts stay disabled the whole time, so the rest
+//! of the kernel is frozen while stopped.
+
+mod packet;
+
+use core::fmt::Write;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::arch::device::serial::{SerialPort, COM2, MAX_BAUD_RATE};
+use crate::arch::instructions::registers::debug::{Dr6Flags, NUM_BREAKPOINTS};
+use crate::arch::instructions::registers::rflags::RFlags;
+use crate::arch::instructions::registers::segmentation;
+use crate::arch::interrupt::{InterruptStackFrame, SavedRegisters};
+use crate::arch::paging::is_range_mapped;
+use crate::arch::watchpoint::{self, Condition, Owner, Size, Watchpoint, WatchpointId};
+use crate::cmdline::Param;
+use crate::sync::IrqSafeMutex;
+use packet::{PacketReader, Received, Response, MAX_PACKET_SIZE};
+
+/// How many software breakpoints GDB can insert at once
+const MAX_SOFTWARE_BREAKPOINTS: usize = 32;
+
+/// The `int3` instruction, which software breakpoints are replaced with
+const INT3: u8 = 0xCC;
+
+/// The registers GDB's x86_64 register set starts with: the 16 general purpose registers and
+/// RIP (8 bytes each), then EFLAGS and the six segment registers (4 bytes each). We don't send
+/// the rest (i.e. the FPU registers), so GDB shows them as unavailable.
+const NUM_REGISTERS: usize = 24;
+const RIP_REGISTER: usize = 16;
+
+// Error replies, which GDB expects to hold an errno
+const E2BIG: &str = "E07";
+const EFAULT: &str = "E0e";
+const EINVAL: &str = "E16";
+const ENOSPC: &str = "E1c";
+
+pub static GDB_PARAM: Param<bool> = Param::new(
+    "gdb",
+    "start the GDB stub on COM2, and wait for GDB to attach during boot",
+    false,
+);
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static STUB: IrqSafeMutex<Stub> = IrqSafeMutex::new(Stub::new());
+
+/// Why the kernel stopped, as reported to GDB
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum StopReason {
+    Breakpoint,
+    Step,
+    HardwareBreakpoint,
+    Watchpoint(Condition, u64),
+}
+
+/// How the kernel should resume once GDB is done
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Resume {
+    Continue,
+    Step,
+    /// Continue without the stub, until GDB attaches again
+    Detach,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct SoftwareBreakpoint {
+    addr: u64,
+    /// The byte `int3` replaced
+    original: u8,
+}
+
+/// Where the kernel stopped. Changes to either are picked up when it resumes.
+struct Context<'a> {
+    stack_frame: &'a mut InterruptStackFrame,
+    registers: &'a mut SavedRegisters,
+}
+
+impl<'a> Context<'a> {
+    fn register(&self, index: usize) -> Option<u64> {
+        let r = &self.registers;
+        let value = match index {
+            0 => r.rax,
+            1 => r.rbx,
+            2 => r.rcx,
+            3 => r.rdx,
+            4 => r.rsi,
+            5 => r.rdi,
+            6 => r.rbp,
+            7 => self.stack_frame.stack_pointer(),
+            8 => r.r8,
+            9 => r.r9,
+            10 => r.r10,
+            11 => r.r11,
+            12 => r.r12,
+            13 => r.r13,
+            14 => r.r14,
+            15 => r.r15,
+            RIP_REGISTER => self.stack_frame.instruction_pointer(),
+            17 => self.stack_frame.flags(),
+            18 => self.stack_frame.code_segment(),
+            19 => self.stack_frame.stack_segment(),
+            // the kernel never changes these, so they are the same as when we stopped
+            20 => segmentation::ds() as usize,
+            21 => segmentation::es() as usize,
+            22 => segmentation::fs() as usize,
+            23 => segmentation::gs() as usize,
+            _ => return None,
+        };
+        Some(value as u64)
+    }
+
+    /// Sets a register. Changes to the segment registers are ignored, as they aren't used in
+    /// long mode (and changing CS or SS would likely crash us).
+    fn set_register(&mut self, index: usize, value: u64) -> bool {
+        let value = value as usize;
+        let r = &mut self.registers;
+        match index {
+            0 => r.rax = value,
+            1 => r.rbx = value,
+            2 => r.rcx = value,
+            3 => r.rdx = value,
+            4 => r.rsi = value,
+            5 => r.rdi = value,
+            6 => r.rbp = value,
+            7 => self.stack_frame.set_stack_pointer(value),
+            8 => r.r8 = value,
+            9 => r.r9 = value,
+            10 => r.r10 = value,
+            11 => r.r11 = value,
+            12 => r.r12 = value,
+            13 => r.r13 = value,
+            14 => r.r14 = value,
+            15 => r.r15 = value,
+            RIP_REGISTER => self.stack_frame.set_instruction_pointer(value),
+            17 => self.stack_frame.set_flags(value),
+            18..=23 => {}
+            _ => return false,
+        }
+        true
+    }
+
+    fn set_flag(&mut self, flag: RFlags, enabled: bool) {
+        let mut flags = RFlags::from_bits_truncate(self.stack_frame.flags() as u64);
+        flags.set(flag, enabled);
+        self.stack_frame.set_flags(flags.bits() as usize);
+    }
+}
+
+fn register_size(index: usize) -> usize {
+    if index <= RIP_REGISTER {
+        8
+    } else {
+        4
+    }
+}
+
+/// The breakpoints GDB has inserted
+struct Breakpoints {
+    software: [Option<SoftwareBreakpoint>; MAX_SOFTWARE_BREAKPOINTS],
+    hardware: [Option<WatchpointId>; NUM_BREAKPOINTS],
+}
+
+impl Breakpoints {
+    const fn new() -> Breakpoints {
+        Breakpoints {
+            software: [None; MAX_SOFTWARE_BREAKPOINTS],
+            hardware: [None; NUM_BREAKPOINTS],
+        }
+    }
+
+    fn insert_software(&mut self, addr: u64) -> Result<(), &'static str> {
+        if self.software.iter().flatten().any(|bp| bp.addr == addr) {
+            return Ok(());
+        }
+        if !is_range_mapped(addr, 1) {
+            return Err(EFAULT);
+        }
+        let slot = self
+            .software
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(ENOSPC)?;
+
+        // SAFETY: We just checked the address is mapped. Kernel code is mapped writable.
+        unsafe {
+            let original = ptr::read_volatile(addr as *const u8);
+            ptr::write_volatile(addr as *mut u8, INT3);
+            *slot = Some(SoftwareBreakpoint { addr, original });
+        }
+        Ok(())
+    }
+
+    fn remove_software(&mut self, addr: u64) {
+        for slot in self.software.iter_mut() {
+            if let Some(bp) = slot.filter(|bp| bp.addr == addr) {
+                // SAFETY: This is where we wrote the int3, so it is still mapped.
+                unsafe { ptr::write_volatile(bp.addr as *mut u8, bp.original) };
+                *slot = None;
+            }
+        }
+    }
+
+    fn insert_hardware(
+        &mut self,
+        addr: u64,
+        condition: Condition,
+        size: Size,
+    ) -> Result<(), &'static str> {
+        if addr % size.bytes() as u64 != 0 {
+            return Err(EINVAL);
+        }
+        let slot = self
+            .hardware
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(ENOSPC)?;
+
+        let watchpoint = Watchpoint::new(addr, condition, size, Owner::Gdb);
+        *slot = Some(watchpoint::set(watchpoint).map_err(|_| ENOSPC)?);
+        Ok(())
+    }
+
+    fn remove_hardware(&mut self, addr: u64, condition: Condition) {
+        for slot in self.hardware.iter_mut() {
+            if let Some(id) = *slot {
+                let watchpoint = watchpoint::get(id);
+                if matches!(watchpoint, Some(w) if w.addr == addr && w.condition == condition) {
+                    watchpoint::clear(id);
+                    *slot = None;
+                }
+            }
+        }
+    }
+
+    fn remove_all(&mut self) {
+        for index in 0..MAX_SOFTWARE_BREAKPOINTS {
+            if let Some(bp) = self.software[index] {
+                self.remove_software(bp.addr);
+            }
+        }
+        for slot in self.hardware.iter_mut() {
+            if let Some(id) = slot.take() {
+                watchpoint::clear(id);
+            }
+        }
+    }
+
+    /// Returns the original byte at `addr` if it was replaced by a software breakpoint
+    fn original_byte(&self, addr: u64) -> Option<u8> {
+        self.software
+            .iter()
+            .flatten()
+            .find(|bp| bp.addr == addr)
+            .map(|bp| bp.original)
+    }
+}
+
+struct Stub {
+    reader: PacketReader,
+    response: Response,
+    breakpoints: Breakpoints,
+    /// Set once GDB has sent us something, so it is waiting for stop replies
+    attached: bool,
+}
+
+impl Stub {
+    const fn new() -> Stub {
+        Stub {
+            reader: PacketReader::new(),
+            response: Response::new(),
+            breakpoints: Breakpoints::new(),
+            attached: false,
+        }
+    }
+
+    /// Serves GDB until it tells us to resume
+    fn run(&mut self, port: &mut SerialPort, reason: StopReason, context: &mut Context) {
+        if self.attached {
+            self.response.clear();
+            write_stop_reply(&mut self.response, reason);
+            send_packet(port, self.response.data());
+        }
+
+        loop {
+            receive_packet(&mut self.reader, port);
+            self.attached = true;
+            self.response.clear();
+
+            let resume = handle_packet(
+                self.reader.packet(),
+                &mut self.response,
+                &mut self.breakpoints,
+                reason,
+                context,
+            );
+
+            let resume = match resume {
+                Some(resume) => resume,
+                None => {
+                    send_packet(port, self.response.data());
+                    continue;
+                }
+            };
+
+            if resume == Resume::Detach {
+                // GDB waits for a reply to `D`, but not to `k`
+                if self.reader.packet() == b"D" {
+                    send_packet(port, self.response.data());
+                }
+                self.attached = false;
+            }
+            context.set_flag(RFlags::TRAP, resume == Resume::Step);
+            // don't immediately hit a hardware breakpoint on the instruction we stopped at
+            context.set_flag(RFlags::RESUME, true);
+            return;
+        }
+    }
+}
+
+/// Handles a single packet, returning how to resume if it was a command to, or otherwise
+/// leaving the reply in `response`
+fn handle_packet(
+    packet: &[u8],
+    response: &mut Response,
+    breakpoints: &mut Breakpoints,
+    reason: StopReason,
+    context: &mut Context,
+) -> Option<Resume> {
+    let (command, args) = match packet.split_first() {
+        Some((command, args)) => (*command, args),
+        None => return None,
+    };
+
+    match command {
+        b'?' => write_stop_reply(response, reason),
+        b'g' => {
+            for index in 0..NUM_REGISTERS {
+                let value = context.register(index).unwrap_or(0);
+                response.push_hex_le(value, register_size(index));
+            }
+        }
+        b'G' => {
+            let mut rest = args;
+            for index in 0..NUM_REGISTERS {
+                let size = register_size(index) * 2;
+                if rest.len() < size {
+                    break;
+                }
+                if let Some(value) = packet::decode_hex_le(&rest[..size]) {
+                    context.set_register(index, value);
+                }
+                rest = &rest[size..];
+            }
+            response.push_str("OK");
+        }
+        b'p' => match packet::parse_hex(args).and_then(|index| {
+            let index = index as usize;
+            Some((context.register(index)?, register_size(index)))
+        }) {
+            Some((value, size)) => response.push_hex_le(value, size),
+            None => response.push_str(EINVAL),
+        },
+        b'P' => {
+            let written = split_once(args, b'=').and_then(|(index, value)| {
+                let index = packet::parse_hex(index)? as usize;
+                Some(context.set_register(index, packet::decode_hex_le(value)?))
+            });
+            response.push_str(if written == Some(true) { "OK" } else { EINVAL });
+        }
+        b'm' => read_memory(args, response, breakpoints),
+        b'M' => write_memory(args, response),
+        b'c' | b's' => {
+            if let Some(addr) = packet::parse_hex(args) {
+                context.stack_frame.set_instruction_pointer(addr as usize);
+            }
+            return Some(if command == b'c' {
+                Resume::Continue
+            } else {
+                Resume::Step
+            });
+        }
+        b'Z' | b'z' => {
+            if let Err(error) = set_breakpoint(args, command == b'Z', breakpoints) {
+                response.push_str(error);
+            } else {
+                response.push_str("OK");
+            }
+        }
+        b'D' | b'k' => {
+            breakpoints.remove_all();
+            response.push_str("OK");
+            return Some(Resume::Detach);
+        }
+        b'H' | b'T' => response.push_str("OK"),
+        b'q' => handle_query(args, response),
+        // an empty reply means the command isn't supported
+        _ => {}
+    }
+
+    None
+}
+
+fn handle_query(query: &[u8], response: &mut Response) {
+    if query.starts_with(b"Supported") {
+        write!(response, "PacketSize={:x}", MAX_PACKET_SIZE).unwrap();
+    } else if query == b"Attached" {
+        // we were already running, so GDB should detach rather than kill us when it quits
+        response.push_str("1");
+    } else if query == b"C" {
+        response.push_str("QC1");
+    } else if query == b"fThreadInfo" {
+        response.push_str("m1");
+    } else if query == b"sThreadInfo" {
+        response.push_str("l");
+    }
+}
+
+fn write_stop_reply(response: &mut Response, reason: StopReason) {
+    // SIGTRAP
+    match reason {
+        StopReason::Breakpoint | StopReason::Step => response.push_str("S05"),
+        StopReason::HardwareBreakpoint => response.push_str("T05hwbreak:;"),
+        StopReason::Watchpoint(condition, addr) => {
+            let kind = if condition == Condition::Write {
+                "watch"
+            } else {
+                "awatch"
+            };
+            write!(response, "T05{}:{:x};", kind, addr).unwrap();
+        }
+    }
+}
+
+/// Handles `m addr,length`. Software breakpoints are hidden, so GDB sees the original code.
+fn read_memory(args: &[u8], response: &mut Response, breakpoints: &Breakpoints) {
+    let (addr, len) = match parse_addr_len(args) {
+        Some(range) => range,
+        None => return response.push_str(EINVAL),
+    };
+    let len = len.min(MAX_PACKET_SIZE / 2);
+    if !is_range_mapped(addr, len) {
+        return response.push_str(EFAULT);
+    }
+
+    for byte_addr in addr..addr + len as u64 {
+        // SAFETY: We just checked the whole range is mapped.
+        let byte = unsafe { ptr::read_volatile(byte_addr as *const u8) };
+        response.push_hex_byte(breakpoints.original_byte(byte_addr).unwrap_or(byte));
+    }
+}
+
+/// Handles `M addr,length:XX...`
+fn write_memory(args: &[u8], response: &mut Response) {
+    let parsed = split_once(args, b':')
+        .and_then(|(range, data)| Some((parse_addr_len(range)?, data)))
+        .filter(|((_, len), data)| data.len() == len * 2);
+    let ((addr, len), data) = match parsed {
+        Some(parsed) => parsed,
+        None => return response.push_str(EINVAL),
+    };
+    if !is_range_mapped(addr, len) {
+        return response.push_str(EFAULT);
+    }
+
+    for (byte_addr, digits) in (addr..).zip(data.chunks(2)) {
+        let mut byte = [0];
+        if packet::decode_hex_bytes(digits, &mut byte).is_none() {
+            return response.push_str(EINVAL);
+        }
+        // SAFETY: We just checked the whole range is mapped. GDB is trusted to know what it is
+        //         overwriting.
+        unsafe { ptr::write_volatile(byte_addr as *mut u8, byte[0]) };
+    }
+    response.push_str("OK");
+}
+
+/// Handles `Z type,addr,kind` and `z type,addr,kind`
+fn set_breakpoint(
+    args: &[u8],
+    insert: bool,
+    breakpoints: &mut Breakpoints,
+) -> Result<(), &'static str> {
+    let (kind, rest) = split_once(args, b',').ok_or(EINVAL)?;
+    let (addr, len) = parse_addr_len(rest).ok_or(EINVAL)?;
+
+    let condition = match kind {
+        b"0" if insert => return breakpoints.insert_software(addr),
+        b"0" => {
+            breakpoints.remove_software(addr);
+            return Ok(());
+        }
+        b"1" => Condition::Execute,
+        b"2" => Condition::Write,
+        // x86 can't break on reads alone, so GDB falls back to an access watchpoint
+        b"4" => Condition::ReadWrite,
+        // an empty reply tells GDB the type isn't supported
+        _ => return Err(""),
+    };
+    let size = if condition == Condition::Execute {
+        Size::One
+    } else {
+        Size::from_bytes(len).ok_or(EINVAL)?
+    };
+
+    if insert {
+        breakpoints.insert_hardware(addr, condition, size)
+    } else {
+        breakpoints.remove_hardware(addr, condition);
+        Ok(())
+    }
+}
+
+fn parse_addr_len(args: &[u8]) -> Option<(u64, usize)> {
+    let (addr, len) = split_once(args, b',')?;
+    Some((packet::parse_hex(addr)?, packet::parse_hex(len)? as usize))
+}
+
+fn split_once(bytes: &[u8], separator: u8) -> Option<(&[u8], &[u8])> {
+    let index = bytes.iter().position(|byte| *byte == separator)?;
+    Some((&bytes[..index], &bytes[index + 1..]))
+}
+
+fn receive_byte(port: &mut SerialPort) -> u8 {
+    loop {
+        if let Some(byte) = port.receive() {
+            return byte;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+/// Waits for a packet, acknowledging it once it arrives intact. Packets too long for the buffer
+/// are acknowledged and answered with an error, as GDB would only send them again.
+fn receive_packet(reader: &mut PacketReader, port: &mut SerialPort) {
+    loop {
+        match reader.push(receive_byte(port)) {
+            Some(Received::Packet) => return port.send(b'+'),
+            Some(Received::BadChecksum) => port.send(b'-'),
+            Some(Received::TooLong) => {
+                port.send(b'+');
+                send_packet(port, E2BIG.as_bytes());
+            }
+            None => {}
+        }
+    }
+}
+
+/// Sends a packet, repeating it until GDB acknowledges it
+fn send_packet(port: &mut SerialPort, data: &[u8]) {
+    loop {
+        let checksum = packet::checksum(data);
+        port.send(b'$');
+        for byte in data {
+            port.send(*byte);
+        }
+        port.send(b'#');
+        port.send(packet::hex_digit(checksum >> 4));
+        port.send(packet::hex_digit(checksum));
+
+        loop {
+            match receive_byte(port) {
+                b'+' => return,
+                b'-' => break,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Starts the stub on COM2 if `gdb` is given, returning false if it isn't or there is no UART
+/// there. The IDT must be loaded.
+pub fn init() -> bool {
+    if !GDB_PARAM.get() || !COM2.lock().init(MAX_BAUD_RATE) {
+        return false;
+    }
+
+    ACTIVE.store(true, Ordering::SeqCst);
+    true
+}
+
+/// Returns true if the stub is running, so breakpoints should trap into it
+pub fn is_active() -> bool {
+    ACTIVE.load(Ordering::Relaxed)
+}
+
+/// Traps into the debugger, if the stub is running
+pub fn breakpoint() {
+    if is_active() {
+        // SAFETY: The breakpoint handler returns straight back here once GDB continues.
+        unsafe { asm!("int3") };
+    }
+}
+
+fn enter(
+    reason: StopReason,
+    stack_frame: &mut InterruptStackFrame,
+    registers: &mut SavedRegisters,
+) {
+    let mut stub = STUB.lock();
+    let mut context = Context {
+        stack_frame,
+        registers,
+    };
+    stub.run(&mut COM2.lock(), reason, &mut context);
+}
+
+/// Called by the breakpoint (#BP) handler while the stub is active
+pub fn handle_breakpoint(stack_frame: &mut InterruptStackFrame, registers: &mut SavedRegisters) {
+    enter(StopReason::Breakpoint, stack_frame, registers);
+}
+
+/// Called by the debug (#DB) handler while the stub is active, with the value of DR6. Returns
+/// false if the exception wasn't caused by the stub (i.e. by a watchpoint the kernel set), so
+/// should be reported by the caller instead. DR6 is left for the caller to clear.
+pub fn handle_debug(
+    dr6: Dr6Flags,
+    stack_frame: &mut InterruptStackFrame,
+    registers: &mut SavedRegisters,
+) -> bool {
+    let reason = match watchpoint::hit(dr6) {
+        Some((_, watchpoint)) if watchpoint.owner != Owner::Gdb => return false,
+        Some((_, watchpoint)) if watchpoint.condition == Condition::Execute => {
+            StopReason::HardwareBreakpoint
+        }
+        Some((_, watchpoint)) => StopReason::Watchpoint(watchpoint.condition, watchpoint.addr),
+        None if dr6.contains(Dr6Flags::SINGLE_STEP) => StopReason::Step,
+        None => return false,
+    };
+
+    enter(reason, stack_frame, registers);
+    true
+}
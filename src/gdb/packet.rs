@@ -0,0 +1,276 @@
+//! Framing for the GDB Remote Serial Protocol. Every packet is sent as `$data#cs`, where `cs`
+//! is the sum of the data bytes modulo 256 as two hex digits. The receiver acknowledges a
+//! packet with `+`, or asks for it to be sent again with `-`.
+
+use core::fmt::{self, Write};
+
+/// The largest packet we accept or send, which we advertise to GDB
+pub const MAX_PACKET_SIZE: usize = 4096;
+
+pub fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0, |sum, byte| sum.wrapping_add(*byte))
+}
+
+pub fn hex_digit(value: u8) -> u8 {
+    b"0123456789abcdef"[(value & 0xF) as usize]
+}
+
+pub fn from_hex_digit(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None,
+    }
+}
+
+/// Parses a big-endian hex number, i.e. an address or length
+pub fn parse_hex(digits: &[u8]) -> Option<u64> {
+    if digits.is_empty() || digits.len() > 16 {
+        return None;
+    }
+
+    digits.iter().try_fold(0, |value, digit| {
+        Some((value << 4) | from_hex_digit(*digit)? as u64)
+    })
+}
+
+/// Decodes pairs of hex digits into `out`, returning how many bytes were decoded
+pub fn decode_hex_bytes(digits: &[u8], out: &mut [u8]) -> Option<usize> {
+    if digits.len() % 2 != 0 || digits.len() / 2 > out.len() {
+        return None;
+    }
+
+    for (byte, pair) in out.iter_mut().zip(digits.chunks(2)) {
+        *byte = (from_hex_digit(pair[0])? << 4) | from_hex_digit(pair[1])?;
+    }
+    Some(digits.len() / 2)
+}
+
+/// Decodes a register value, which GDB sends as little-endian bytes
+pub fn decode_hex_le(digits: &[u8]) -> Option<u64> {
+    let mut bytes = [0; 8];
+    let len = decode_hex_bytes(digits, &mut bytes)?;
+    if len == 0 {
+        return None;
+    }
+    Some(u64::from_le_bytes(bytes))
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum State {
+    /// Waiting for the `$` that starts a packet
+    Idle,
+    Data,
+    /// Waiting for the first checksum digit
+    Checksum,
+    /// Waiting for the second checksum digit, holding the first
+    ChecksumLow(u8),
+}
+
+/// The result of feeding a byte to a `PacketReader`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Received {
+    /// A packet with a valid checksum is ready in `PacketReader::packet`
+    Packet,
+    /// A packet arrived corrupted, so should be asked for again
+    BadChecksum,
+    /// A packet was longer than `MAX_PACKET_SIZE`, so would be the same if sent again
+    TooLong,
+}
+
+/// Assembles received bytes into packets
+pub struct PacketReader {
+    buffer: [u8; MAX_PACKET_SIZE],
+    len: usize,
+    /// Whether the packet had more data than fit in the buffer
+    overflowed: bool,
+    state: State,
+}
+
+impl PacketReader {
+    pub const fn new() -> PacketReader {
+        PacketReader {
+            buffer: [0; MAX_PACKET_SIZE],
+            len: 0,
+            overflowed: false,
+            state: State::Idle,
+        }
+    }
+
+    /// Feeds in the next received byte. Bytes outside of a packet (i.e. acknowledgements) are
+    /// ignored.
+    pub fn push(&mut self, byte: u8) -> Option<Received> {
+        match self.state {
+            State::Idle => {
+                if byte == b'$' {
+                    self.len = 0;
+                    self.overflowed = false;
+                    self.state = State::Data;
+                }
+                None
+            }
+            State::Data => {
+                if byte == b'#' {
+                    self.state = State::Checksum;
+                } else if byte == b'$' {
+                    // the rest of the last packet was lost, so start again
+                    self.len = 0;
+                    self.overflowed = false;
+                } else if self.len < MAX_PACKET_SIZE {
+                    self.buffer[self.len] = byte;
+                    self.len += 1;
+                } else {
+                    self.overflowed = true;
+                }
+                None
+            }
+            State::Checksum => {
+                self.state = State::ChecksumLow(byte);
+                None
+            }
+            State::ChecksumLow(high) => {
+                self.state = State::Idle;
+                let expected =
+                    from_hex_digit(high).and_then(|high| Some((high << 4) | from_hex_digit(byte)?));
+
+                if self.overflowed {
+                    Some(Received::TooLong)
+                } else if expected == Some(checksum(self.packet())) {
+                    Some(Received::Packet)
+                } else {
+                    Some(Received::BadChecksum)
+                }
+            }
+        }
+    }
+
+    /// The data of the last packet received
+    pub fn packet(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+}
+
+/// A reply being built. Anything that doesn't fit is dropped, so callers should keep replies
+/// within `MAX_PACKET_SIZE`.
+pub struct Response {
+    buffer: [u8; MAX_PACKET_SIZE],
+    len: usize,
+}
+
+impl Response {
+    pub const fn new() -> Response {
+        Response {
+            buffer: [0; MAX_PACKET_SIZE],
+            len: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    pub fn push(&mut self, byte: u8) {
+        if self.len < MAX_PACKET_SIZE {
+            self.buffer[self.len] = byte;
+            self.len += 1;
+        }
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        for byte in s.bytes() {
+            self.push(byte);
+        }
+    }
+
+    pub fn push_hex_byte(&mut self, byte: u8) {
+        self.push(hex_digit(byte >> 4));
+        self.push(hex_digit(byte));
+    }
+
+    pub fn push_hex_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.push_hex_byte(*byte);
+        }
+    }
+
+    /// Pushes the low `size` bytes of `value` in little-endian order, as registers are sent
+    pub fn push_hex_le(&mut self, value: u64, size: usize) {
+        self.push_hex_bytes(&value.to_le_bytes()[..size]);
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.buffer[..self.len]
+    }
+}
+
+impl Write for Response {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.push_str(s);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn feed(reader: &mut PacketReader, bytes: &[u8]) -> Option<Received> {
+        bytes.iter().filter_map(|byte| reader.push(*byte)).last()
+    }
+
+    #[test]
+    fn read_packets() {
+        let mut reader = PacketReader::new();
+        assert_eq!(feed(&mut reader, b"+$g#67"), Some(Received::Packet));
+        assert_eq!(reader.packet(), b"g");
+
+        assert_eq!(feed(&mut reader, b"$m10,4#2e"), Some(Received::Packet));
+        assert_eq!(reader.packet(), b"m10,4");
+
+        assert_eq!(feed(&mut reader, b"$g#00"), Some(Received::BadChecksum));
+    }
+
+    #[test]
+    fn reject_oversized_packets() {
+        let mut reader = PacketReader::new();
+        reader.push(b'$');
+        for _ in 0..=MAX_PACKET_SIZE {
+            assert_eq!(reader.push(b'0'), None);
+        }
+        assert_eq!(feed(&mut reader, b"#00"), Some(Received::TooLong));
+
+        // the next packet is read as normal
+        assert_eq!(feed(&mut reader, b"$g#67"), Some(Received::Packet));
+        assert_eq!(reader.packet(), b"g");
+    }
+
+    #[test]
+    fn restart_on_new_packet() {
+        let mut reader = PacketReader::new();
+        assert_eq!(feed(&mut reader, b"$m10$?#3f"), Some(Received::Packet));
+        assert_eq!(reader.packet(), b"?");
+    }
+
+    #[test]
+    fn parse_hex_numbers() {
+        assert_eq!(parse_hex(b"ffff800000100000"), Some(0xFFFF_8000_0010_0000));
+        assert_eq!(parse_hex(b"1A"), Some(0x1A));
+        assert_eq!(parse_hex(b""), None);
+        assert_eq!(parse_hex(b"12g"), None);
+        assert_eq!(parse_hex(b"10000000000000000"), None);
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        let mut response = Response::new();
+        response.push_hex_le(0x1122_3344, 4);
+        assert_eq!(response.data(), b"44332211");
+        assert_eq!(decode_hex_le(response.data()), Some(0x1122_3344));
+
+        let mut bytes = [0; 2];
+        assert_eq!(decode_hex_bytes(b"cc90", &mut bytes), Some(2));
+        assert_eq!(bytes, [0xCC, 0x90]);
+        assert_eq!(decode_hex_bytes(b"cc9", &mut bytes), None);
+    }
+}
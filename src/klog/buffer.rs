@@ -0,0 +1,163 @@
+use core::fmt::{self, Write};
+use core::str;
+
+/// The size of the in-memory log, in bytes
+pub const LOG_BUFFER_SIZE: usize = 16 * 1024;
+
+/// The longest a single formatted log line can be. Longer lines are truncated.
+pub const MAX_LINE_LENGTH: usize = 256;
+
+/// A fixed-size buffer holding the most recent log output. Once full, the oldest bytes are
+/// overwritten.
+pub struct RingBuffer {
+    bytes: [u8; LOG_BUFFER_SIZE],
+    /// The total number of bytes ever written
+    written: usize,
+}
+
+impl RingBuffer {
+    pub const fn new() -> RingBuffer {
+        RingBuffer {
+            bytes: [0; LOG_BUFFER_SIZE],
+            written: 0,
+        }
+    }
+
+    pub fn write(&mut self, data: &[u8]) {
+        for byte in data {
+            self.bytes[self.written % LOG_BUFFER_SIZE] = *byte;
+            self.written += 1;
+        }
+    }
+
+    /// Returns the buffered output in order, as two slices (as it may wrap around the end of
+    /// the buffer). If older output has been overwritten, the first partial line is skipped.
+    pub fn contents(&self) -> (&[u8], &[u8]) {
+        if self.written <= LOG_BUFFER_SIZE {
+            return (&self.bytes[..self.written], &[]);
+        }
+
+        let start = self.written % LOG_BUFFER_SIZE;
+        let (newer, older) = self.bytes.split_at(start);
+
+        match older.iter().position(|byte| *byte == b'\n') {
+            Some(newline) => (&older[newline + 1..], newer),
+            None => {
+                let newline = newer.iter().position(|byte| *byte == b'\n');
+                (
+                    &newer[newline.map_or(newer.len(), |newline| newline + 1)..],
+                    &[],
+                )
+            }
+        }
+    }
+}
+
+/// A single log line being formatted, without needing the heap
+pub struct LineBuffer {
+    bytes: [u8; MAX_LINE_LENGTH],
+    len: usize,
+}
+
+impl LineBuffer {
+    pub const fn new() -> LineBuffer {
+        LineBuffer {
+            bytes: [0; MAX_LINE_LENGTH],
+            len: 0,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // SAFETY: Only whole `str`s, or prefixes of them cut at a char boundary, are written.
+        unsafe { str::from_utf8_unchecked(&self.bytes[..self.len]) }
+    }
+
+    /// Ends the line with a newline. There is always room for it, even if the line was
+    /// truncated.
+    pub fn finish(&mut self) {
+        self.bytes[self.len] = b'\n';
+        self.len += 1;
+    }
+}
+
+impl Write for LineBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // leave room for the newline added by `finish`
+        let remaining = MAX_LINE_LENGTH - 1 - self.len;
+
+        let mut count = s.len().min(remaining);
+        while !s.is_char_boundary(count) {
+            count -= 1;
+        }
+
+        self.bytes[self.len..self.len + count].copy_from_slice(&s.as_bytes()[..count]);
+        self.len += count;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn contents(buffer: &RingBuffer) -> std::vec::Vec<u8> {
+        let (first, second) = buffer.contents();
+        [first, second].concat()
+    }
+
+    #[test]
+    fn ring_buffer_not_full() {
+        let mut buffer = RingBuffer::new();
+        assert_eq!(contents(&buffer), b"");
+
+        buffer.write(b"one\n");
+        buffer.write(b"two\n");
+        assert_eq!(contents(&buffer), b"one\ntwo\n");
+    }
+
+    #[test]
+    fn ring_buffer_wraps() {
+        let mut buffer = RingBuffer::new();
+
+        let line = [b'a'; 99];
+        for _ in 0..LOG_BUFFER_SIZE / 100 + 5 {
+            buffer.write(&line);
+            buffer.write(b"\n");
+        }
+        buffer.write(b"last\n");
+
+        let contents = contents(&buffer);
+        assert!(contents.len() <= LOG_BUFFER_SIZE);
+        assert!(contents.ends_with(b"last\n"));
+
+        // the partially overwritten line is dropped, so every line is whole
+        for line in contents
+            .split(|byte| *byte == b'\n')
+            .filter(|line| !line.is_empty())
+        {
+            assert!(line == &[b'a'; 99][..] || line == b"last");
+        }
+    }
+
+    #[test]
+    fn line_buffer_truncates() {
+        let mut line = LineBuffer::new();
+        for _ in 0..MAX_LINE_LENGTH {
+            write!(line, "é").unwrap();
+        }
+        line.finish();
+
+        let line = line.as_str();
+        assert!(line.len() <= MAX_LINE_LENGTH);
+        assert!(line.ends_with("é\n"));
+    }
+
+    #[test]
+    fn line_buffer_finish() {
+        let mut line = LineBuffer::new();
+        write!(line, "hello {}", 42).unwrap();
+        line.finish();
+        assert_eq!(line.as_str(), "hello 42\n");
+    }
+}
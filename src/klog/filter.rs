@@ -0,0 +1,148 @@
+use super::Level;
+
+/// The maximum number of per-module filters
+const MAX_MODULE_FILTERS: usize = 8;
+
+/// The longest module path a filter can match on
+const MAX_MODULE_LENGTH: usize = 32;
+
+/// Decides which messages are logged: a default maximum level, overridden for specific modules
+#[derive(Debug, Copy, Clone)]
+pub struct Filters {
+    default: Level,
+    modules: [Option<ModuleFilter>; MAX_MODULE_FILTERS],
+}
+
+#[derive(Debug, Copy, Clone)]
+struct ModuleFilter {
+    module: [u8; MAX_MODULE_LENGTH],
+    len: usize,
+    level: Level,
+}
+
+impl ModuleFilter {
+    fn new(module: &str, level: Level) -> Option<ModuleFilter> {
+        if module.is_empty() || module.len() > MAX_MODULE_LENGTH {
+            return None;
+        }
+
+        let mut filter = ModuleFilter {
+            module: [0; MAX_MODULE_LENGTH],
+            len: module.len(),
+            level,
+        };
+        filter.module[..module.len()].copy_from_slice(module.as_bytes());
+        Some(filter)
+    }
+
+    fn module(&self) -> &[u8] {
+        &self.module[..self.len]
+    }
+
+    /// Returns true if `module` is this filter's module, or one of its submodules
+    fn matches(&self, module: &str) -> bool {
+        let module = module.as_bytes();
+        let filter = self.module();
+
+        module.starts_with(filter)
+            && (module.len() == filter.len() || module[filter.len()..].starts_with(b"::"))
+    }
+}
+
+impl Filters {
+    pub const fn new(default: Level) -> Filters {
+        Filters {
+            default,
+            modules: [None; MAX_MODULE_FILTERS],
+        }
+    }
+
+    /// Parses a filter specification, of the form `level,module=level,...`. For example,
+    /// `warn,time=trace` only logs warnings and errors, except from `time` and its submodules,
+    /// which log everything. Invalid entries are ignored, and levels not given default to
+    /// `default`.
+    pub fn parse(spec: &str, default: Level) -> Filters {
+        let mut filters = Filters::new(default);
+        let mut count = 0;
+
+        for entry in spec.split(',') {
+            let mut parts = entry.splitn(2, '=');
+            let (first, second) = (parts.next().unwrap_or(""), parts.next());
+
+            match second {
+                None => {
+                    if let Some(level) = Level::parse(first) {
+                        filters.default = level;
+                    }
+                }
+                Some(level) => {
+                    let filter =
+                        Level::parse(level).and_then(|level| ModuleFilter::new(first, level));
+                    if let (Some(filter), true) = (filter, count < MAX_MODULE_FILTERS) {
+                        filters.modules[count] = Some(filter);
+                        count += 1;
+                    }
+                }
+            }
+        }
+
+        filters
+    }
+
+    /// Returns the most verbose level for `module`. `module` is a path relative to the crate
+    /// root, i.e. `time::clock`. The most specific matching filter wins.
+    pub fn level_for(&self, module: &str) -> Level {
+        self.modules
+            .iter()
+            .flatten()
+            .filter(|filter| filter.matches(module))
+            .max_by_key(|filter| filter.len)
+            .map_or(self.default, |filter| filter.level)
+    }
+
+    /// Returns the most verbose level enabled for any module
+    pub fn max_level(&self) -> Level {
+        self.modules
+            .iter()
+            .flatten()
+            .map(|filter| filter.level)
+            .fold(self.default, Level::max)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn default_level() {
+        let filters = Filters::parse("debug", Level::Info);
+        assert_eq!(filters.level_for("time::clock"), Level::Debug);
+        assert_eq!(filters.max_level(), Level::Debug);
+
+        let filters = Filters::parse("", Level::Info);
+        assert_eq!(filters.level_for("time::clock"), Level::Info);
+    }
+
+    #[test]
+    fn module_levels() {
+        let filters = Filters::parse("warn,time=trace,time::clock=error,acpi=info", Level::Info);
+
+        assert_eq!(filters.level_for("time"), Level::Trace);
+        assert_eq!(filters.level_for("time::timer"), Level::Trace);
+        assert_eq!(filters.level_for("time::clock"), Level::Error);
+        assert_eq!(filters.level_for("acpi::hpet"), Level::Info);
+        assert_eq!(filters.level_for("timer"), Level::Warn);
+        assert_eq!(filters.level_for("memory"), Level::Warn);
+        assert_eq!(filters.max_level(), Level::Trace);
+    }
+
+    #[test]
+    fn invalid_entries() {
+        let filters = Filters::parse("loud,time=,=debug,acpi=verbose,memory=debug", Level::Info);
+
+        assert_eq!(filters.level_for("time"), Level::Info);
+        assert_eq!(filters.level_for("acpi"), Level::Info);
+        assert_eq!(filters.level_for("memory"), Level::Debug);
+    }
+}
@@ -0,0 +1,218 @@
+//! Kernel logging.
+//!
+//! Messages are logged with the `error!`, `warn!`, `info!`, `debug!` and `trace!` macros, and
+//! filtered by level per module. Every message that passes the filters is kept in an in-memory
+//! ring buffer, and written to each registered `Sink`. Sinks registered later (i.e. once their
+//! device is initialized) are sent the buffered messages first, so early boot messages are not
+//! lost.
+
+mod buffer;
+mod filter;
+
+use core::fmt::{self, Arguments, Write};
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use crate::cmdline::Param;
+use crate::sync::IrqSafeMutex;
+use crate::time::{self, DateTime};
+use buffer::{LineBuffer, RingBuffer};
+use filter::Filters;
+
+/// The maximum number of sinks that can be registered
+const MAX_SINKS: usize = 4;
+
+/// The level logged when nothing else is specified on the command line
+const DEFAULT_LEVEL: Level = Level::Info;
+
+/// The command line option holding the filter specification, see `Filters::parse`
+pub static FILTERS_PARAM: Param<Option<&'static str>> =
+    Param::new("log", "log filters, i.e. `warn,time=trace`", None);
+
+static BUFFER: IrqSafeMutex<RingBuffer> = IrqSafeMutex::new(RingBuffer::new());
+static SINKS: IrqSafeMutex<[Option<&'static dyn Sink>; MAX_SINKS]> =
+    IrqSafeMutex::new([None; MAX_SINKS]);
+static FILTERS: IrqSafeMutex<Filters> = IrqSafeMutex::new(Filters::new(DEFAULT_LEVEL));
+
+/// The most verbose level enabled for any module, so most disabled messages can be skipped
+/// without taking a lock.
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(DEFAULT_LEVEL as u8);
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+#[repr(u8)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    pub fn parse(level: &str) -> Option<Level> {
+        match level {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            "trace" => Some(Level::Trace),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+/// Somewhere log messages are written to
+pub trait Sink: Sync {
+    fn name(&self) -> &'static str;
+
+    /// Writes part of the log. This is always whole lines, each ending with a newline.
+    fn write_str(&self, s: &str);
+}
+
+/// Registers a sink, and writes every message still in the ring buffer to it
+pub fn register_sink(sink: &'static dyn Sink) {
+    // hold the buffer, so no message can be logged between replaying and registering
+    let buffer = BUFFER.lock();
+    let (older, newer) = buffer.contents();
+    for part in [older, newer].iter() {
+        // SAFETY: The buffer only holds whole lines, which are valid UTF-8.
+        sink.write_str(unsafe { core::str::from_utf8_unchecked(part) });
+    }
+
+    let mut sinks = SINKS.lock();
+    let slot = sinks
+        .iter_mut()
+        .find(|slot| slot.is_none())
+        .expect("Too many log sinks registered!");
+    *slot = Some(sink);
+}
+
+/// Sets the log filters from the kernel command line, if it has a `log=` option
+pub fn init_from_command_line() {
+    if let Some(spec) = FILTERS_PARAM.get() {
+        set_filters(Filters::parse(spec, DEFAULT_LEVEL));
+    }
+}
+
+fn set_filters(filters: Filters) {
+    MAX_LEVEL.store(filters.max_level() as u8, Ordering::SeqCst);
+    *FILTERS.lock() = filters;
+}
+
+/// Returns true if a message at `level` from `module` would be logged
+pub fn enabled(level: Level, module: &str) -> bool {
+    if level as u8 > MAX_LEVEL.load(Ordering::Relaxed) {
+        return false;
+    }
+
+    level <= FILTERS.lock().level_for(strip_crate_name(module))
+}
+
+/// Module paths start with the crate name, which filters leave out
+fn strip_crate_name(module: &str) -> &str {
+    match module.find("::") {
+        Some(index) => &module[index + 2..],
+        None => "",
+    }
+}
+
+/// Steals the log locks. Only for use in the panic handler, which never returns to whoever
+/// may be holding them.
+pub unsafe fn force_unlock() {
+    BUFFER.force_unlock();
+    SINKS.force_unlock();
+    FILTERS.force_unlock();
+}
+
+#[doc(hidden)]
+pub fn _log(level: Level, module: &str, args: Arguments) {
+    if !enabled(level, module) {
+        return;
+    }
+
+    let mut line = LineBuffer::new();
+
+    // stamp with the wall-clock time once the RTC has been read, and before that with the
+    // monotonic clock, which only means something once the timers have started
+    if let Some(ns) = time::unix_ns() {
+        let now = DateTime::from_unix(ns / time::NANOS_PER_SEC);
+        let _ = write!(
+            line,
+            "[{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z] ",
+            now.year,
+            now.month,
+            now.day,
+            now.hour,
+            now.minute,
+            now.second,
+            ns % time::NANOS_PER_SEC / 1000
+        );
+    } else if time::has_started() {
+        let ns = time::monotonic();
+        let _ = write!(
+            line,
+            "[{:5}.{:06}] ",
+            ns / time::NANOS_PER_SEC,
+            ns % time::NANOS_PER_SEC / 1000
+        );
+    }
+
+    let _ = write!(line, "{:<5} {}: {}", level, strip_crate_name(module), args);
+    line.finish();
+
+    let mut buffer = BUFFER.lock();
+    buffer.write(line.as_str().as_bytes());
+
+    // sinks must not log themselves, as the buffer stays locked to keep messages in order
+    let sinks = *SINKS.lock();
+    for sink in sinks.iter().flatten() {
+        sink.write_str(line.as_str());
+    }
+}
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {
+        $crate::klog::_log($level, module_path!(), format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => {$crate::log!($crate::klog::Level::Error, $($arg)*)};
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => {$crate::log!($crate::klog::Level::Warn, $($arg)*)};
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => {$crate::log!($crate::klog::Level::Info, $($arg)*)};
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => {$crate::log!($crate::klog::Level::Debug, $($arg)*)};
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => {$crate::log!($crate::klog::Level::Trace, $($arg)*)};
+}
@@ -0,0 +1,157 @@
+//! A test harness that runs `#[kernel_test]`s inside the kernel, with real hardware state.
+//!
+//! Build with the `kernel-test` feature (i.e. `make test-kernel`) to boot into the harness
+//! instead of the rest of `kernel_main`. Results are reported over serial, and QEMU is exited
+//! through its `isa-debug-exit` device with `ExitCode::Success` or `ExitCode::Failure`.
+//!
+//! Tests that are expected to panic are marked `#[kernel_test(should_panic)]`. As we cannot
+//! unwind, the panic handler instead resets the kernel stack, and continues with the next
+//! test. Any locks the test was holding stay locked.
+
+use core::panic::PanicInfo;
+use core::slice;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::arch::instructions::interrupts;
+use crate::arch::instructions::port::Port;
+use crate::serial_println;
+
+/// The I/O port QEMU's `isa-debug-exit` device is configured at
+const DEBUG_EXIT_PORT: u16 = 0xF4;
+
+/// Marks that no test is running
+const NO_TEST: usize = usize::MAX;
+
+/// A test registered by `#[kernel_test]`
+pub struct KernelTest {
+    pub name: &'static str,
+    pub func: fn(),
+    pub should_panic: bool,
+}
+
+/// QEMU exits with `(code << 1) | 1`, so these can't be confused with QEMU's own exit codes
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[repr(u32)]
+pub enum ExitCode {
+    Success = 0x10,
+    Failure = 0x11,
+}
+
+/// The index of the test currently running, or NO_TEST
+static CURRENT: AtomicUsize = AtomicUsize::new(NO_TEST);
+static FAILED: AtomicUsize = AtomicUsize::new(0);
+
+extern "C" {
+    // defined by the linker script, around the .kernel_tests section
+    static __kernel_tests_start: KernelTest;
+    static __kernel_tests_end: KernelTest;
+
+    // the (virtual) top of the kernel stack, from the linker script
+    static vstack_bottom: u8;
+}
+
+fn tests() -> &'static [KernelTest] {
+    // SAFETY: The linker places every KernelTest static (and nothing else) between these two
+    //         symbols, and they are all properly aligned as the section is only made of them.
+    unsafe {
+        let start = &__kernel_tests_start as *const KernelTest;
+        let end = &__kernel_tests_end as *const KernelTest;
+        slice::from_raw_parts(start, end.offset_from(start) as usize)
+    }
+}
+
+/// Runs every registered test, then exits QEMU. The kernel should be fully initialized first.
+pub fn run() -> ! {
+    serial_println!("running {} kernel tests", tests().len());
+    run_from(0)
+}
+
+fn run_from(first: usize) -> ! {
+    for (index, test) in tests().iter().enumerate().skip(first) {
+        CURRENT.store(index, Ordering::SeqCst);
+        (test.func)();
+        CURRENT.store(NO_TEST, Ordering::SeqCst);
+
+        if test.should_panic {
+            serial_println!("test {} ... FAILED (did not panic)", test.name);
+            FAILED.fetch_add(1, Ordering::SeqCst);
+        } else {
+            serial_println!("test {} ... ok", test.name);
+        }
+    }
+
+    let failed = FAILED.load(Ordering::SeqCst);
+    serial_println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failed == 0 { "ok" } else { "FAILED" },
+        tests().len() - failed,
+        failed
+    );
+
+    exit_qemu(if failed == 0 {
+        ExitCode::Success
+    } else {
+        ExitCode::Failure
+    })
+}
+
+/// Called by the panic handler. If a test is running, this records the result and moves on to
+/// the next test. Otherwise, the panic happened outside of a test, so this fails the run.
+pub fn handle_panic(info: &PanicInfo<'_>) -> ! {
+    let index = CURRENT.swap(NO_TEST, Ordering::SeqCst);
+    let test = match tests().get(index) {
+        Some(test) => test,
+        None => {
+            serial_println!("panic outside of a kernel test: {}", info);
+            exit_qemu(ExitCode::Failure);
+        }
+    };
+
+    if test.should_panic {
+        serial_println!("test {} ... ok (panicked)", test.name);
+    } else {
+        serial_println!("test {} ... FAILED\n{}", test.name, info);
+        FAILED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    // SAFETY: The harness never returns to kernel_main, so once the panicking test is
+    //         abandoned, nothing on the kernel stack is used again.
+    unsafe { resume_on_fresh_stack(index + 1) }
+}
+
+/// Resets the kernel stack to empty, and runs the tests from `next` onwards. The kernel stack
+/// is reused (rather than some other stack) so the tests keep its guard page.
+///
+/// # Safety
+/// Nothing on the kernel stack may be used again.
+unsafe fn resume_on_fresh_stack(next: usize) -> ! {
+    let stack_top = &vstack_bottom as *const u8 as usize;
+
+    asm!(
+        "mov rsp, {stack_top}",
+        "xor rbp, rbp",
+        "call {resume}",
+        stack_top = in(reg) stack_top,
+        resume = sym resume,
+        in("rdi") next,
+        options(noreturn)
+    );
+}
+
+extern "C" fn resume(next: usize) -> ! {
+    // the test may have panicked in an exception handler, or while holding an IrqSafeMutex
+    interrupts::enable();
+    run_from(next)
+}
+
+/// Exits QEMU through the `isa-debug-exit` device
+pub fn exit_qemu(code: ExitCode) -> ! {
+    // SAFETY: Writing to the isa-debug-exit port immediately stops QEMU.
+    unsafe { Port::<u32>::new(DEBUG_EXIT_PORT).write(code as u32) };
+
+    // not running in QEMU (or without the device), so there is nothing to exit to
+    loop {
+        interrupts::disable();
+        crate::arch::instructions::halt();
+    }
+}
@@ -16,19 +16,40 @@ extern crate alloc;
 #[cfg(not(test))]
 mod kalloc;
 
+mod acpi;
 mod arch;
 
+mod backtrace;
 mod bochs;
+mod boot_info;
+mod boot_modules;
+mod cmdline;
+mod framebuffer;
+mod gdb;
+mod klog;
+#[cfg(feature = "kernel-test")]
+mod ktest;
 mod memory;
 mod panic;
+mod power;
 mod print;
+mod sync;
+mod time;
 mod utils;
 mod vga;
 
 #[allow(dead_code)]
 mod multiboot;
+#[allow(dead_code)]
+mod pci;
+#[allow(dead_code)]
+mod smbios;
 
-use core::mem;
+use arch::paging::{PhysicalAddress, PAGE_SIZE};
+use bitflags::bitflags;
+use cmdline::{Param, ParamValue, Size};
+use multiboot::tag::framebuffer::FramebufferType;
+use multiboot::tag::memory_map::EntryType;
 use multiboot::Multiboot2Info;
 
 const MAGIC: u32 = 0x36d76289;
@@ -36,7 +57,7 @@ const MAGIC: u32 = 0x36d76289;
 // just used to pass stack addresses from the bootloader
 // into rust. Not sure if I really it
 #[repr(C)]
-pub struct BootInfo {
+pub struct KernelInfo {
     // bottom as in where the stack starts (i.e. HIGH memory)
     stack_bottom: u64,
     stack_top: u64,
@@ -46,17 +67,105 @@ pub struct BootInfo {
     vkernel_end: u64,
 }
 
+bitflags! {
+    /// Where log messages are shown
+    struct Consoles: u8 {
+        const VGA = 1 << 0;
+        const SERIAL = 1 << 1;
+        const DEBUGCON = 1 << 2;
+    }
+}
+
+/// A comma separated list of consoles, i.e. `vga,serial`, or `all`
+impl ParamValue for Consoles {
+    fn parse(value: Option<&'static str>) -> Option<Consoles> {
+        value?
+            .split(',')
+            .try_fold(Consoles::empty(), |consoles, name| {
+                let console = match name {
+                    "vga" => Consoles::VGA,
+                    "serial" => Consoles::SERIAL,
+                    "debugcon" => Consoles::DEBUGCON,
+                    "all" => Consoles::all(),
+                    _ => return None,
+                };
+                Some(consoles | console)
+            })
+    }
+}
+
+static CONSOLE_PARAM: Param<Consoles> = Param::new(
+    "console",
+    "where log messages are shown, any of `vga`, `serial` and `debugcon`, or `all`",
+    Consoles::all(),
+);
+
+static MEM_PARAM: Param<Option<Size>> = Param::new(
+    "mem",
+    "only use physical memory below this address, i.e. `512M`",
+    None,
+);
+
+/// There is no userspace to start yet, but boot loader configs already pass this
+static INIT_PARAM: Param<Option<&'static str>> = Param::new(
+    "init",
+    "the program to run once the kernel has booted, i.e. `/bin/sh`",
+    None,
+);
+
+/// Registers the log sinks the command line asks for. If the screen isn't in `text_mode`, it is
+/// registered once the framebuffer console is started.
+fn init_logging(text_mode: bool) {
+    klog::init_from_command_line();
+
+    let consoles = CONSOLE_PARAM.get();
+    if consoles.contains(Consoles::VGA) && text_mode {
+        klog::register_sink(&vga::VGA_SINK);
+    }
+    if consoles.contains(Consoles::SERIAL) && arch::device::serial::is_present() {
+        klog::register_sink(&arch::device::serial::SERIAL_SINK);
+    }
+    if consoles.contains(Consoles::DEBUGCON) && arch::device::debugcon::is_present() {
+        klog::register_sink(&arch::device::debugcon::DEBUGCON);
+    }
+
+    cmdline::report_unknown();
+}
+
 #[no_mangle]
-pub extern "C" fn kernel_main(
-    multiboot_info: &Multiboot2Info,
-    magic: u32,
-    boot_info: &BootInfo,
-) -> ! {
+pub extern "C" fn kernel_main(multiboot_addr: usize, magic: u32, kernel_info: &KernelInfo) -> ! {
     // ensure multiboot2 magic is correct (or else we were loaded by the wrong bootloader)
     assert!(magic == MAGIC);
-    println!(
+    // copy what we need out of the multiboot info before anything could overwrite it, so its
+    // memory can go to the frame allocator
+    let (boot_info, multiboot_range) = {
+        // SAFETY: The bootloader put the info at this address, and nothing has been allocated
+        //         over it yet.
+        let multiboot_info = unsafe { Multiboot2Info::from_ptr(multiboot_addr as *const u8) }
+            .expect("Invalid multiboot2 info");
+        for tag_type in multiboot::header::missing_tags(&multiboot_info) {
+            warn!(
+                "The bootloader didn't pass the requested {:?} tag",
+                tag_type
+            );
+        }
+        (
+            boot_info::init(&multiboot_info),
+            multiboot_info.memory_region(),
+        )
+    };
+
+    // bring up the serial port and logging first, so every boot message can be captured
+    arch::device::serial::init();
+    cmdline::init(boot_info.command_line());
+    // the screen stays in text mode unless the bootloader set up a framebuffer for us
+    let framebuffer_info = boot_info
+        .framebuffer()
+        .filter(|info| info.framebuffer_type() != FramebufferType::EgaText);
+    init_logging(framebuffer_info.is_none());
+    debug!(
         "Stack bottom: {:x?} stack top: {:x?}",
-        boot_info.stack_bottom, boot_info.stack_top
+        kernel_info.stack_bottom, kernel_info.stack_top
     );
 
     // TODO:
@@ -64,63 +173,145 @@ pub extern "C" fn kernel_main(
     // 2. Add section in bss for starter bitmap
 
     // Run architecture specific initialization code
-    arch::arch_init(boot_info);
+    arch::arch_init(kernel_info);
 
-    let multiboot_range = multiboot_info.memory_region();
+    if gdb::init() {
+        info!("Waiting for GDB on COM2");
+        gdb::breakpoint();
+    }
 
     // TODO: this wont work due to higher half mapping. Just get it from linker instead
     //let _kernel_range = multiboot_info.elf_symbols().unwrap().kernel_memory_region();
 
-    // subtract the memory regions for the kernel and multiboot header
+    // subtract the memory region for the kernel
     // Honestly should just make a data structure that manages this for me
-    // TODO: should also copy it to kernel memory so I don't need to keep the multiboot struct
-    //        around
-    //
-
-    let mmap = multiboot_info.memory_map().unwrap().available();
-    let kernel_entry = mmap
+    let kernel_entry = boot_info
+        .memory_areas()
+        .iter()
         .filter(|entry| {
-            entry.start_addr() <= boot_info.pkernel_start
-                && entry.end_addr() >= boot_info.pkernel_end
+            entry.entry_type() == EntryType::Available
+                && entry.start_addr() <= kernel_info.pkernel_start
+                && entry.end_addr() >= kernel_info.pkernel_end
         })
         .next()
         .expect("Couldn't find kernel in memory map!");
-    println!("{:?}", multiboot_range);
-    println!("{:?} {:?}", boot_info.pkernel_start, boot_info.pkernel_end);
+    debug!(
+        "{:?} {:?}",
+        kernel_info.pkernel_start, kernel_info.pkernel_end
+    );
     let mut main_region = PhysicalMemoryRegion::from_multiboot(kernel_entry);
+
+    // the bootloader usually loads the symbol tables and modules just past the kernel, so they
+    // are reserved in the frame allocator too
+    let symbol_sections = boot_info.symbol_sections();
+    let reserved_ends = symbol_sections
+        .map(|sections| sections.end().as_u64())
+        .into_iter()
+        .chain(boot_info.modules().iter().map(|module| module.end_addr()));
+    let reserved_end = reserved_ends
+        .map(|end| {
+            PhysicalAddress::new(end)
+                .align_up(PAGE_SIZE as u64)
+                .as_u64()
+        })
+        .filter(|end| *end <= main_region.end().as_u64())
+        .fold(kernel_info.pkernel_end, u64::max);
     let kernel_region =
-        main_region.take((boot_info.pkernel_end - main_region.base.as_u64()) as usize);
+        main_region.take((kernel_info.pkernel_end - main_region.base.as_u64()) as usize);
+    if let Some(Size(limit)) = MEM_PARAM.get() {
+        main_region.truncate(PhysicalAddress::new(limit).max(main_region.base));
+        info!("Limiting physical memory to {:#x}", limit);
+    }
 
-    println!("{:x?} {:x?}", kernel_region, main_region);
-    println!(
+    debug!("{:x?} {:x?}", kernel_region, main_region);
+    debug!("Multiboot info was at {:x?}", multiboot_range);
+    debug!(
         "{:x?} {:x?}",
-        boot_info.vkernel_start, boot_info.vkernel_end
+        kernel_info.vkernel_start, kernel_info.vkernel_end
     );
 
-    println!(
+    debug!(
         "Stack size: {:x?} {:x?}",
-        boot_info.stack_bottom, boot_info.stack_top
+        kernel_info.stack_bottom, kernel_info.stack_top
     );
 
-    // TODO: if we don't save multiboot_region, we need to drop it
-    mem::drop(multiboot_info);
-
     // TEST New alloc design
     use crate::memory::BootstrapAllocator;
     use crate::memory::{FrameAllocator, PhysicalMemoryRegion};
-    unsafe { BootstrapAllocator::init(main_region) }
+    let reserved_region = PhysicalMemoryRegion {
+        base: main_region.base,
+        size: (reserved_end.max(main_region.base.as_u64()) - main_region.base.as_u64()) as usize,
+    };
+    // the multiboot info was copied, so its frames are freed. They may be among the reserved
+    // frames, or already free, and any outside the allocator's region can't be reused.
+    let multiboot_region = PhysicalMemoryRegion {
+        base: multiboot_range.start_addr(),
+        size: (multiboot_range.end_addr().as_u64() - multiboot_range.start_addr().as_u64())
+            as usize,
+    };
+    // SAFETY: Only the symbol tables and modules are reserved, and nothing uses the multiboot
+    //         info after it was copied into `boot_info`.
+    unsafe {
+        BootstrapAllocator::init(main_region);
+        BootstrapAllocator::reserve(reserved_region);
+        BootstrapAllocator::free(multiboot_region);
+    }
     let alloc = BootstrapAllocator::get();
 
+    match symbol_sections {
+        Some(sections) => backtrace::init(sections),
+        None => warn!("No kernel symbols found, backtraces will not be symbolized"),
+    }
+    if let Some(name) = boot_info.bootloader_name() {
+        info!("Booted by {}", name);
+    }
+    if let Some(system_table) = boot_info.efi_system_table() {
+        info!("Booted from UEFI, system table at {:#x}", system_table);
+    }
+    acpi::init(boot_info.xsdp(), boot_info.rsdp());
+    power::init();
+    smbios::init(boot_info.smbios_entry_point());
+    pci::init();
+    boot_modules::init(boot_info.modules());
+    if let Some(initrd) = boot_modules::initrd() {
+        info!("initrd: {} bytes", initrd.data().len());
+    }
+    if let Some(info) = framebuffer_info {
+        if framebuffer::init(info) {
+            info!(
+                "Framebuffer console at {}x{}, {} bpp",
+                info.width(),
+                info.height(),
+                info.bpp()
+            );
+            if CONSOLE_PARAM.get().contains(Consoles::VGA) {
+                klog::register_sink(&framebuffer::FRAMEBUFFER_SINK);
+            }
+        } else {
+            warn!("Unsupported framebuffer {:?}", info);
+        }
+    }
+
+    arch::arch_late_init();
+    time::init();
+    arch::instructions::interrupts::enable();
+    info!(
+        "Timer started, TSC frequency: {} Hz",
+        time::tsc_frequency().unwrap_or(0)
+    );
+    info!("Booted at {}", time::wall_clock());
+
     // TEST: check paging code
     use arch::x86_64::paging::{Page, VirtualAddress, PAGE_TABLE};
     use memory::Frame;
     use vga::{Color, ColorCode, VgaChar};
-    let mut pte = PAGE_TABLE.lock();
 
     // try out the page table mappings
     let page = Page::containing(VirtualAddress::new(0xFFFF_DEAD_BEEF_B000));
     let frame = Frame::<BootstrapAllocator>::containing((0xB_8000) as usize);
-    pte.modify(|mut page_table| page_table.map(page, frame, alloc));
+    PAGE_TABLE
+        .lock()
+        .modify(|mut page_table| page_table.map(page, frame, alloc));
 
     // Just write some random chars. Should only see a red T if it worked
     unsafe {
@@ -129,6 +320,13 @@ pub extern "C" fn kernel_main(
             VgaChar::new(b'T', ColorCode::new(Color::Red, Color::Black));
     }
 
-    println!("-- kernel_main end --");
-    loop {}
+    // the test harness takes over once the kernel is fully up
+    #[cfg(feature = "kernel-test")]
+    ktest::run();
+
+    #[cfg(not(feature = "kernel-test"))]
+    {
+        println!("-- kernel_main end --");
+        loop {}
+    }
 }
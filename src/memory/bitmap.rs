@@ -1,4 +1,5 @@
 use core::mem::size_of;
+use core::ops::Range;
 
 use super::{FrameAllocatorImpl, PhysicalMemoryRegion, RawFrame, PAGE_SIZE};
 use crate::arch::x86_64::paging::PhysicalAddress;
@@ -26,6 +27,18 @@ impl BootstrapAllocatorImpl {
         index + first_frame_num
     }
 
+    /// Converts the frames `first_frame_num..end_frame_num` into bitmap indices, leaving out any
+    /// that are outside the arena or past the end of the bitmap.
+    fn indices(&self, first_frame_num: usize, end_frame_num: usize) -> Range<usize> {
+        let arena_start = self.first_frame_num();
+        let arena_end = self
+            .end_frame_num()
+            .min(arena_start + self.bitmap.capacity());
+        let start = first_frame_num.max(arena_start).min(arena_end);
+        let end = end_frame_num.max(start).min(arena_end);
+        start - arena_start..end - arena_start
+    }
+
     /// Allocates a sub-range from the allocator. Can be useful for creating
     /// sub-allocators. Currently, only supports 4K aligned sizes.
     fn alloc_range(&mut self, size: usize) -> Option<PhysicalMemoryRegion> {
@@ -67,6 +80,22 @@ impl FrameAllocatorImpl for BootstrapAllocatorImpl {
         self.arena = region;
     }
 
+    fn reserve(&mut self, region: PhysicalMemoryRegion) {
+        // every frame the region touches
+        let end = region.end().align_up(PAGE_SIZE as u64).frame_num();
+        for index in self.indices(region.base.frame_num(), end) {
+            self.bitmap.set(index);
+        }
+    }
+
+    fn free(&mut self, region: PhysicalMemoryRegion) {
+        // only the frames entirely within the region
+        let first = region.base.align_up(PAGE_SIZE as u64).frame_num();
+        for index in self.indices(first, region.end().frame_num()) {
+            self.bitmap.unset(index);
+        }
+    }
+
     fn alloc(&mut self) -> Option<RawFrame> {
         let first_frame_num = self.first_frame_num();
         let first_free = self.bitmap.first_unset()?;
@@ -128,13 +157,18 @@ impl FixedBitmap {
         for i in 0..self.words.len() {
             let word = self.words[i];
             if self.words[i] != 0xFFFF_FFFF_FFFF_FFFF {
-                return Some(i * 8 + word.leading_ones() as usize);
+                return Some(i * 64 + word.leading_ones() as usize);
             }
         }
 
         return None;
     }
 
+    /// The number of frames the bitmap can track
+    fn capacity(&self) -> usize {
+        self.words.len() * size_of::<u64>() * 8
+    }
+
     /// Finds a contiguous range of free frames in the bitmap, and returns
     /// the index of the first frame.
     ///
@@ -264,6 +298,8 @@ mod test {
         assert_eq!(bitmap.first_unset(), Some(5));
         bitmap.set(5);
         assert_eq!(bitmap.first_unset(), Some(8));
+        bitmap.words[0] = u64::MAX;
+        assert_eq!(bitmap.first_unset(), Some(64));
     }
 
     #[test]
@@ -318,6 +354,29 @@ mod test {
         assert_eq!(bitmap_alloc.alloc_range(8192), None);
     }
 
+    #[test]
+    fn reserve_and_free() {
+        let bitmap = FixedBitmap { words: [0u64; 64] };
+        let region = PhysicalMemoryRegion::new(PhysicalAddress::new(0x1300), 0x6000);
+        let mut bitmap_alloc = BootstrapAllocatorImpl {
+            bitmap,
+            arena: region,
+        };
+
+        // frames 2-5 are reserved, along with some memory outside the arena
+        bitmap_alloc.reserve(PhysicalMemoryRegion::new(PhysicalAddress::new(0x0), 0x5800));
+        assert_eq!(bitmap_alloc.alloc(), Some(RawFrame { num: 0x6 }));
+        assert_eq!(bitmap_alloc.alloc(), None);
+
+        // only frames entirely within the freed region are freed
+        bitmap_alloc.free(PhysicalMemoryRegion::new(
+            PhysicalAddress::new(0x2800),
+            0x2000,
+        ));
+        assert_eq!(bitmap_alloc.alloc(), Some(RawFrame { num: 0x3 }));
+        assert_eq!(bitmap_alloc.alloc(), None);
+    }
+
     #[test]
     #[should_panic(expected = "Attempting to free unallocated frame!")]
     fn dealloc_unallocated() {
@@ -4,10 +4,10 @@
 mod bitmap;
 
 use lazy_static::lazy_static;
-use spin::Mutex;
 
 use crate::arch::paging::{PhysicalAddress, PAGE_SIZE};
-use crate::println;
+use crate::sync::IrqSafeMutex;
+use crate::trace;
 pub use bitmap::BootstrapAllocatorImpl;
 
 // TODO: If allocator needs some args to init, we can add that.
@@ -18,9 +18,9 @@ macro_rules! frame_allocator {
         pub struct $type;
 
         impl $type {
-            fn __impl() -> &'static Mutex<$impl> {
+            fn __impl() -> &'static IrqSafeMutex<$impl> {
                 lazy_static! {
-                    static ref ALLOCATOR: Mutex<$impl> = Mutex::new(<$impl>::new());
+                    static ref ALLOCATOR: IrqSafeMutex<$impl> = IrqSafeMutex::new(<$impl>::new());
                 }
                 &ALLOCATOR
             }
@@ -31,6 +31,14 @@ macro_rules! frame_allocator {
                 <$type>::__impl().lock().init(region);
             }
 
+            unsafe fn reserve(region: PhysicalMemoryRegion) {
+                <$type>::__impl().lock().reserve(region);
+            }
+
+            unsafe fn free(region: PhysicalMemoryRegion) {
+                <$type>::__impl().lock().free(region);
+            }
+
             fn get() -> Self {
                 $type
             }
@@ -44,7 +52,7 @@ macro_rules! frame_allocator {
 
             #[doc(hidden)]
             unsafe fn __free_frame(&self, frame: &mut Frame<Self>) {
-                println!("We are freeing frame");
+                trace!("We are freeing frame");
                 <$type>::__impl()
                     .lock()
                     .dealloc(RawFrame { num: frame.num })
@@ -66,6 +74,20 @@ pub unsafe trait FrameAllocator: Copy {
     /// multiple times. Therefore, it is safe so long as it is only called once.
     unsafe fn init(region: PhysicalMemoryRegion);
 
+    /// Marks every frame in `region` as allocated, so it is never handed out. Frames the
+    /// allocator doesn't manage are ignored.
+    ///
+    /// # Safety
+    /// The frames are never freed, unless by `free`.
+    unsafe fn reserve(region: PhysicalMemoryRegion);
+
+    /// Frees every frame entirely within `region`, usually one that was reserved. Frames the
+    /// allocator doesn't manage are ignored.
+    ///
+    /// # Safety
+    /// Nothing may use the memory in these frames anymore.
+    unsafe fn free(region: PhysicalMemoryRegion);
+
     /// Returns a handle to the memory allocator.
     fn get() -> Self;
 
@@ -115,6 +137,8 @@ impl<A: FrameAllocator> Drop for Frame<A> {
 pub trait FrameAllocatorImpl {
     fn new() -> Self;
     fn init(&mut self, arena: PhysicalMemoryRegion);
+    fn reserve(&mut self, region: PhysicalMemoryRegion);
+    fn free(&mut self, region: PhysicalMemoryRegion);
     fn alloc(&mut self) -> Option<RawFrame>;
     fn dealloc(&mut self, frame: RawFrame);
 }
@@ -175,6 +199,12 @@ impl PhysicalMemoryRegion {
     pub fn end(&self) -> PhysicalAddress {
         self.base.add(self.size as u64)
     }
+
+    /// Shrinks the region so that it ends at or before `end`, which must not be before its base
+    pub fn truncate(&mut self, end: PhysicalAddress) {
+        assert!(end >= self.base, "Can't truncate a region before its base");
+        self.size = self.size.min((end.as_u64() - self.base.as_u64()) as usize);
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -195,6 +225,14 @@ impl MemoryRange {
         }
     }
 
+    pub fn start_addr(&self) -> PhysicalAddress {
+        self.start_addr
+    }
+
+    pub fn end_addr(&self) -> PhysicalAddress {
+        self.end_addr
+    }
+
     /// Returns true if this region entirely contains `region`
     pub fn contains(&self, region: &MemoryRange) -> bool {
         self.start_addr <= region.start_addr && region.end_addr <= self.end_addr
@@ -0,0 +1,225 @@
+//! The multiboot2 header, which tells the bootloader what the kernel wants.
+//!
+//! The header asks for info tags by their `Type`, so it can't disagree with how the tags are
+//! parsed. The bootloader may not support every request, so `missing_tags` is checked at boot.
+
+use core::mem::size_of;
+
+use super::tag::Type;
+use super::Multiboot2Info;
+
+const MAGIC: u32 = 0xE852_50D6;
+/// 32 bit protected mode i386
+const ARCHITECTURE: u32 = 0;
+
+/// Header tag types
+const END: u16 = 0;
+const INFORMATION_REQUEST: u16 = 1;
+const ENTRY_ADDRESS: u16 = 3;
+const FRAMEBUFFER: u16 = 5;
+const MODULE_ALIGN: u16 = 6;
+
+/// Header tag flags
+const REQUIRED: u16 = 0;
+const OPTIONAL: u16 = 1;
+
+/// The framebuffer mode we would like, which the bootloader may not be able to set
+const FRAMEBUFFER_WIDTH: u32 = 1024;
+const FRAMEBUFFER_HEIGHT: u32 = 768;
+const FRAMEBUFFER_DEPTH: u32 = 32;
+
+/// The info tags the bootloader is asked for
+pub const REQUESTED_TAGS: [Type; 6] = [
+    Type::BootCmdLine,
+    Type::Modules,
+    Type::MemoryMap,
+    Type::FramebufferInfo,
+    Type::AcpiOldRdsp,
+    Type::AcpiNewRdsp,
+];
+
+/// Builds the header, which enters the kernel at `$entry`. This is a macro rather than a
+/// `const fn`, as those can't take function pointers.
+macro_rules! header {
+    ($entry:expr) => {
+        Header {
+            magic: MAGIC,
+            architecture: ARCHITECTURE,
+            header_length: size_of::<Header>() as u32,
+            checksum: 0u32.wrapping_sub(
+                MAGIC
+                    .wrapping_add(ARCHITECTURE)
+                    .wrapping_add(size_of::<Header>() as u32),
+            ),
+            // optional, so a bootloader that can't pass one of them still boots us
+            information_request: InformationRequest {
+                header: HeaderTag::new::<InformationRequest>(INFORMATION_REQUEST, OPTIONAL),
+                requests: REQUESTED_TAGS,
+            },
+            // optional, so we can still boot in text mode
+            framebuffer: FramebufferRequest {
+                header: HeaderTag::new::<FramebufferRequest>(FRAMEBUFFER, OPTIONAL),
+                width: FRAMEBUFFER_WIDTH,
+                height: FRAMEBUFFER_HEIGHT,
+                depth: FRAMEBUFFER_DEPTH,
+            },
+            _framebuffer_padding: 0,
+            // modules are page aligned, so mapping one never maps part of something else
+            module_align: HeaderTag::new::<HeaderTag>(MODULE_ALIGN, REQUIRED),
+            entry_address: EntryAddress {
+                header: HeaderTag {
+                    tag_type: ENTRY_ADDRESS,
+                    flags: REQUIRED,
+                    size: (size_of::<HeaderTag>() + size_of::<u32>()) as u32,
+                },
+                entry_addr: $entry,
+            },
+            end: HeaderTag::new::<HeaderTag>(END, REQUIRED),
+        }
+    };
+}
+
+#[cfg(not(test))]
+extern "C" {
+    fn _start() -> !;
+}
+
+// the linker script keeps this at the start of the image, where the bootloader searches for it
+#[cfg(not(test))]
+#[no_mangle]
+#[used]
+#[link_section = ".multiboot_header"]
+static MULTIBOOT2_HEADER: Header = header!(_start);
+
+/// The header, where every tag starts on an 8 byte boundary
+#[repr(C, align(8))]
+struct Header {
+    magic: u32,
+    architecture: u32,
+    header_length: u32,
+    checksum: u32,
+    information_request: InformationRequest,
+    framebuffer: FramebufferRequest,
+    _framebuffer_padding: u32,
+    module_align: HeaderTag,
+    entry_address: EntryAddress,
+    end: HeaderTag,
+}
+
+#[repr(C)]
+struct HeaderTag {
+    tag_type: u16,
+    flags: u16,
+    size: u32,
+}
+
+impl HeaderTag {
+    /// The header of a tag that is a `T`
+    const fn new<T>(tag_type: u16, flags: u16) -> HeaderTag {
+        HeaderTag {
+            tag_type,
+            flags,
+            size: size_of::<T>() as u32,
+        }
+    }
+}
+
+#[repr(C)]
+struct InformationRequest {
+    header: HeaderTag,
+    requests: [Type; 6],
+}
+
+#[repr(C)]
+struct FramebufferRequest {
+    header: HeaderTag,
+    width: u32,
+    height: u32,
+    depth: u32,
+}
+
+#[repr(C)]
+struct EntryAddress {
+    header: HeaderTag,
+    /// The standard has a 32 bit address here. `_start` is below 4 GiB, so the upper half of
+    /// the pointer is the padding up to the next tag.
+    entry_addr: unsafe extern "C" fn() -> !,
+}
+
+/// Returns true if the bootloader didn't pass a tag we asked for. A modules tag is only passed
+/// for each module that was loaded, and an RSDP tag for each RSDP the firmware has, so only a
+/// missing modules tag, or both RSDP tags missing, doesn't count.
+fn is_missing(info: &Multiboot2Info, tag_type: Type) -> bool {
+    match tag_type {
+        Type::Modules => false,
+        Type::AcpiOldRdsp | Type::AcpiNewRdsp => {
+            !info.has_tag(Type::AcpiOldRdsp) && !info.has_tag(Type::AcpiNewRdsp)
+        }
+        _ => !info.has_tag(tag_type),
+    }
+}
+
+/// Returns the tags the header asked for that the bootloader didn't pass
+pub fn missing_tags<'a>(info: &'a Multiboot2Info<'a>) -> impl Iterator<Item = Type> + 'a {
+    REQUESTED_TAGS
+        .iter()
+        .copied()
+        .filter(move |tag_type| is_missing(info, *tag_type))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use core::slice;
+
+    fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+        let mut value = [0; 4];
+        value.copy_from_slice(&bytes[offset..offset + 4]);
+        u32::from_le_bytes(value)
+    }
+
+    extern "C" fn entry() -> ! {
+        unreachable!()
+    }
+
+    #[test]
+    fn header_layout() {
+        let header = header!(entry);
+        // SAFETY: The header is plain data.
+        let bytes = unsafe {
+            slice::from_raw_parts(&header as *const Header as *const u8, size_of::<Header>())
+        };
+        let length = read_u32(bytes, 8);
+        assert_eq!(length as usize, bytes.len());
+        let sum = (0..4).fold(0u32, |sum, field| {
+            sum.wrapping_add(read_u32(bytes, field * 4))
+        });
+        assert_eq!(sum, 0);
+
+        // every tag is 8 byte aligned, and the last is the end tag
+        let mut offset = 16;
+        let mut tags = [0u16; 5];
+        for tag in tags.iter_mut() {
+            assert_eq!(offset % 8, 0);
+            *tag = read_u32(bytes, offset) as u16;
+            offset += (read_u32(bytes, offset + 4) as usize + 7) & !7;
+        }
+        assert_eq!(
+            tags,
+            [
+                INFORMATION_REQUEST,
+                FRAMEBUFFER,
+                MODULE_ALIGN,
+                ENTRY_ADDRESS,
+                END
+            ]
+        );
+        assert_eq!(offset, bytes.len());
+
+        // the requests are the tag types the parser numbers them as
+        let requests: [u32; 6] = [1, 3, 6, 8, 14, 15];
+        for (index, request) in requests.iter().enumerate() {
+            assert_eq!(read_u32(bytes, 24 + index * 4), *request);
+        }
+    }
+}
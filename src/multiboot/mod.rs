@@ -1,55 +1,418 @@
+pub mod header;
 pub mod tag;
 
-use core::marker::PhantomData;
+use core::mem::size_of;
+use core::slice;
 
+use crate::acpi::{Rsdp, Xsdp};
 use crate::memory::MemoryRange;
 use tag::*;
 
-// TODO: I may want to wrap this in another struct
-pub struct Multiboot2Info<'a> {
+/// The fixed part of the info, before the tags
+#[repr(C)]
+struct InfoHeader {
     total_size: u32,
     _reserved: u32,
-    _marker: PhantomData<&'a TagHeader>,
+}
+
+// TODO: I may want to wrap this in another struct
+/// The information the bootloader passes, which has been checked to be well formed
+pub struct Multiboot2Info<'a> {
+    bytes: &'a [u8],
+    tags: TagIterator<'a>,
 }
 
 impl<'a> Multiboot2Info<'a> {
-    fn tags(&self) -> TagIterator {
-        // SAFETY: This is safe because the multiboot2 standard ensures the first tag will always
-        //         immediatly follow this header. This ptr will also be non-null and 8-byte aligned
-        //         as the header starts 8-byte aligned and is 8 bytes big.
-        unsafe { TagIterator::new((self as *const Multiboot2Info).offset(1) as *const TagHeader) }
+    /// Parses the info the bootloader left at `addr`.
+    ///
+    /// # Safety
+    /// `addr` must point to readable memory at least as big as the info's `total_size` says, which
+    /// stays mapped and unmodified for `'a`.
+    pub unsafe fn from_ptr(addr: *const u8) -> Result<Multiboot2Info<'a>, &'static str> {
+        let total_size = (addr as *const u32).read_unaligned();
+        Multiboot2Info::from_bytes(slice::from_raw_parts(addr, total_size as usize))
+    }
+
+    /// Parses the info in `bytes`, which must be 8 byte aligned. Every tag is checked to fit
+    /// within the info's `total_size`.
+    pub fn from_bytes(bytes: &'a [u8]) -> Result<Multiboot2Info<'a>, &'static str> {
+        let total_size = bytes
+            .get(..size_of::<u32>())
+            .map(|size| u32::from_le_bytes([size[0], size[1], size[2], size[3]]) as usize)
+            .ok_or("multiboot2 info is truncated")?;
+        if total_size < size_of::<InfoHeader>() || total_size > bytes.len() {
+            return Err("multiboot2 info size is out of bounds");
+        }
+
+        let bytes = &bytes[..total_size];
+        let tags = TagIterator::new(&bytes[size_of::<InfoHeader>()..])?;
+        Ok(Multiboot2Info { bytes, tags })
+    }
+
+    /// Returns every tag the bootloader passed
+    pub fn tags(&self) -> TagIterator<'a> {
+        self.tags.clone()
     }
 
     /// Returns a logical memory region in which this multiboot2 struct resides
     pub fn memory_region(&self) -> MemoryRange {
-        let start = (self as *const Multiboot2Info) as usize;
-        MemoryRange::new(start, start + self.total_size as usize)
+        let start = self.bytes.as_ptr() as usize;
+        MemoryRange::new(start, start + self.bytes.len())
+    }
+
+    /// Returns true if the bootloader passed at least one tag of `tag_type`
+    pub fn has_tag(&self, tag_type: Type) -> bool {
+        self.tags().any(|tag| tag.tag_type() == tag_type)
+    }
+
+    /// Returns the command line the kernel was booted with, if the bootloader passed one
+    pub fn command_line(&self) -> Option<&'a str> {
+        self.tags().find_map(|tag| match tag {
+            Tag::BootCmdLine(command_line) => Some(command_line.string()),
+            _ => None,
+        })
+    }
+
+    /// Returns the name of the bootloader, i.e. `GRUB 2.04`
+    pub fn bootloader_name(&self) -> Option<&'a str> {
+        self.tags().find_map(|tag| match tag {
+            Tag::BootLoaderName(name) => Some(name.string()),
+            _ => None,
+        })
+    }
+
+    /// Returns the BIOS disk the kernel was loaded from, if it was loaded from one
+    pub fn bios_boot_device(&self) -> Option<&'a BiosBoot> {
+        self.tags().find_map(|tag| match tag {
+            Tag::BiosBoot(bios_boot) => Some(bios_boot),
+            _ => None,
+        })
     }
 
     pub fn memory_info(&self) -> Option<&'a MemoryInfo> {
-        // SAFETY: This is safe, as we know the TagHeader is valid from the tag iterator, and we
-        //         also know from the multiboot2 standard that the tag with type 4 is a valid
-        //         MemoryInfo tag.
-        self.tags()
-            .find(|tag| tag.tag_type == 4)
-            .map(|header| unsafe { &*((header as *const TagHeader) as *const MemoryInfo) })
+        self.tags().find_map(|tag| match tag {
+            Tag::MemoryInfo(memory_info) => Some(memory_info),
+            _ => None,
+        })
     }
 
-    pub fn memory_map(&self) -> Option<&'a MemoryMap> {
-        // SAFETY: This is safe, as we know the TagHeader is valid from the tag iterator, and we
-        //         also know from the multiboot2 standard that the tag with type 6 is a valid
-        //         MemoryMap tag.
+    pub fn memory_map(&self) -> Option<&'a MemoryMap<'a>> {
+        self.tags().find_map(|tag| match tag {
+            Tag::MemoryMap(memory_map) => Some(memory_map),
+            _ => None,
+        })
+    }
+
+    /// Returns the memory map from the UEFI firmware, if we were booted from UEFI
+    pub fn efi_memory_map(&self) -> Option<&'a EfiMemoryMap> {
+        self.tags().find_map(|tag| match tag {
+            Tag::EfiMemoryMap(efi_memory_map) => Some(efi_memory_map),
+            _ => None,
+        })
+    }
+
+    /// Returns true if the bootloader left the UEFI boot services running, so their memory is
+    /// still in use
+    pub fn efi_boot_services_running(&self) -> bool {
         self.tags()
-            .find(|tag| tag.tag_type == 6)
-            .map(|header| unsafe { &*((header as *const TagHeader) as *const MemoryMap) })
+            .any(|tag| matches!(tag, Tag::EfiBootServicesNotTerminated(_)))
+    }
+
+    /// Returns the memory map, preferring the one from the UEFI firmware (when its boot services
+    /// have exited) over the multiboot one
+    pub fn memory_areas(&self) -> Option<MemoryAreas<'a>> {
+        match self.efi_memory_map() {
+            Some(efi_memory_map) if !self.efi_boot_services_running() => {
+                Some(MemoryAreas::Efi(efi_memory_map.entries()))
+            }
+            _ => self
+                .memory_map()
+                .map(|memory_map| MemoryAreas::Multiboot(memory_map.entries())),
+        }
+    }
+
+    /// Returns the physical address of the UEFI system table, if we were booted from UEFI
+    pub fn efi_system_table(&self) -> Option<u64> {
+        self.tags().find_map(|tag| match tag {
+            Tag::Efi32SystemTable(system_table) => Some(system_table.pointer()),
+            Tag::Efi64SystemTable(system_table) => Some(system_table.pointer()),
+            _ => None,
+        })
+    }
+
+    /// Returns each module the bootloader loaded along with the kernel
+    pub fn modules(&self) -> impl Iterator<Item = &'a Modules> {
+        self.tags().filter_map(|tag| match tag {
+            Tag::Modules(module) => Some(module),
+            _ => None,
+        })
+    }
+
+    pub fn framebuffer_info(&self) -> Option<&'a FramebufferInfo> {
+        self.tags().find_map(|tag| match tag {
+            Tag::FramebufferInfo(framebuffer_info) => Some(framebuffer_info),
+            _ => None,
+        })
+    }
+
+    /// Returns the copy of the ACPI 1.0 RSDP, if the bootloader passed a valid one
+    pub fn acpi_old_rsdp(&self) -> Option<&'a Rsdp> {
+        self.tags().find_map(|tag| match tag {
+            Tag::AcpiOldRdsp(tag) => tag.rsdp(),
+            _ => None,
+        })
+    }
+
+    /// Returns the copy of the ACPI 2.0+ RSDP, if the bootloader passed a valid one
+    pub fn acpi_new_rsdp(&self) -> Option<&'a Xsdp> {
+        self.tags().find_map(|tag| match tag {
+            Tag::AcpiNewRdsp(tag) => tag.xsdp(),
+            _ => None,
+        })
+    }
+
+    /// Returns the copy of the SMBIOS tables, if the bootloader passed one
+    pub fn smbios_tables(&self) -> Option<&'a SMBiosTable> {
+        self.tags().find_map(|tag| match tag {
+            Tag::SMBiosTable(smbios_table) => Some(smbios_table),
+            _ => None,
+        })
     }
 
     pub fn elf_symbols(&self) -> Option<&'a ElfSymbols> {
-        // SAFETY: This is safe, as we know the TagHeader is valid from the tag iterator, and we
-        //         also know from the multiboot2 standard that the tag with type 9 is a valid
-        //         ElfSymbols tag.
-        self.tags()
-            .find(|tag| tag.tag_type == 9)
-            .map(|header| unsafe { &*((header as *const TagHeader) as *const ElfSymbols) })
+        self.tags().find_map(|tag| match tag {
+            Tag::ElfSymbols(elf_symbols) => Some(elf_symbols),
+            _ => None,
+        })
+    }
+}
+
+/// The memory map, from whichever tag describes it
+pub enum MemoryAreas<'a> {
+    Efi(efi_memory_map::Entries<'a>),
+    Multiboot(memory_map::Iter<'a>),
+}
+
+impl<'a> Iterator for MemoryAreas<'a> {
+    type Item = memory_map::Entry;
+
+    fn next(&mut self) -> Option<memory_map::Entry> {
+        match self {
+            MemoryAreas::Efi(entries) => entries.next(),
+            MemoryAreas::Multiboot(entries) => entries.next().copied(),
+        }
+    }
+}
+
+/// Reads everything each tag points to within itself, returning how much was read. The tests and
+/// the fuzzer in `fuzz/` use this to check that every tag stays within the info.
+#[doc(hidden)]
+pub fn read_tags(info: &Multiboot2Info) -> usize {
+    let mut read = 0;
+    for tag in info.tags() {
+        read += match tag {
+            Tag::BootCmdLine(command_line) => command_line.string().len(),
+            Tag::BootLoaderName(name) => name.string().len(),
+            Tag::Modules(module) => module.string().len() + module.size(),
+            Tag::MemoryMap(memory_map) => memory_map.entries().count(),
+            Tag::EfiMemoryMap(efi_memory_map) => efi_memory_map.entries().count(),
+            Tag::FramebufferInfo(framebuffer_info) => match framebuffer_info.framebuffer_type() {
+                tag::framebuffer::FramebufferType::Indexed(palette) => palette.len(),
+                _ => 0,
+            },
+            // names are in the loaded image rather than the tag, so only the headers are read
+            Tag::ElfSymbols(elf_symbols) => elf_symbols
+                .sections()
+                .filter_map(|section| elf_symbols.section(section.link()))
+                .count(),
+            Tag::SMBiosTable(smbios_table) => smbios_table.tables().len(),
+            Tag::NetworkingInfo(networking_info) => networking_info.dhcp_ack().len(),
+            Tag::Unknown(header) => header.size(),
+            _ => 0,
+        };
+    }
+    read + info.memory_areas().map_or(0, |areas| areas.count())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    const BUFFER_SIZE: usize = 512;
+
+    /// Builds multiboot2 info in an 8 byte aligned buffer
+    struct TestInfo {
+        buffer: [u64; BUFFER_SIZE / 8],
+        len: usize,
+    }
+
+    impl TestInfo {
+        fn new() -> TestInfo {
+            TestInfo {
+                buffer: [0; BUFFER_SIZE / 8],
+                len: size_of::<InfoHeader>(),
+            }
+        }
+
+        fn bytes_mut(&mut self) -> &mut [u8] {
+            // SAFETY: The buffer is plain memory, and the slice covers exactly it.
+            unsafe { slice::from_raw_parts_mut(self.buffer.as_mut_ptr() as *mut u8, BUFFER_SIZE) }
+        }
+
+        fn bytes(&self) -> &[u8] {
+            // SAFETY: The buffer is plain memory, and `len` is always within it.
+            unsafe { slice::from_raw_parts(self.buffer.as_ptr() as *const u8, self.len) }
+        }
+
+        /// Appends a tag with `data` after its header, padded to the next tag
+        fn tag(mut self, tag_type: u32, data: &[u8]) -> TestInfo {
+            let start = self.len;
+            let size = 8 + data.len();
+            let bytes = self.bytes_mut();
+            bytes[start..start + 4].copy_from_slice(&tag_type.to_le_bytes());
+            bytes[start + 4..start + 8].copy_from_slice(&(size as u32).to_le_bytes());
+            bytes[start + 8..start + size].copy_from_slice(data);
+            self.len = (start + size + 7) & !7;
+            self
+        }
+
+        /// Appends the end tag, and sets the total size
+        fn finish(self) -> TestInfo {
+            let mut info = self.tag(0, &[]);
+            let total_size = info.len as u32;
+            info.bytes_mut()[..4].copy_from_slice(&total_size.to_le_bytes());
+            info
+        }
+    }
+
+    fn memory_map_entry(base_addr: u64, length: u64, entry_type: u32) -> [u8; 24] {
+        let mut entry = [0u8; 24];
+        entry[..8].copy_from_slice(&base_addr.to_le_bytes());
+        entry[8..16].copy_from_slice(&length.to_le_bytes());
+        entry[16..20].copy_from_slice(&entry_type.to_le_bytes());
+        entry
+    }
+
+    fn valid_info() -> TestInfo {
+        let mut memory_map = [0u8; 8 + 24 * 2];
+        memory_map[..4].copy_from_slice(&24u32.to_le_bytes());
+        memory_map[8..32].copy_from_slice(&memory_map_entry(0, 0x9_F000, 1));
+        memory_map[32..56].copy_from_slice(&memory_map_entry(0x10_0000, 0x7F0_0000, 1));
+
+        let mut module = [0u8; 8 + 7];
+        module[..4].copy_from_slice(&0x20_0000u32.to_le_bytes());
+        module[4..8].copy_from_slice(&0x20_1000u32.to_le_bytes());
+        module[8..].copy_from_slice(b"initrd\0");
+
+        // a null section and a string table, where the 64 bit section headers start 4 bytes
+        // after an 8 byte boundary
+        let mut elf_symbols = [0u8; 12 + 64 * 2];
+        elf_symbols[..4].copy_from_slice(&2u32.to_le_bytes());
+        elf_symbols[4..8].copy_from_slice(&64u32.to_le_bytes());
+        elf_symbols[8..12].copy_from_slice(&1u32.to_le_bytes());
+        elf_symbols[12 + 64 + 4] = 3;
+
+        TestInfo::new()
+            .tag(1, b"log=debug\0")
+            .tag(2, b"GRUB 2.04\0")
+            .tag(3, &module)
+            .tag(6, &memory_map)
+            .tag(9, &elf_symbols)
+            .finish()
+    }
+
+    #[test]
+    fn parse_info() {
+        let test_info = valid_info();
+        let info = Multiboot2Info::from_bytes(test_info.bytes()).unwrap();
+
+        assert_eq!(info.command_line(), Some("log=debug"));
+        assert_eq!(info.bootloader_name(), Some("GRUB 2.04"));
+        let module = info.modules().next().unwrap();
+        assert_eq!((module.start_addr(), module.size()), (0x20_0000, 0x1000));
+        assert_eq!(module.string(), "initrd");
+        let areas = info.memory_areas().unwrap().collect::<Vec<_>>();
+        assert_eq!(areas.len(), 2);
+        assert_eq!(areas[1].start_addr(), 0x10_0000);
+        assert!(info.efi_memory_map().is_none());
+        let elf_symbols = info.elf_symbols().unwrap();
+        assert_eq!(elf_symbols.sections().count(), 2);
+        assert_eq!(
+            elf_symbols.section(1).unwrap().section_type(),
+            tag::elf_symbols::SectionType::StringTable
+        );
+        assert_eq!(info.tags().count(), 5);
+    }
+
+    #[test]
+    fn reject_malformed_info() {
+        let test_info = valid_info();
+        let bytes = test_info.bytes();
+
+        // truncated, or shorter than it says it is
+        assert!(Multiboot2Info::from_bytes(&bytes[..2]).is_err());
+        assert!(Multiboot2Info::from_bytes(&bytes[..bytes.len() - 8]).is_err());
+        // not 8 byte aligned
+        let mut unaligned = [0u64; BUFFER_SIZE / 8 + 1];
+        // SAFETY: The buffer is plain memory, and the slice is within it.
+        let unaligned = unsafe {
+            slice::from_raw_parts_mut((unaligned.as_mut_ptr() as *mut u8).add(4), bytes.len())
+        };
+        unaligned.copy_from_slice(bytes);
+        assert!(Multiboot2Info::from_bytes(unaligned).is_err());
+
+        // a tag too small for its header, or bigger than the info
+        let mut info = TestInfo::new().tag(1, b"\0").finish();
+        info.bytes_mut()[12..16].copy_from_slice(&4u32.to_le_bytes());
+        assert!(Multiboot2Info::from_bytes(info.bytes()).is_err());
+        info.bytes_mut()[12..16].copy_from_slice(&0x1000u32.to_le_bytes());
+        assert!(Multiboot2Info::from_bytes(info.bytes()).is_err());
+
+        // no end tag
+        let mut info = TestInfo::new().tag(1, b"\0");
+        let total_size = info.len as u32;
+        info.bytes_mut()[..4].copy_from_slice(&total_size.to_le_bytes());
+        assert!(Multiboot2Info::from_bytes(info.bytes()).is_err());
+
+        // strings must be null-terminated UTF-8
+        let info = TestInfo::new().tag(1, b"log=debug").finish();
+        assert!(Multiboot2Info::from_bytes(info.bytes()).is_err());
+        let info = TestInfo::new().tag(2, b"\xFF\xFE\0").finish();
+        assert!(Multiboot2Info::from_bytes(info.bytes()).is_err());
+
+        // a tag too small for its type, and a memory map that can't be stepped through
+        let info = TestInfo::new().tag(5, &[0; 4]).finish();
+        assert!(Multiboot2Info::from_bytes(info.bytes()).is_err());
+        let info = TestInfo::new().tag(6, &[0; 8]).finish();
+        assert!(Multiboot2Info::from_bytes(info.bytes()).is_err());
+    }
+
+    #[test]
+    fn fuzz() {
+        let valid = valid_info();
+        // seeded, so a failure always reproduces. `fuzz/` has a coverage guided fuzzer.
+        let mut rng = StdRng::seed_from_u64(0x6A75_6E74_6F73);
+        for _ in 0..10_000 {
+            let mut info = TestInfo::new();
+            info.buffer = valid.buffer;
+            info.len = valid.len;
+
+            // corrupt a few random bytes, which are likely to be in a tag's header
+            for _ in 0..rng.gen::<usize>() % 4 + 1 {
+                let index = rng.gen::<usize>() % info.len;
+                info.bytes_mut()[index] = rng.gen();
+            }
+            // and sometimes cut it short
+            let len = match rng.gen::<bool>() {
+                true => info.len,
+                false => rng.gen::<usize>() % (info.len + 1),
+            };
+
+            if let Ok(parsed) = Multiboot2Info::from_bytes(&info.bytes()[..len]) {
+                read_tags(&parsed);
+            }
+        }
     }
 }
@@ -0,0 +1,238 @@
+use core::iter::Peekable;
+use core::mem::size_of;
+use core::{ptr, slice};
+
+use super::memory_map::{Entry, EntryType};
+use super::TagHeader;
+
+/// EFI pages are always 4 KiB, whatever page size the kernel uses
+const EFI_PAGE_SIZE: u64 = 4096;
+
+/// The memory map from the UEFI firmware, which is more detailed than the `MemoryMap`
+#[derive(Debug)]
+#[repr(C)]
+pub struct EfiMemoryMap {
+    header: TagHeader,
+    descriptor_size: u32,
+    descriptor_version: u32,
+    // the descriptors are after here
+}
+
+impl EfiMemoryMap {
+    /// Returns the firmware's descriptors, as they are
+    pub fn descriptors(&self) -> Descriptors<'_> {
+        let length = self.header.size as usize - size_of::<EfiMemoryMap>();
+        // SAFETY: The descriptors directly follow this struct, and fill the rest of the tag.
+        let bytes = unsafe {
+            slice::from_raw_parts((self as *const EfiMemoryMap).offset(1) as *const u8, length)
+        };
+        Descriptors::new(bytes, self.descriptor_size as usize)
+    }
+
+    /// Returns the memory map as multiboot entries. The firmware splits memory much more finely
+    /// (i.e. the kernel is in its own descriptor), so neighbouring descriptors that are the same
+    /// `EntryType` are merged.
+    pub fn entries(&self) -> Entries<'_> {
+        Entries {
+            descriptors: self.descriptors().peekable(),
+        }
+    }
+}
+
+/// The type of memory a descriptor covers, as defined by the UEFI specification
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EfiMemoryType {
+    Reserved,
+    LoaderCode,
+    LoaderData,
+    BootServicesCode,
+    BootServicesData,
+    RuntimeServicesCode,
+    RuntimeServicesData,
+    Conventional,
+    Unusable,
+    AcpiReclaim,
+    AcpiNvs,
+    MemoryMappedIo,
+    MemoryMappedIoPortSpace,
+    PalCode,
+    Persistent,
+    Unknown(u32),
+}
+
+impl EfiMemoryType {
+    /// The type of multiboot entry this memory would be. The bootloader has exited boot
+    /// services, so their memory is free, as is the memory the bootloader used (although the
+    /// kernel and multiboot info are in it).
+    pub fn entry_type(&self) -> EntryType {
+        match self {
+            EfiMemoryType::LoaderCode
+            | EfiMemoryType::LoaderData
+            | EfiMemoryType::BootServicesCode
+            | EfiMemoryType::BootServicesData
+            | EfiMemoryType::Conventional => EntryType::Available,
+            EfiMemoryType::AcpiReclaim => EntryType::Acpi,
+            EfiMemoryType::AcpiNvs => EntryType::PreservedHibernation,
+            EfiMemoryType::Unusable => EntryType::Defective,
+            _ => EntryType::Reserved,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+#[repr(C)]
+pub struct Descriptor {
+    memory_type: u32,
+    _padding: u32,
+    physical_start: u64,
+    virtual_start: u64,
+    page_count: u64,
+    attribute: u64,
+}
+
+impl Descriptor {
+    pub fn memory_type(&self) -> EfiMemoryType {
+        match self.memory_type {
+            0 => EfiMemoryType::Reserved,
+            1 => EfiMemoryType::LoaderCode,
+            2 => EfiMemoryType::LoaderData,
+            3 => EfiMemoryType::BootServicesCode,
+            4 => EfiMemoryType::BootServicesData,
+            5 => EfiMemoryType::RuntimeServicesCode,
+            6 => EfiMemoryType::RuntimeServicesData,
+            7 => EfiMemoryType::Conventional,
+            8 => EfiMemoryType::Unusable,
+            9 => EfiMemoryType::AcpiReclaim,
+            10 => EfiMemoryType::AcpiNvs,
+            11 => EfiMemoryType::MemoryMappedIo,
+            12 => EfiMemoryType::MemoryMappedIoPortSpace,
+            13 => EfiMemoryType::PalCode,
+            14 => EfiMemoryType::Persistent,
+            other => EfiMemoryType::Unknown(other),
+        }
+    }
+
+    pub fn start_addr(&self) -> u64 {
+        self.physical_start
+    }
+
+    pub fn end_addr(&self) -> u64 {
+        self.physical_start + self.page_count * EFI_PAGE_SIZE
+    }
+
+    /// The memory's caching and protection attributes, i.e. whether it is used by runtime
+    /// services
+    pub fn attribute(&self) -> u64 {
+        self.attribute
+    }
+}
+
+// The firmware's descriptors may be bigger than `Descriptor` for forward compatibility, so we
+// always move forward by the descriptor size the tag gives
+pub struct Descriptors<'a> {
+    bytes: &'a [u8],
+    descriptor_size: usize,
+}
+
+impl<'a> Descriptors<'a> {
+    fn new(bytes: &'a [u8], descriptor_size: usize) -> Descriptors<'a> {
+        // a size too small to hold a descriptor means there are none we can read
+        let bytes = if descriptor_size < size_of::<Descriptor>() {
+            &[]
+        } else {
+            bytes
+        };
+
+        Descriptors {
+            bytes,
+            descriptor_size,
+        }
+    }
+}
+
+impl<'a> Iterator for Descriptors<'a> {
+    type Item = Descriptor;
+
+    fn next(&mut self) -> Option<Descriptor> {
+        if self.bytes.len() < self.descriptor_size {
+            return None;
+        }
+
+        let (descriptor, rest) = self.bytes.split_at(self.descriptor_size);
+        self.bytes = rest;
+        // SAFETY: There is at least a `Descriptor` worth of bytes, and reading it unaligned
+        //         means the descriptor size doesn't need to keep it aligned.
+        Some(unsafe { ptr::read_unaligned(descriptor.as_ptr() as *const Descriptor) })
+    }
+}
+
+/// The descriptors as multiboot entries, with neighbouring ones of the same type merged
+pub struct Entries<'a> {
+    descriptors: Peekable<Descriptors<'a>>,
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = Entry;
+
+    fn next(&mut self) -> Option<Entry> {
+        let first = self.descriptors.next()?;
+        let entry_type = first.memory_type().entry_type();
+
+        let mut end = first.end_addr();
+        while let Some(next) = self.descriptors.peek() {
+            if next.start_addr() != end || next.memory_type().entry_type() != entry_type {
+                break;
+            }
+            end = next.end_addr();
+            self.descriptors.next();
+        }
+
+        Some(Entry::new(
+            first.start_addr(),
+            end - first.start_addr(),
+            entry_type,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn descriptor(memory_type: u32, start: u64, pages: u64) -> [u8; 48] {
+        let mut bytes = [0u8; 48];
+        bytes[0..4].copy_from_slice(&memory_type.to_le_bytes());
+        bytes[8..16].copy_from_slice(&start.to_le_bytes());
+        bytes[24..32].copy_from_slice(&pages.to_le_bytes());
+        bytes
+    }
+
+    #[test]
+    fn merge_descriptors() {
+        let mut bytes = [0u8; 48 * 5];
+        let descriptors = [
+            descriptor(7, 0x0, 0x9F),
+            // the kernel, then free memory after it
+            descriptor(2, 0x10_0000, 0x100),
+            descriptor(7, 0x20_0000, 0x100),
+            descriptor(4, 0x30_0000, 0x100),
+            descriptor(9, 0x40_0000, 0x10),
+        ];
+        for (chunk, descriptor) in bytes.chunks_exact_mut(48).zip(descriptors.iter()) {
+            chunk.copy_from_slice(descriptor);
+        }
+
+        let mut entries = Entries {
+            descriptors: Descriptors::new(&bytes, 48).peekable(),
+        };
+        let mut next = || {
+            entries
+                .next()
+                .map(|entry| (entry.start_addr(), entry.end_addr(), entry.entry_type()))
+        };
+        assert_eq!(next(), Some((0x0, 0x9_F000, EntryType::Available)));
+        assert_eq!(next(), Some((0x10_0000, 0x40_0000, EntryType::Available)));
+        assert_eq!(next(), Some((0x40_0000, 0x41_0000, EntryType::Acpi)));
+        assert_eq!(next(), None);
+    }
+}
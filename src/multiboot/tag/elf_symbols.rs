@@ -2,6 +2,8 @@ use bitflags::bitflags;
 use core::fmt::{Debug, Formatter, Result};
 use core::marker::PhantomData;
 use core::mem::size_of;
+use core::ops::Deref;
+use core::ptr;
 
 use super::InternalCStr;
 use super::TagHeader;
@@ -9,6 +11,8 @@ use super::TagHeader;
 const ELF32_SHDR_SIZE: u32 = size_of::<Elf32Shdr>() as u32;
 const ELF64_SHDR_SIZE: u32 = size_of::<Elf64Shdr>() as u32;
 
+#[derive(Debug)]
+#[repr(C)]
 pub struct ElfSymbols {
     header: TagHeader,
     num: u32,
@@ -29,6 +33,28 @@ impl ElfSymbols {
         }
     }
 
+    /// Returns the section at `index` in the section header table, i.e. the section another
+    /// section links to
+    pub fn section(&self, index: u32) -> Option<ElfSection> {
+        self.sections().nth(index as usize)
+    }
+
+    /// Checks the section headers are a size we know, and fit within the tag
+    pub(super) fn check(&self) -> core::result::Result<(), &'static str> {
+        if self.entry_size != ELF32_SHDR_SIZE && self.entry_size != ELF64_SHDR_SIZE {
+            return Err("ELF section header size is invalid");
+        }
+
+        let sections_size = self.num as usize * self.entry_size as usize;
+        if size_of::<ElfSymbols>() + sections_size > self.header.size() {
+            return Err("ELF section headers don't fit in the tag");
+        }
+        if self.shndx >= self.num {
+            return Err("ELF string section index is out of bounds");
+        }
+        Ok(())
+    }
+
     fn section_list_start(&self) -> *const u8 {
         // SAFETY: This is safe because `self.offset(1)` will return the first byte past the
         //         ElfSymbols struct in memory, the computed offset cannot overflow an isize, and
@@ -39,20 +65,22 @@ impl ElfSymbols {
     }
 
     fn string_section(&self) -> StringSection {
-        let string_section_addr = self.section_list_start() as u32 + self.shndx * self.entry_size;
-
-        // SAFETY: We know the address is properly aligned, as the entry size is a multiple of 8.
-        //         We also know it points to either a valid 64-bit Shdr or 32-bit Shdr due to the
-        //         multiboot2 specification.
-        let shdr: &dyn ElfShdr = unsafe {
-            match self.entry_size {
-                ELF32_SHDR_SIZE => &*(string_section_addr as *const Elf32Shdr),
-                ELF64_SHDR_SIZE => &*(string_section_addr as *const Elf64Shdr),
-                _ => panic!("Unknown Elf Shdr size!"),
-            }
-        };
-
-        StringSection { shdr }
+        assert!(
+            self.shndx < self.num,
+            "ELF string section index is out of bounds!"
+        );
+        let string_section_addr = self
+            .section_list_start()
+            .wrapping_add(self.shndx as usize * self.entry_size as usize);
+
+        // SAFETY: The index is within the section headers, which `check` made sure fit in the
+        //         tag.
+        let shdr = unsafe { Shdr::read(string_section_addr, self.entry_size) };
+
+        StringSection {
+            shdr,
+            _marker: PhantomData,
+        }
     }
 }
 
@@ -72,18 +100,10 @@ impl<'a> Iterator for Iter<'a> {
             return None;
         }
 
-        // SAFETY: We know the entry is properly aligned, as we are always incrementing the
-        //         `current_entry` pointer by 40 or 64 bytes (which is a multiple of 8). Similarly,
-        //         we know it points to a valid 64-bit Shdr or 32-bit Shdr because we know how many
-        //         entries remain due to the information in the multiboot2 tag, and the list always
-        //         contains valid entries of those types.
-        let shdr: &dyn ElfShdr = unsafe {
-            match self.entry_size {
-                ELF32_SHDR_SIZE => &*(self.current_entry as *const Elf32Shdr),
-                ELF64_SHDR_SIZE => &*(self.current_entry as *const Elf64Shdr),
-                _ => panic!("Unknown Elf Shdr size!"),
-            }
-        };
+        // SAFETY: We know it points to a valid 64-bit Shdr or 32-bit Shdr because we know how
+        //         many entries remain due to the information in the multiboot2 tag, and the list
+        //         always contains valid entries of those types.
+        let shdr = unsafe { Shdr::read(self.current_entry, self.entry_size) };
 
         self.current_entry = (self.current_entry as usize + self.entry_size as usize) as *const u8;
         self.entries_remaining -= 1;
@@ -97,7 +117,7 @@ impl<'a> Iterator for Iter<'a> {
 
 macro_rules! delegate_to_inner {
     ($func:ident, $ret_type:ty) => {
-        fn $func(&self) -> $ret_type {
+        pub fn $func(&self) -> $ret_type {
             self.shdr.$func()
         }
     };
@@ -132,7 +152,7 @@ bitflags! {
 }
 
 pub struct ElfSection<'a> {
-    shdr: &'a dyn ElfShdr,
+    shdr: Shdr,
     string_section: StringSection<'a>,
 }
 
@@ -241,6 +261,37 @@ macro_rules! impl_elf_section {
     };
 }
 
+/// A copy of a section header. The headers follow the 20 byte `ElfSymbols`, so they may not be
+/// aligned, and are read out rather than referenced in place.
+#[derive(Debug, Copy, Clone)]
+enum Shdr {
+    Elf32(Elf32Shdr),
+    Elf64(Elf64Shdr),
+}
+
+impl Shdr {
+    /// # Safety
+    /// `addr` must point to a section header that is `entry_size` bytes, which may be unaligned.
+    unsafe fn read(addr: *const u8, entry_size: u32) -> Shdr {
+        match entry_size {
+            ELF32_SHDR_SIZE => Shdr::Elf32(ptr::read_unaligned(addr as *const Elf32Shdr)),
+            ELF64_SHDR_SIZE => Shdr::Elf64(ptr::read_unaligned(addr as *const Elf64Shdr)),
+            _ => panic!("Unknown Elf Shdr size!"),
+        }
+    }
+}
+
+impl Deref for Shdr {
+    type Target = dyn ElfShdr;
+
+    fn deref(&self) -> &(dyn ElfShdr + 'static) {
+        match self {
+            Shdr::Elf32(shdr) => shdr,
+            Shdr::Elf64(shdr) => shdr,
+        }
+    }
+}
+
 /// Allows us to be generic between Elf32 and Elf64 sections
 trait ElfShdr: core::fmt::Debug {
     fn name_offset(&self) -> u32;
@@ -313,7 +364,8 @@ impl_elf_section!(Elf64Shdr);
 /// Represents an Elf String section
 #[derive(Debug, Copy, Clone)]
 struct StringSection<'a> {
-    shdr: &'a dyn ElfShdr,
+    shdr: Shdr,
+    _marker: PhantomData<&'a u8>,
 }
 
 impl<'a> StringSection<'a> {
@@ -382,8 +434,10 @@ mod test {
         // create a random list of shdrs
         let shdrs = [random_elf64_shdr(); 5];
         // just a random string section NOTE: Don't call name() or lookup() -> this is super unsafe
-        let string_shdr = random_elf64_shdr();
-        let string_section = StringSection { shdr: &string_shdr };
+        let string_section = StringSection {
+            shdr: Shdr::Elf64(random_elf64_shdr()),
+            _marker: PhantomData,
+        };
 
         let iterator = Iter {
             current_entry: shdrs.as_ptr() as *const u8,
@@ -403,8 +457,10 @@ mod test {
         // create a random list of shdrs
         let shdrs = [random_elf32_shdr(); 5];
         // just a random string section NOTE: Don't call name() or lookup() -> this will
-        let string_shdr = random_elf32_shdr();
-        let string_section = StringSection { shdr: &string_shdr };
+        let string_section = StringSection {
+            shdr: Shdr::Elf32(random_elf32_shdr()),
+            _marker: PhantomData,
+        };
 
         let iterator = Iter {
             current_entry: shdrs.as_ptr() as *const u8,
@@ -0,0 +1,128 @@
+use core::mem::size_of;
+use core::slice;
+
+use super::TagHeader;
+
+/// The framebuffer the bootloader set up, which the kernel header asks for
+#[derive(Debug)]
+#[repr(C)]
+pub struct FramebufferInfo {
+    header: TagHeader,
+    addr: u64,
+    pitch: u32,
+    width: u32,
+    height: u32,
+    bpp: u8,
+    framebuffer_type: u8,
+    _reserved: u16,
+    // the color info for the framebuffer type is after here
+}
+
+/// Where one of the color channels is in an RGB pixel
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[repr(C)]
+pub struct ColorField {
+    /// The bit offset of the channel's lowest bit
+    pub position: u8,
+    /// The number of bits in the channel
+    pub size: u8,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[repr(C)]
+pub struct RgbLayout {
+    pub red: ColorField,
+    pub green: ColorField,
+    pub blue: ColorField,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[repr(C)]
+pub struct PaletteColor {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+}
+
+/// How pixels in the framebuffer are laid out
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FramebufferType<'a> {
+    /// Each pixel is an index into the palette
+    Indexed(&'a [PaletteColor]),
+    /// Each pixel holds its color channels directly
+    Rgb(RgbLayout),
+    /// The framebuffer is EGA text (i.e. the VGA text buffer), where each "pixel" is a
+    /// character and its attributes, and the width and height are in characters
+    EgaText,
+    Unknown(u8),
+}
+
+impl FramebufferInfo {
+    /// The physical address of the framebuffer
+    pub fn addr(&self) -> u64 {
+        self.addr
+    }
+
+    /// The number of bytes in each row of pixels, which may be more than the width
+    pub fn pitch(&self) -> u32 {
+        self.pitch
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The number of bits in each pixel
+    pub fn bpp(&self) -> u8 {
+        self.bpp
+    }
+
+    /// The size of the framebuffer in bytes
+    pub fn size(&self) -> usize {
+        self.pitch as usize * self.height as usize
+    }
+
+    /// Checks the tag is big enough for the color info its type has
+    pub(super) fn check(&self) -> Result<(), &'static str> {
+        let color_info = self.header.data_after(size_of::<FramebufferInfo>());
+        let color_info_size = match self.framebuffer_type {
+            0 => {
+                let num_colors = color_info
+                    .get(..size_of::<u16>())
+                    .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+                    .ok_or("framebuffer palette is truncated")?;
+                size_of::<u16>() + num_colors * size_of::<PaletteColor>()
+            }
+            1 => size_of::<RgbLayout>(),
+            _ => 0,
+        };
+
+        if color_info.len() < color_info_size {
+            return Err("framebuffer color info is truncated");
+        }
+        Ok(())
+    }
+
+    pub fn framebuffer_type(&self) -> FramebufferType<'_> {
+        // SAFETY: The color info directly follows the common fields, and its layout depends on
+        //         the type, as defined by the multiboot2 standard. GRUB (unlike the standard)
+        //         gives the number of palette colors as a u16.
+        unsafe {
+            let color_info = (self as *const FramebufferInfo).offset(1) as *const u8;
+            match self.framebuffer_type {
+                0 => {
+                    let num_colors = (color_info as *const u16).read_unaligned();
+                    let palette = color_info.offset(2) as *const PaletteColor;
+                    FramebufferType::Indexed(slice::from_raw_parts(palette, num_colors as usize))
+                }
+                1 => FramebufferType::Rgb((color_info as *const RgbLayout).read_unaligned()),
+                2 => FramebufferType::EgaText,
+                other => FramebufferType::Unknown(other),
+            }
+        }
+    }
+}
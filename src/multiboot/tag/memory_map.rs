@@ -1,5 +1,5 @@
 use core::marker::PhantomData;
-use core::mem::size_of;
+use core::mem::{align_of, size_of};
 
 use super::TagHeader;
 
@@ -30,6 +30,15 @@ impl<'a> MemoryMap<'a> {
         }
     }
 
+    /// Checks the entries are big enough, and stay aligned
+    pub(super) fn check(&self) -> Result<(), &'static str> {
+        let entry_size = self.entry_size as usize;
+        if entry_size < size_of::<Entry>() || entry_size % align_of::<Entry>() != 0 {
+            return Err("memory map entry size is invalid");
+        }
+        Ok(())
+    }
+
     pub fn available(&self) -> impl Iterator<Item = &'a Entry> + 'a {
         self.entries().filter_map(|entry| {
             if entry.entry_type() == EntryType::Available {
@@ -71,7 +80,7 @@ impl<'a> Iterator for Iter<'a> {
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum EntryType {
     Reserved,
     Available,
@@ -80,7 +89,7 @@ pub enum EntryType {
     Defective,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone)]
 #[repr(C)]
 pub struct Entry {
     pub base_addr: u64,
@@ -90,6 +99,23 @@ pub struct Entry {
 }
 
 impl Entry {
+    pub fn new(base_addr: u64, length: u64, entry_type: EntryType) -> Entry {
+        let entry_type = match entry_type {
+            EntryType::Available => 1,
+            EntryType::Reserved => 2,
+            EntryType::Acpi => 3,
+            EntryType::PreservedHibernation => 4,
+            EntryType::Defective => 5,
+        };
+
+        Entry {
+            base_addr,
+            length,
+            entry_type,
+            _reserved: 0,
+        }
+    }
+
     pub fn start_addr(&self) -> u64 {
         self.base_addr
     }
@@ -1,48 +1,78 @@
+pub mod efi_memory_map;
 pub mod elf_symbols;
+pub mod framebuffer;
 pub mod memory_map;
 
-use core::marker::PhantomData;
+use core::fmt::{self, Debug, Formatter};
+use core::mem::size_of;
 use core::{slice, str};
 
+use crate::acpi::{Rsdp, Xsdp};
+pub use efi_memory_map::EfiMemoryMap;
 pub use elf_symbols::ElfSymbols;
+pub use framebuffer::FramebufferInfo;
 pub use memory_map::MemoryMap;
 
+/// Tags always start on an 8 byte boundary
+const TAG_ALIGN: usize = 8;
+
+/// The tag that ends the list
+const END_TAG_TYPE: u32 = 0;
+
+/// Iterates over a tag list that has been checked to be well formed
+#[derive(Clone)]
 pub struct TagIterator<'a> {
-    current_tag: *const TagHeader,
-    _marker: PhantomData<&'a TagHeader>,
+    bytes: &'a [u8],
 }
 
 impl<'a> TagIterator<'a> {
-    /// # Safety
-    /// This method is safe so long as `first_tag` correctly points to the first tag in a valid
-    /// multiboot2 tag list, is non-null, and is properly aligned to an 8 byte boundary.
-    pub unsafe fn new(first_tag: *const TagHeader) -> TagIterator<'a> {
-        TagIterator {
-            current_tag: first_tag,
-            _marker: PhantomData,
+    /// Checks that `bytes` holds a valid tag list, which must be 8 byte aligned and end with an
+    /// end tag. Every tag must fit within `bytes`, and be big enough for its type.
+    pub fn new(bytes: &'a [u8]) -> Result<TagIterator<'a>, &'static str> {
+        if bytes.as_ptr() as usize % TAG_ALIGN != 0 {
+            return Err("tags aren't 8 byte aligned");
         }
+
+        let tags = TagIterator { bytes };
+        let mut unchecked = tags.clone();
+        while unchecked.next_tag()?.is_some() {}
+
+        Ok(tags)
     }
-}
 
-impl<'a> Iterator for TagIterator<'a> {
-    type Item = &'a TagHeader;
+    /// Returns the next tag, or `None` at the end tag
+    fn next_tag(&mut self) -> Result<Option<Tag<'a>>, &'static str> {
+        if self.bytes.len() < size_of::<TagHeader>() {
+            return Err("tag list has no end tag");
+        }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        // SAFETY: We know current_tag is a valid pointer to a tag because the first is correct due
-        //         to the safety of the constructor of the TagIterator, and the following tags are
-        //         correct as multiboot2 ensures size (with alignment) will point to the next tag.
-        let tag = unsafe { &*self.current_tag };
-
-        // if ending tag, return None
-        if tag.tag_type == 0 && tag.size == 8 {
-            return None;
+        // SAFETY: There are enough bytes for a header, and it is aligned as the list is, and each
+        //         tag's size is rounded up to the alignment.
+        let header: &'a TagHeader = unsafe { &*(self.bytes.as_ptr() as *const TagHeader) };
+        if header.size() < size_of::<TagHeader>() || header.size() > self.bytes.len() {
+            return Err("tag size is out of bounds");
         }
 
-        let next_tag_start = self.current_tag as usize + tag.size as usize;
-        let next_tag_addr = (next_tag_start - 1 + 8) & !(8 - 1); // align to 8 byte boundary
-        self.current_tag = next_tag_addr as *const TagHeader;
+        if header.tag_type == END_TAG_TYPE {
+            return match header.size() {
+                8 => Ok(None),
+                _ => Err("end tag has the wrong size"),
+            };
+        }
 
-        Some(tag)
+        let tag = header.parse()?;
+        let next_tag = (header.size() + TAG_ALIGN - 1) & !(TAG_ALIGN - 1);
+        self.bytes = self.bytes.get(next_tag..).unwrap_or(&[]);
+        Ok(Some(tag))
+    }
+}
+
+impl<'a> Iterator for TagIterator<'a> {
+    type Item = Tag<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // the list was checked by `new`, so this never fails
+        self.next_tag().ok().flatten()
     }
 }
 
@@ -77,64 +107,249 @@ impl InternalCStr {
     }
 }
 
-#[derive(Eq, PartialEq)]
+/// The type of a tag, numbered as in the multiboot2 standard so the header can ask for tags by
+/// their `Type`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+#[repr(u32)]
 pub enum Type {
-    MemoryInfo,
-    BiosBoot,
-    BootCmdLine,
-    Modules,
-    ElfSymbols,
-    MemoryMap,
-    BootLoaderName,
-    ApmTable,
-    VbeInfo,
-    FramebufferInfo,
-    Efi32SystemTable,
-    Efi64SystemTable,
-    SMBiosTable,
-    AcpiOldRdsp,
-    AcpiNewRdsp,
-    NetworkingInfo,
-    EfiMemoryMap,
-    EfiBootServicesNotTerminated,
-    Efi32ImageHandle,
-    Efi64ImageHandle,
-    ImageLoadBase,
+    BootCmdLine = 1,
+    BootLoaderName = 2,
+    Modules = 3,
+    MemoryInfo = 4,
+    BiosBoot = 5,
+    MemoryMap = 6,
+    VbeInfo = 7,
+    FramebufferInfo = 8,
+    ElfSymbols = 9,
+    ApmTable = 10,
+    Efi32SystemTable = 11,
+    Efi64SystemTable = 12,
+    SMBiosTable = 13,
+    AcpiOldRdsp = 14,
+    AcpiNewRdsp = 15,
+    NetworkingInfo = 16,
+    EfiMemoryMap = 17,
+    EfiBootServicesNotTerminated = 18,
+    Efi32ImageHandle = 19,
+    Efi64ImageHandle = 20,
+    ImageLoadBase = 21,
     Unknown,
 }
 
+impl Type {
+    /// Every type this kernel knows
+    const KNOWN: [Type; 21] = [
+        Type::BootCmdLine,
+        Type::BootLoaderName,
+        Type::Modules,
+        Type::MemoryInfo,
+        Type::BiosBoot,
+        Type::MemoryMap,
+        Type::VbeInfo,
+        Type::FramebufferInfo,
+        Type::ElfSymbols,
+        Type::ApmTable,
+        Type::Efi32SystemTable,
+        Type::Efi64SystemTable,
+        Type::SMBiosTable,
+        Type::AcpiOldRdsp,
+        Type::AcpiNewRdsp,
+        Type::NetworkingInfo,
+        Type::EfiMemoryMap,
+        Type::EfiBootServicesNotTerminated,
+        Type::Efi32ImageHandle,
+        Type::Efi64ImageHandle,
+        Type::ImageLoadBase,
+    ];
+}
+
+/// A tag, along with its contents
+#[derive(Debug)]
+pub enum Tag<'a> {
+    MemoryInfo(&'a MemoryInfo),
+    BiosBoot(&'a BiosBoot),
+    BootCmdLine(&'a BootCmdLine),
+    Modules(&'a Modules),
+    ElfSymbols(&'a ElfSymbols),
+    MemoryMap(&'a MemoryMap<'a>),
+    BootLoaderName(&'a BootLoaderName),
+    ApmTable(&'a ApmTable),
+    VbeInfo(&'a VbeInfo),
+    FramebufferInfo(&'a FramebufferInfo),
+    Efi32SystemTable(&'a Efi32SystemTable),
+    Efi64SystemTable(&'a Efi64SystemTable),
+    SMBiosTable(&'a SMBiosTable),
+    AcpiOldRdsp(&'a AcpiOldRdsp),
+    AcpiNewRdsp(&'a AcpiNewRdsp),
+    NetworkingInfo(&'a NetworkingInfo),
+    EfiMemoryMap(&'a EfiMemoryMap),
+    EfiBootServicesNotTerminated(&'a EfiBootServicesNotTerminated),
+    Efi32ImageHandle(&'a Efi32ImageHandle),
+    Efi64ImageHandle(&'a Efi64ImageHandle),
+    ImageLoadBase(&'a ImageLoadBase),
+    /// A tag newer than this kernel
+    Unknown(&'a TagHeader),
+}
+
+impl Tag<'_> {
+    pub fn tag_type(&self) -> Type {
+        match self {
+            Tag::MemoryInfo(_) => Type::MemoryInfo,
+            Tag::BiosBoot(_) => Type::BiosBoot,
+            Tag::BootCmdLine(_) => Type::BootCmdLine,
+            Tag::Modules(_) => Type::Modules,
+            Tag::ElfSymbols(_) => Type::ElfSymbols,
+            Tag::MemoryMap(_) => Type::MemoryMap,
+            Tag::BootLoaderName(_) => Type::BootLoaderName,
+            Tag::ApmTable(_) => Type::ApmTable,
+            Tag::VbeInfo(_) => Type::VbeInfo,
+            Tag::FramebufferInfo(_) => Type::FramebufferInfo,
+            Tag::Efi32SystemTable(_) => Type::Efi32SystemTable,
+            Tag::Efi64SystemTable(_) => Type::Efi64SystemTable,
+            Tag::SMBiosTable(_) => Type::SMBiosTable,
+            Tag::AcpiOldRdsp(_) => Type::AcpiOldRdsp,
+            Tag::AcpiNewRdsp(_) => Type::AcpiNewRdsp,
+            Tag::NetworkingInfo(_) => Type::NetworkingInfo,
+            Tag::EfiMemoryMap(_) => Type::EfiMemoryMap,
+            Tag::EfiBootServicesNotTerminated(_) => Type::EfiBootServicesNotTerminated,
+            Tag::Efi32ImageHandle(_) => Type::Efi32ImageHandle,
+            Tag::Efi64ImageHandle(_) => Type::Efi64ImageHandle,
+            Tag::ImageLoadBase(_) => Type::ImageLoadBase,
+            Tag::Unknown(header) => header.tag_type(),
+        }
+    }
+}
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct TagHeader {
-    pub tag_type: u32,
+    tag_type: u32,
     size: u32,
 }
 
 impl TagHeader {
     pub fn tag_type(&self) -> Type {
-        match self.tag_type {
-            4 => Type::MemoryInfo,
-            5 => Type::BiosBoot,
-            1 => Type::BootCmdLine,
-            3 => Type::Modules,
-            9 => Type::ElfSymbols,
-            6 => Type::MemoryMap,
-            2 => Type::BootLoaderName,
-            10 => Type::ApmTable,
-            7 => Type::VbeInfo,
-            8 => Type::FramebufferInfo,
-            11 => Type::Efi32SystemTable,
-            12 => Type::Efi64SystemTable,
-            13 => Type::SMBiosTable,
-            14 => Type::AcpiOldRdsp,
-            15 => Type::AcpiNewRdsp,
-            16 => Type::NetworkingInfo,
-            17 => Type::EfiMemoryMap,
-            18 => Type::EfiBootServicesNotTerminated,
-            19 => Type::Efi32ImageHandle,
-            20 => Type::Efi64ImageHandle,
-            21 => Type::ImageLoadBase,
-            _ => Type::Unknown,
+        Type::KNOWN
+            .iter()
+            .copied()
+            .find(|tag_type| *tag_type as u32 == self.tag_type)
+            .unwrap_or(Type::Unknown)
+    }
+
+    /// Returns the tag this is the header of, checking the tag is big enough for its type and
+    /// that everything it points to within itself is in bounds
+    fn parse(&self) -> Result<Tag<'_>, &'static str> {
+        let min_size = match self.tag_type() {
+            Type::MemoryInfo => size_of::<MemoryInfo>(),
+            Type::BiosBoot => size_of::<BiosBoot>(),
+            Type::BootCmdLine => size_of::<BootCmdLine>(),
+            Type::Modules => size_of::<Modules>(),
+            Type::ElfSymbols => size_of::<ElfSymbols>(),
+            Type::MemoryMap => size_of::<MemoryMap>(),
+            Type::BootLoaderName => size_of::<BootLoaderName>(),
+            Type::ApmTable => size_of::<ApmTable>(),
+            Type::VbeInfo => size_of::<VbeInfo>(),
+            Type::FramebufferInfo => size_of::<FramebufferInfo>(),
+            Type::Efi32SystemTable => size_of::<Efi32SystemTable>(),
+            Type::Efi64SystemTable => size_of::<Efi64SystemTable>(),
+            Type::SMBiosTable => size_of::<SMBiosTable>(),
+            Type::AcpiOldRdsp => size_of::<AcpiOldRdsp>(),
+            Type::AcpiNewRdsp => size_of::<AcpiNewRdsp>(),
+            Type::NetworkingInfo => size_of::<NetworkingInfo>(),
+            Type::EfiMemoryMap => size_of::<EfiMemoryMap>(),
+            Type::EfiBootServicesNotTerminated => size_of::<EfiBootServicesNotTerminated>(),
+            Type::Efi32ImageHandle => size_of::<Efi32ImageHandle>(),
+            Type::Efi64ImageHandle => size_of::<Efi64ImageHandle>(),
+            Type::ImageLoadBase => size_of::<ImageLoadBase>(),
+            Type::Unknown => size_of::<TagHeader>(),
+        };
+        if self.size() < min_size {
+            return Err("tag is too small for its type");
+        }
+
+        // SAFETY: The tag is big enough to be the struct the multiboot2 standard defines for its
+        //         type.
+        let tag = unsafe { self.as_tag() };
+        match tag {
+            Tag::BootCmdLine(_) => self.check_string(size_of::<BootCmdLine>())?,
+            Tag::BootLoaderName(_) => self.check_string(size_of::<BootLoaderName>())?,
+            Tag::Modules(module) => {
+                self.check_string(size_of::<Modules>())?;
+                if module.end_addr() < module.start_addr() {
+                    return Err("module ends before it starts");
+                }
+            }
+            Tag::ElfSymbols(elf_symbols) => elf_symbols.check()?,
+            Tag::MemoryMap(memory_map) => memory_map.check()?,
+            Tag::FramebufferInfo(framebuffer_info) => framebuffer_info.check()?,
+            _ => {}
+        }
+
+        Ok(tag)
+    }
+
+    /// Checks the internal string that ends a tag of `struct_size` is null-terminated UTF-8
+    fn check_string(&self, struct_size: usize) -> Result<(), &'static str> {
+        let bytes = self.data_after(struct_size - size_of::<InternalCStr>());
+        let len = bytes
+            .iter()
+            .position(|byte| *byte == b'\0')
+            .ok_or("string isn't null-terminated")?;
+        str::from_utf8(&bytes[..len]).map_err(|_| "string isn't UTF-8")?;
+        Ok(())
+    }
+
+    /// Returns the tag this is the header of
+    ///
+    /// # Safety
+    /// The tag must be big enough to be the struct for its type.
+    unsafe fn as_tag(&self) -> Tag<'_> {
+        let tag = self as *const TagHeader;
+        match self.tag_type() {
+            Type::MemoryInfo => Tag::MemoryInfo(&*(tag as *const MemoryInfo)),
+            Type::BiosBoot => Tag::BiosBoot(&*(tag as *const BiosBoot)),
+            Type::BootCmdLine => Tag::BootCmdLine(&*(tag as *const BootCmdLine)),
+            Type::Modules => Tag::Modules(&*(tag as *const Modules)),
+            Type::ElfSymbols => Tag::ElfSymbols(&*(tag as *const ElfSymbols)),
+            Type::MemoryMap => Tag::MemoryMap(&*(tag as *const MemoryMap)),
+            Type::BootLoaderName => Tag::BootLoaderName(&*(tag as *const BootLoaderName)),
+            Type::ApmTable => Tag::ApmTable(&*(tag as *const ApmTable)),
+            Type::VbeInfo => Tag::VbeInfo(&*(tag as *const VbeInfo)),
+            Type::FramebufferInfo => Tag::FramebufferInfo(&*(tag as *const FramebufferInfo)),
+            Type::Efi32SystemTable => Tag::Efi32SystemTable(&*(tag as *const Efi32SystemTable)),
+            Type::Efi64SystemTable => Tag::Efi64SystemTable(&*(tag as *const Efi64SystemTable)),
+            Type::SMBiosTable => Tag::SMBiosTable(&*(tag as *const SMBiosTable)),
+            Type::AcpiOldRdsp => Tag::AcpiOldRdsp(&*(tag as *const AcpiOldRdsp)),
+            Type::AcpiNewRdsp => Tag::AcpiNewRdsp(&*(tag as *const AcpiNewRdsp)),
+            Type::NetworkingInfo => Tag::NetworkingInfo(&*(tag as *const NetworkingInfo)),
+            Type::EfiMemoryMap => Tag::EfiMemoryMap(&*(tag as *const EfiMemoryMap)),
+            Type::EfiBootServicesNotTerminated => {
+                Tag::EfiBootServicesNotTerminated(&*(tag as *const EfiBootServicesNotTerminated))
+            }
+            Type::Efi32ImageHandle => Tag::Efi32ImageHandle(&*(tag as *const Efi32ImageHandle)),
+            Type::Efi64ImageHandle => Tag::Efi64ImageHandle(&*(tag as *const Efi64ImageHandle)),
+            Type::ImageLoadBase => Tag::ImageLoadBase(&*(tag as *const ImageLoadBase)),
+            Type::Unknown => Tag::Unknown(self),
+        }
+    }
+
+    /// The raw type, which is what `Type::Unknown` tags can be told apart by
+    pub fn raw_type(&self) -> u32 {
+        self.tag_type
+    }
+
+    /// The size of the tag, including this header
+    pub fn size(&self) -> usize {
+        self.size as usize
+    }
+
+    /// Returns the bytes of the tag after the first `offset`, i.e. the variable sized data after
+    /// a tag's fixed fields
+    fn data_after(&self, offset: usize) -> &[u8] {
+        let length = self.size().saturating_sub(offset);
+        // SAFETY: The tag is `size` bytes long, so these bytes are all within it.
+        unsafe {
+            slice::from_raw_parts((self as *const TagHeader as *const u8).add(offset), length)
         }
     }
 }
@@ -147,6 +362,18 @@ pub struct MemoryInfo {
     mem_upper: u32,
 }
 
+impl MemoryInfo {
+    /// The KiB of memory starting at address 0, at most 640
+    pub fn lower_kib(&self) -> u32 {
+        self.mem_lower
+    }
+
+    /// The KiB of memory starting at 1 MiB, up to the first hole
+    pub fn upper_kib(&self) -> u32 {
+        self.mem_upper
+    }
+}
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct BiosBoot {
@@ -156,6 +383,26 @@ pub struct BiosBoot {
     sub_partition: u32,
 }
 
+/// The value of a partition that isn't used
+const NO_PARTITION: u32 = 0xFFFF_FFFF;
+
+impl BiosBoot {
+    /// The BIOS drive number the kernel was loaded from, i.e. 0x80 for the first hard disk
+    pub fn bios_device(&self) -> u32 {
+        self.bios_dev
+    }
+
+    /// The partition on the drive, if the kernel was loaded from one
+    pub fn partition(&self) -> Option<u32> {
+        Some(self.partition).filter(|partition| *partition != NO_PARTITION)
+    }
+
+    /// The partition within `partition`, i.e. a BSD disklabel partition
+    pub fn sub_partition(&self) -> Option<u32> {
+        Some(self.sub_partition).filter(|partition| *partition != NO_PARTITION)
+    }
+}
+
 #[repr(C, packed)]
 pub struct BootCmdLine {
     header: TagHeader,
@@ -163,13 +410,19 @@ pub struct BootCmdLine {
 }
 
 impl BootCmdLine {
-    fn string(&self) -> &str {
+    pub fn string(&self) -> &str {
         // SAFETY: This is safe, because we know the BootCmdLine tag will have an internal
         //         null-terminated UTF-8 string within the tag itself from the multiboot2 standard.
         unsafe { self.string.to_str() }
     }
 }
 
+impl Debug for BootCmdLine {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_tuple("BootCmdLine").field(&self.string()).finish()
+    }
+}
+
 #[repr(C, packed)]
 pub struct Modules {
     header: TagHeader,
@@ -179,13 +432,39 @@ pub struct Modules {
 }
 
 impl Modules {
-    fn string(&self) -> &str {
+    /// The physical address the module was loaded at
+    pub fn start_addr(&self) -> u64 {
+        self.mod_start as u64
+    }
+
+    /// The physical address just past the end of the module
+    pub fn end_addr(&self) -> u64 {
+        self.mod_end as u64
+    }
+
+    pub fn size(&self) -> usize {
+        (self.mod_end - self.mod_start) as usize
+    }
+
+    /// The command line the module was loaded with, i.e. `initrd` for
+    /// `module2 /boot/initrd initrd`
+    pub fn string(&self) -> &str {
         // SAFETY: This is safe, because we know the Modules tag will have an internal
         //         null-terminated UTF-8 string within the tag itself from the multiboot2 standard.
         unsafe { self.string.to_str() }
     }
 }
 
+impl Debug for Modules {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Modules")
+            .field("start_addr", &self.start_addr())
+            .field("end_addr", &self.end_addr())
+            .field("string", &self.string())
+            .finish()
+    }
+}
+
 #[repr(C, packed)]
 pub struct BootLoaderName {
     header: TagHeader,
@@ -193,13 +472,21 @@ pub struct BootLoaderName {
 }
 
 impl BootLoaderName {
-    fn string(&self) -> &str {
+    pub fn string(&self) -> &str {
         // SAFETY: This is safe, because we know the BootLoaderName tag will have an internal
         //         null-terminated UTF-8 string within the tag itself from the multiboot2 standard.
         unsafe { self.string.to_str() }
     }
 }
 
+impl Debug for BootLoaderName {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_tuple("BootLoaderName")
+            .field(&self.string())
+            .finish()
+    }
+}
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct ApmTable {
@@ -215,21 +502,80 @@ pub struct ApmTable {
     dseg_len: u16,
 }
 
+impl ApmTable {
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    /// The 32 bit protected mode code segment, and the entry point's offset into it
+    pub fn entry_point(&self) -> (u16, u32) {
+        (self.cseg, self.offset)
+    }
+
+    /// The 16 bit protected mode code segment
+    pub fn code_segment_16(&self) -> u16 {
+        self.cseg_16
+    }
+
+    /// The data segment
+    pub fn data_segment(&self) -> u16 {
+        self.dseg
+    }
+
+    pub fn flags(&self) -> u16 {
+        self.flags
+    }
+
+    /// The lengths of the 32 bit code, 16 bit code, and data segments
+    pub fn segment_lengths(&self) -> (u16, u16, u16) {
+        (self.cseg_len, self.cseg_16_len, self.dseg_len)
+    }
+}
+
 #[repr(C)]
 pub struct VbeInfo {
     header: TagHeader,
     vbe_mode: u16,
     vbe_interface_seg: u16,
-    vge_interface_off: u16,
+    vbe_interface_off: u16,
     vbe_interface_len: u16,
     vbe_control_info: [u8; 512],
     vbe_mode_info: [u8; 256],
 }
 
-#[derive(Debug)]
-#[repr(C)]
-struct FramebufferInfo {
-    // TODO
+impl VbeInfo {
+    /// The VBE mode the bootloader set
+    pub fn mode(&self) -> u16 {
+        self.vbe_mode
+    }
+
+    /// The real mode segment, offset and length of the VBE 3.0 protected mode interface
+    pub fn interface(&self) -> (u16, u16, u16) {
+        (
+            self.vbe_interface_seg,
+            self.vbe_interface_off,
+            self.vbe_interface_len,
+        )
+    }
+
+    /// The VBE controller information block, from VBE function 00h
+    pub fn control_info(&self) -> &[u8; 512] {
+        &self.vbe_control_info
+    }
+
+    /// The VBE mode information block, from VBE function 01h
+    pub fn mode_info(&self) -> &[u8; 256] {
+        &self.vbe_mode_info
+    }
+}
+
+impl Debug for VbeInfo {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("VbeInfo")
+            .field("mode", &self.mode())
+            .field("interface", &self.interface())
+            .finish()
+    }
 }
 
 #[derive(Debug)]
@@ -239,6 +585,13 @@ pub struct Efi32SystemTable {
     pointer: u32,
 }
 
+impl Efi32SystemTable {
+    /// The physical address of the UEFI system table
+    pub fn pointer(&self) -> u64 {
+        self.pointer as u64
+    }
+}
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct Efi64SystemTable {
@@ -246,56 +599,184 @@ pub struct Efi64SystemTable {
     pointer: u64,
 }
 
+impl Efi64SystemTable {
+    /// The physical address of the UEFI system table
+    pub fn pointer(&self) -> u64 {
+        self.pointer
+    }
+}
+
+/// A copy of the SMBIOS tables, which bootloaders take to mean the entry point that points to
+/// them
 #[derive(Debug)]
 #[repr(C)]
-struct SMBiosTable {
-    // TODO
+pub struct SMBiosTable {
+    header: TagHeader,
+    major: u8,
+    minor: u8,
+    _reserved: [u8; 6],
+    // the tables are after here
+}
+
+impl SMBiosTable {
+    /// The SMBIOS major and minor version
+    pub fn version(&self) -> (u8, u8) {
+        (self.major, self.minor)
+    }
+
+    /// The copy, as raw bytes
+    pub fn tables(&self) -> &[u8] {
+        self.header.data_after(size_of::<SMBiosTable>())
+    }
 }
 
+/// A copy of the ACPI 1.0 RSDP
 #[derive(Debug)]
 #[repr(C)]
 pub struct AcpiOldRdsp {
-    // TODO
+    header: TagHeader,
+    rsdp: Rsdp,
 }
 
+impl AcpiOldRdsp {
+    /// Returns the RSDP, if its checksum is valid
+    pub fn rsdp(&self) -> Option<&Rsdp> {
+        Some(&self.rsdp).filter(|rsdp| rsdp.is_valid())
+    }
+}
+
+/// A copy of the ACPI 2.0+ RSDP
 #[derive(Debug)]
 #[repr(C)]
 pub struct AcpiNewRdsp {
-    // TODO
+    header: TagHeader,
+    xsdp: Xsdp,
 }
 
+impl AcpiNewRdsp {
+    /// Returns the XSDP, if both its checksums are valid
+    pub fn xsdp(&self) -> Option<&Xsdp> {
+        Some(&self.xsdp).filter(|xsdp| xsdp.is_valid())
+    }
+}
+
+/// The DHCP ACK packet the bootloader got when it booted from the network
 #[derive(Debug)]
 #[repr(C)]
 pub struct NetworkingInfo {
-    // TODO
+    header: TagHeader,
+    // the packet is after here
 }
 
-#[derive(Debug)]
-#[repr(C)]
-pub struct EfiMemoryMap {
-    // TODO
+impl NetworkingInfo {
+    /// The DHCP ACK packet, as raw bytes
+    pub fn dhcp_ack(&self) -> &[u8] {
+        self.header.data_after(size_of::<NetworkingInfo>())
+    }
 }
 
+/// Present if the bootloader left the firmware's boot services running
 #[derive(Debug)]
 #[repr(C)]
 pub struct EfiBootServicesNotTerminated {
-    // TODO
+    header: TagHeader,
 }
 
 #[derive(Debug)]
 #[repr(C)]
 pub struct Efi32ImageHandle {
-    // TODO
+    header: TagHeader,
+    pointer: u32,
+}
+
+impl Efi32ImageHandle {
+    /// The UEFI image handle of the kernel
+    pub fn pointer(&self) -> u64 {
+        self.pointer as u64
+    }
 }
 
 #[derive(Debug)]
 #[repr(C)]
 pub struct Efi64ImageHandle {
-    // TODO
+    header: TagHeader,
+    pointer: u64,
+}
+
+impl Efi64ImageHandle {
+    /// The UEFI image handle of the kernel
+    pub fn pointer(&self) -> u64 {
+        self.pointer
+    }
 }
 
+/// Where the bootloader loaded the kernel, if it was relocated
 #[derive(Debug)]
 #[repr(C)]
 pub struct ImageLoadBase {
-    // TODO
+    header: TagHeader,
+    load_base_addr: u32,
+}
+
+impl ImageLoadBase {
+    /// The physical address of the start of the kernel image
+    pub fn load_base_addr(&self) -> u64 {
+        self.load_base_addr as u64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Appends a tag with `data` after its header, padded to the next tag
+    fn push_tag(bytes: &mut [u8], offset: &mut usize, tag_type: u32, data: &[u8]) {
+        let size = size_of::<TagHeader>() + data.len();
+        bytes[*offset..*offset + 4].copy_from_slice(&tag_type.to_le_bytes());
+        bytes[*offset + 4..*offset + 8].copy_from_slice(&(size as u32).to_le_bytes());
+        bytes[*offset + 8..*offset + size].copy_from_slice(data);
+        *offset = (*offset + size + 7) & !7;
+    }
+
+    #[test]
+    fn typed_tags() {
+        // u64s, so the tags are 8 byte aligned
+        let mut buffer = [0u64; 16];
+        // SAFETY: The buffer is plain memory, and the slice covers exactly it.
+        let bytes = unsafe { slice::from_raw_parts_mut(buffer.as_mut_ptr() as *mut u8, 16 * 8) };
+        let mut offset = 0;
+        push_tag(bytes, &mut offset, 2, b"GRUB 2.04\0");
+        let mut bios_boot = [0xFFu8; 12];
+        bios_boot[..4].copy_from_slice(&0x80u32.to_le_bytes());
+        bios_boot[4..8].copy_from_slice(&1u32.to_le_bytes());
+        push_tag(bytes, &mut offset, 5, &bios_boot);
+        push_tag(bytes, &mut offset, 21, &0x20_0000u32.to_le_bytes());
+        push_tag(bytes, &mut offset, 99, &[]);
+        push_tag(bytes, &mut offset, 0, &[]);
+
+        let mut tags = TagIterator::new(&bytes[..offset]).unwrap();
+        match tags.next() {
+            Some(Tag::BootLoaderName(name)) => assert_eq!(name.string(), "GRUB 2.04"),
+            other => panic!("expected the bootloader name, got {:?}", other),
+        }
+        match tags.next() {
+            Some(Tag::BiosBoot(bios_boot)) => {
+                assert_eq!(bios_boot.bios_device(), 0x80);
+                assert_eq!(bios_boot.partition(), Some(1));
+                assert_eq!(bios_boot.sub_partition(), None);
+            }
+            other => panic!("expected the BIOS boot device, got {:?}", other),
+        }
+        match tags.next() {
+            Some(Tag::ImageLoadBase(load_base)) => {
+                assert_eq!(load_base.load_base_addr(), 0x20_0000)
+            }
+            other => panic!("expected the image load base, got {:?}", other),
+        }
+        match tags.next() {
+            Some(Tag::Unknown(header)) => assert_eq!(header.raw_type(), 99),
+            other => panic!("expected an unknown tag, got {:?}", other),
+        }
+        assert!(tags.next().is_none());
+    }
 }
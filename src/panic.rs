@@ -1,12 +1,54 @@
 use core::panic::PanicInfo;
+#[cfg(not(test))]
+use core::sync::atomic::{AtomicBool, Ordering};
+
+#[cfg(not(test))]
+use crate::backtrace;
 
+#[cfg(not(test))]
+use crate::arch::device::serial::COM1;
+#[cfg(not(test))]
+use crate::framebuffer;
+#[cfg(not(test))]
+use crate::klog;
+#[cfg(not(test))]
+use crate::power;
 use crate::println;
+#[cfg(not(test))]
+use crate::vga::VGA_WRITER;
+
+/// Set by the first panic. If printing the backtrace faults (i.e. the stack is corrupted), we
+/// panic again, and shouldn't try to print it a second time.
+#[cfg(not(test))]
+static PANICKING: AtomicBool = AtomicBool::new(false);
 
 #[cfg(not(test))]
 #[panic_handler]
 fn panic(info: &PanicInfo<'_>) -> ! {
+    // SAFETY: We are never returning to whoever may be holding the writer, so it is fine to
+    //         steal it. Otherwise, panicking while printing would deadlock.
+    unsafe {
+        VGA_WRITER.force_unlock();
+        framebuffer::CONSOLE.force_unlock();
+        COM1.force_unlock();
+        klog::force_unlock();
+    }
+
+    #[cfg(feature = "kernel-test")]
+    crate::ktest::handle_panic(info);
+
     println!("{}", info);
-    loop {}
+    if !PANICKING.swap(true, Ordering::SeqCst) {
+        backtrace::print();
+    }
+
+    let delay = power::PANIC_REBOOT_PARAM.get();
+    if delay > 0 {
+        println!("Rebooting in {} seconds", delay);
+        power::wait_ms(delay * 1000);
+        power::reboot();
+    }
+    power::halt()
 }
 
 #[cfg(not(test))]
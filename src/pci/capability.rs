@@ -0,0 +1,222 @@
+//! The capability list in a function's configuration space, which describes optional features
+//! such as power management and message signalled interrupts.
+
+/// Capability IDs
+pub const POWER_MANAGEMENT: u8 = 0x01;
+pub const MSI: u8 = 0x05;
+pub const VENDOR_SPECIFIC: u8 = 0x09;
+pub const PCI_EXPRESS: u8 = 0x10;
+pub const MSI_X: u8 = 0x11;
+
+/// Set in the status register if the function has a capability list
+const STATUS_CAPABILITIES: u16 = 1 << 4;
+
+/// Where the status register and the pointer to the first capability are
+const STATUS_OFFSET: usize = 0x06;
+const CAPABILITIES_POINTER: usize = 0x34;
+
+/// Capabilities are after the header, and dword aligned
+const HEADER_SIZE: u8 = 0x40;
+const POINTER_MASK: u8 = 0xFC;
+
+/// A malformed list could loop, but there is only room for this many capabilities
+const MAX_CAPABILITIES: usize = 48;
+
+/// Bits in the MSI message control register
+const MSI_64_BIT: u16 = 1 << 7;
+const MSI_PER_VECTOR_MASKING: u16 = 1 << 8;
+const MSI_MULTIPLE_MESSAGE_SHIFT: u16 = 1;
+const MSI_MULTIPLE_MESSAGE_MASK: u16 = 0b111;
+
+/// The MSI-X table size (minus one) in its message control register
+const MSI_X_TABLE_SIZE_MASK: u16 = 0x7FF;
+/// The BAR indicator in the low bits of the table and PBA offset registers
+const MSI_X_BAR_MASK: u32 = 0b111;
+
+/// A capability, and where its registers start in configuration space
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Capability {
+    pub id: u8,
+    pub offset: u8,
+}
+
+impl Capability {
+    /// The name `lspci` gives the capability
+    pub fn name(&self) -> &'static str {
+        match self.id {
+            POWER_MANAGEMENT => "Power Management",
+            MSI => "MSI",
+            VENDOR_SPECIFIC => "Vendor Specific",
+            PCI_EXPRESS => "Express",
+            MSI_X => "MSI-X",
+            _ => "Unknown",
+        }
+    }
+}
+
+/// An iterator over the capabilities in a copy of the first 256 bytes of configuration space. It
+/// stops at the end of the list, or at the first pointer into the header.
+pub struct Capabilities<'a> {
+    config: &'a [u8],
+    next: u8,
+    remaining: usize,
+}
+
+impl<'a> Capabilities<'a> {
+    pub fn new(config: &'a [u8]) -> Capabilities<'a> {
+        let status = read_le(config, STATUS_OFFSET, 2) as u16;
+        let next = match status & STATUS_CAPABILITIES {
+            0 => 0,
+            _ => read_le(config, CAPABILITIES_POINTER, 1) as u8,
+        };
+
+        Capabilities {
+            config,
+            next,
+            remaining: MAX_CAPABILITIES,
+        }
+    }
+}
+
+impl<'a> Iterator for Capabilities<'a> {
+    type Item = Capability;
+
+    fn next(&mut self) -> Option<Capability> {
+        let offset = self.next & POINTER_MASK;
+        if offset < HEADER_SIZE || self.remaining == 0 {
+            return None;
+        }
+
+        self.remaining -= 1;
+        self.next = read_le(self.config, offset as usize + 1, 1) as u8;
+        Some(Capability {
+            id: read_le(self.config, offset as usize, 1) as u8,
+            offset,
+        })
+    }
+}
+
+/// A function's MSI capability
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Msi {
+    /// Where the capability is in configuration space
+    pub offset: u8,
+    /// Whether the message address can be above 4 GiB
+    pub is_64_bit: bool,
+    pub per_vector_masking: bool,
+    /// How many vectors the function can use, a power of 2 up to 32
+    pub max_vectors: u8,
+}
+
+impl Msi {
+    pub fn parse(config: &[u8], capability: Capability) -> Msi {
+        let control = read_le(config, capability.offset as usize + 2, 2) as u16;
+        let multiple_message = (control >> MSI_MULTIPLE_MESSAGE_SHIFT) & MSI_MULTIPLE_MESSAGE_MASK;
+        Msi {
+            offset: capability.offset,
+            is_64_bit: control & MSI_64_BIT != 0,
+            per_vector_masking: control & MSI_PER_VECTOR_MASKING != 0,
+            max_vectors: 1 << multiple_message.min(5),
+        }
+    }
+}
+
+/// A function's MSI-X capability. The vector table and pending bit array are in one of its BARs.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MsiX {
+    /// Where the capability is in configuration space
+    pub offset: u8,
+    /// The number of vectors in the table
+    pub table_size: u16,
+    pub table_bar: u8,
+    pub table_offset: u32,
+    pub pba_bar: u8,
+    pub pba_offset: u32,
+}
+
+impl MsiX {
+    pub fn parse(config: &[u8], capability: Capability) -> MsiX {
+        let offset = capability.offset as usize;
+        let control = read_le(config, offset + 2, 2) as u16;
+        let table = read_le(config, offset + 4, 4) as u32;
+        let pba = read_le(config, offset + 8, 4) as u32;
+        MsiX {
+            offset: capability.offset,
+            table_size: (control & MSI_X_TABLE_SIZE_MASK) + 1,
+            table_bar: (table & MSI_X_BAR_MASK) as u8,
+            table_offset: table & !MSI_X_BAR_MASK,
+            pba_bar: (pba & MSI_X_BAR_MASK) as u8,
+            pba_offset: pba & !MSI_X_BAR_MASK,
+        }
+    }
+}
+
+/// Reads a little endian field out of configuration space, or 0 if it is out of range
+pub(super) fn read_le(config: &[u8], offset: usize, size: usize) -> u64 {
+    let mut value = [0u8; 8];
+    if let Some(field) = config.get(offset..offset + size) {
+        value[..size].copy_from_slice(field);
+    }
+    u64::from_le_bytes(value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_capabilities() {
+        let mut config = [0u8; 256];
+        config[STATUS_OFFSET] = STATUS_CAPABILITIES as u8;
+        config[CAPABILITIES_POINTER] = 0x40;
+        // MSI-X with 4 vectors, the table at BAR 1 offset 0, and the PBA at BAR 1 offset 0x800
+        config[0x40..0x4C].copy_from_slice(&[
+            MSI_X, 0x50, 0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x08, 0x00, 0x00,
+        ]);
+        // 64 bit MSI with 8 vectors, ending the list
+        config[0x50..0x54].copy_from_slice(&[MSI, 0x00, 0x86, 0x00]);
+
+        let capabilities: Vec<Capability> = Capabilities::new(&config).collect();
+        assert_eq!(
+            capabilities,
+            [
+                Capability {
+                    id: MSI_X,
+                    offset: 0x40
+                },
+                Capability {
+                    id: MSI,
+                    offset: 0x50
+                },
+            ]
+        );
+        assert_eq!(
+            MsiX::parse(&config, capabilities[0]),
+            MsiX {
+                offset: 0x40,
+                table_size: 4,
+                table_bar: 1,
+                table_offset: 0,
+                pba_bar: 1,
+                pba_offset: 0x800,
+            }
+        );
+        assert_eq!(
+            Msi::parse(&config, capabilities[1]),
+            Msi {
+                offset: 0x50,
+                is_64_bit: true,
+                per_vector_masking: false,
+                max_vectors: 8,
+            }
+        );
+
+        // a list that loops back on itself ends eventually
+        config[0x51] = 0x40;
+        assert_eq!(Capabilities::new(&config).count(), MAX_CAPABILITIES);
+
+        // without the status bit, the pointer isn't valid
+        config[STATUS_OFFSET] = 0;
+        assert_eq!(Capabilities::new(&config).count(), 0);
+    }
+}
@@ -0,0 +1,55 @@
+//! The names `lspci` gives classes and vendors, for the boot log.
+
+/// The name of a class and subclass, falling back to the class's name alone
+pub fn name(class: u8, subclass: u8) -> &'static str {
+    match (class, subclass) {
+        (0x00, 0x01) => "VGA compatible unclassified device",
+        (0x01, 0x00) => "SCSI storage controller",
+        (0x01, 0x01) => "IDE interface",
+        (0x01, 0x05) => "ATA controller",
+        (0x01, 0x06) => "SATA controller",
+        (0x01, 0x08) => "Non-Volatile memory controller",
+        (0x01, _) => "Mass storage controller",
+        (0x02, 0x00) => "Ethernet controller",
+        (0x02, _) => "Network controller",
+        (0x03, 0x00) => "VGA compatible controller",
+        (0x03, _) => "Display controller",
+        (0x04, 0x01) => "Multimedia audio controller",
+        (0x04, 0x03) => "Audio device",
+        (0x04, _) => "Multimedia controller",
+        (0x05, _) => "Memory controller",
+        (0x06, 0x00) => "Host bridge",
+        (0x06, 0x01) => "ISA bridge",
+        (0x06, 0x04) => "PCI bridge",
+        (0x06, _) => "Bridge",
+        (0x07, 0x00) => "Serial controller",
+        (0x07, _) => "Communication controller",
+        (0x08, 0x00) => "PIC",
+        (0x08, 0x05) => "SD Host controller",
+        (0x08, _) => "System peripheral",
+        (0x09, _) => "Input device controller",
+        (0x0C, 0x03) => "USB controller",
+        (0x0C, 0x05) => "SMBus",
+        (0x0C, _) => "Serial bus controller",
+        (0x0D, _) => "Wireless controller",
+        (0x10, _) => "Encryption controller",
+        (0x11, _) => "Signal processing controller",
+        (0x12, _) => "Processing accelerators",
+        _ => "Unclassified device",
+    }
+}
+
+/// The name of a vendor we are likely to run on, or `None`
+pub fn vendor(vendor_id: u16) -> Option<&'static str> {
+    match vendor_id {
+        0x1022 => Some("Advanced Micro Devices, Inc. [AMD]"),
+        0x10DE => Some("NVIDIA Corporation"),
+        0x10EC => Some("Realtek Semiconductor Co., Ltd."),
+        0x1234 => Some("QEMU"),
+        0x15AD => Some("VMware"),
+        0x1AF4 | 0x1B36 => Some("Red Hat, Inc."),
+        0x8086 => Some("Intel Corporation"),
+        0x80EE => Some("InnoTek Systemberatung GmbH"),
+        _ => None,
+    }
+}
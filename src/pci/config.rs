@@ -0,0 +1,144 @@
+//! Access to each function's configuration space.
+//!
+//! Every PC has the legacy mechanism, which selects a register by writing its address to port
+//! 0xCF8 and then accesses it through port 0xCFC, and only reaches the first 256 bytes of segment
+//! 0. When ACPI has an MCFG table, the whole 4 KiB of every function is memory mapped instead
+//! (the Enhanced Configuration Access Mechanism), which we map one bus at a time as it is used.
+
+use core::mem::size_of;
+use spin::Once;
+
+use super::Address;
+use crate::acpi::mcfg::{Mcfg, McfgEntry};
+use crate::arch::instructions::port::{Port, PortValue};
+use crate::arch::paging::{map_physical, VirtualAddress, MMIO_FLAGS};
+use crate::sync::IrqSafeMutex;
+
+const CONFIG_ADDRESS: u16 = 0xCF8;
+const CONFIG_DATA: u16 = 0xCFC;
+
+/// Set in `CONFIG_ADDRESS` to access configuration space, rather than pass the I/O through
+const CONFIG_ENABLE: u32 = 1 << 31;
+
+/// The most MCFG entries used, any more are ignored
+const MAX_ECAM_REGIONS: usize = 4;
+
+/// Each bus has 32 devices with 8 functions, each with 4 KiB of configuration space
+const BUS_SIZE: usize = 1 << 20;
+const NUM_BUSES: usize = 256;
+
+/// The size of the configuration space reachable through the legacy mechanism
+pub const LEGACY_CONFIG_SIZE: u16 = 256;
+
+static ADDRESS_PORT: IrqSafeMutex<Port<u32>> = IrqSafeMutex::new(Port::new(CONFIG_ADDRESS));
+
+static ECAM_REGIONS: Once<[Option<McfgEntry>; MAX_ECAM_REGIONS]> = Once::new();
+
+/// Where each ECAM region's buses are mapped, once they have been used
+static ECAM_BUSES: IrqSafeMutex<[[Option<VirtualAddress>; NUM_BUSES]; MAX_ECAM_REGIONS]> =
+    IrqSafeMutex::new([[None; NUM_BUSES]; MAX_ECAM_REGIONS]);
+
+/// Finds the memory mapped configuration space, if ACPI has an MCFG table. Must be called after
+/// `acpi::init`, and before any other function here, or the legacy mechanism is always used.
+pub fn init() -> usize {
+    let regions = ECAM_REGIONS.call_once(|| {
+        let mut regions = [None; MAX_ECAM_REGIONS];
+        if let Some(mcfg) = Mcfg::get() {
+            for (region, entry) in regions.iter_mut().zip(mcfg.entries()) {
+                *region = Some(entry);
+            }
+        }
+        regions
+    });
+    regions.iter().flatten().count()
+}
+
+/// Returns the ECAM regions, which each cover a range of buses in a segment
+pub fn ecam_regions() -> impl Iterator<Item = &'static McfgEntry> {
+    ECAM_REGIONS
+        .call_once(|| [None; MAX_ECAM_REGIONS])
+        .iter()
+        .flatten()
+}
+
+/// The value to write to `CONFIG_ADDRESS` to access the dword containing `offset`
+fn legacy_address(address: Address, offset: u16) -> u32 {
+    CONFIG_ENABLE
+        | (address.bus as u32) << 16
+        | (address.device as u32) << 11
+        | (address.function as u32) << 8
+        | (offset as u32 & 0xFC)
+}
+
+/// Returns where the function's configuration space is mapped, if it is in an ECAM region
+fn ecam_function(address: Address) -> Option<VirtualAddress> {
+    let (index, region) = ecam_regions().enumerate().find(|(_, region)| {
+        region.segment_group == address.segment
+            && (region.start_bus..=region.end_bus).contains(&address.bus)
+    })?;
+
+    let mut buses = ECAM_BUSES.lock();
+    let bus = buses[index][address.bus as usize].get_or_insert_with(|| {
+        let function = region.function_address(address.bus, 0, 0).unwrap();
+        map_physical(function, BUS_SIZE, MMIO_FLAGS)
+    });
+    let offset = (address.device as u64) << 15 | (address.function as u64) << 12;
+    Some(VirtualAddress::new(bus.as_u64() + offset))
+}
+
+/// Reads the `T` at `offset` in a function's configuration space. `offset` must be aligned to
+/// the size of `T`, and below `LEGACY_CONFIG_SIZE` unless the function is in an ECAM region.
+pub fn read<T: PortValue>(address: Address, offset: u16) -> T {
+    debug_assert!(offset as usize % size_of::<T>() == 0);
+    match ecam_function(address) {
+        // SAFETY: The function's configuration space is mapped, and reading it has no side
+        //         effects.
+        Some(function) => unsafe {
+            ((function.as_u64() + offset as u64) as *const T).read_volatile()
+        },
+        None => {
+            let mut address_port = ADDRESS_PORT.lock();
+            // SAFETY: Selecting and reading a configuration register has no side effects, and
+            //         the address port is held so nobody can select another in between.
+            unsafe {
+                address_port.write(legacy_address(address, offset));
+                Port::<T>::new(CONFIG_DATA + (offset & 0b11)).read()
+            }
+        }
+    }
+}
+
+/// Writes the `T` at `offset` in a function's configuration space, with the same restrictions
+/// as `read`.
+///
+/// # Safety
+/// Configuration space controls the device, i.e. where its registers are decoded and whether it
+/// can perform DMA.
+pub unsafe fn write<T: PortValue>(address: Address, offset: u16, value: T) {
+    debug_assert!(offset as usize % size_of::<T>() == 0);
+    match ecam_function(address) {
+        Some(function) => ((function.as_u64() + offset as u64) as *mut T).write_volatile(value),
+        None => {
+            let mut address_port = ADDRESS_PORT.lock();
+            address_port.write(legacy_address(address, offset));
+            Port::<T>::new(CONFIG_DATA + (offset & 0b11)).write(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn legacy_addresses() {
+        let address = Address {
+            segment: 0,
+            bus: 0x12,
+            device: 0x1F,
+            function: 3,
+        };
+        assert_eq!(legacy_address(address, 0x3E), 0x8012_FB3C);
+        assert_eq!(legacy_address(address, 0x00), 0x8012_FB00);
+    }
+}
@@ -0,0 +1,274 @@
+//! Interrupts for PCI functions. Drivers get MSI-X or MSI when the function supports it and the
+//! local APIC is enabled, and otherwise fall back to the legacy INTx line the firmware routed
+//! through the PICs.
+
+use core::ptr;
+
+use super::capability::MsiX;
+use super::{config, Address, Bar, Device, COMMAND, MAX_DEVICES};
+use crate::arch::interrupt::{irq, lapic, msi};
+use crate::arch::paging::{map_physical, PhysicalAddress, VirtualAddress, MMIO_FLAGS};
+use crate::debug;
+use crate::sync::IrqSafeMutex;
+
+/// The most handlers a function can have
+pub const MAX_VECTORS: usize = 8;
+
+/// Bits in the command register
+const COMMAND_BUS_MASTER: u16 = 1 << 2;
+const COMMAND_INTERRUPT_DISABLE: u16 = 1 << 10;
+
+/// Bits in the MSI message control register
+const MSI_ENABLE: u16 = 1 << 0;
+const MSI_MULTIPLE_MESSAGE_ENABLE_SHIFT: u16 = 4;
+const MSI_MULTIPLE_MESSAGE_ENABLE_MASK: u16 = 0b111 << MSI_MULTIPLE_MESSAGE_ENABLE_SHIFT;
+
+/// Bits in the MSI-X message control register
+const MSI_X_ENABLE: u16 = 1 << 15;
+const MSI_X_FUNCTION_MASK: u16 = 1 << 14;
+
+/// Each MSI-X table entry is the message address, data, and vector control
+const MSI_X_ENTRY_SIZE: usize = 16;
+const MSI_X_VECTOR_MASKED: u32 = 1 << 0;
+
+/// The IRQ line of a function the firmware didn't route
+const NO_IRQ: u8 = 0xFF;
+
+/// The MSI-X table of each function that has requested MSI-X, which stays mapped so requesting
+/// interrupts again reuses it
+static MSI_X_TABLES: IrqSafeMutex<[Option<(Address, VirtualAddress)>; MAX_DEVICES]> =
+    IrqSafeMutex::new([None; MAX_DEVICES]);
+
+/// How a function's interrupts are delivered
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Interrupt {
+    /// A vector for each handler, in the MSI-X table entry with the same index
+    MsiX {
+        vectors: [u8; MAX_VECTORS],
+        count: usize,
+    },
+    /// A block of `count` vectors starting at `vector`, where the function raises the handler
+    /// with index `i` at `vector + i`
+    Msi { vector: u8, count: usize },
+    /// The legacy IRQ line
+    Intx { irq: u8 },
+}
+
+/// Requests interrupts for `device`, where it raises each handler with its own vector. Tries
+/// MSI-X, then MSI, then INTx, which only works with one handler.
+pub fn request(device: &Device, handlers: &[fn()]) -> Result<Interrupt, &'static str> {
+    if handlers.is_empty() || handlers.len() > MAX_VECTORS {
+        return Err("Invalid number of interrupt handlers");
+    }
+
+    let message_signalled = match lapic::is_enabled() {
+        true => request_msi_x(device, handlers).or_else(|| request_msi(device, handlers)),
+        false => None,
+    };
+    let interrupt = match message_signalled {
+        Some(interrupt) => interrupt,
+        None => request_intx(device, handlers)?,
+    };
+    debug!("PCI device {} interrupts: {:?}", device.address, interrupt);
+    Ok(interrupt)
+}
+
+/// Stops the function raising `interrupt`, and frees it
+pub fn release(device: &Device, interrupt: Interrupt) {
+    let address = device.address;
+    match interrupt {
+        Interrupt::MsiX { vectors, count } => {
+            let msi_x = device.msi_x.unwrap();
+            let control_offset = msi_x.offset as u16 + 2;
+            let control = config::read::<u16>(address, control_offset);
+            // SAFETY: Disabling MSI-X only stops the function raising interrupts.
+            unsafe { config::write(address, control_offset, control & !MSI_X_ENABLE) };
+            for vector in &vectors[..count] {
+                msi::free(*vector);
+            }
+        }
+        Interrupt::Msi { vector, count } => {
+            let control_offset = device.msi.unwrap().offset as u16 + 2;
+            let control = config::read::<u16>(address, control_offset);
+            // SAFETY: Disabling MSI only stops the function raising interrupts.
+            unsafe { config::write(address, control_offset, control & !MSI_ENABLE) };
+            for vector in vector..vector + count as u8 {
+                msi::free(vector);
+            }
+        }
+        Interrupt::Intx { irq } => {
+            set_command(device, 0, COMMAND_INTERRUPT_DISABLE);
+            irq::unregister(irq);
+        }
+    }
+}
+
+/// Programs the MSI-X table, or returns `None` if the function doesn't have MSI-X (or its table
+/// isn't in a memory BAR), or there aren't enough vectors
+fn request_msi_x(device: &Device, handlers: &[fn()]) -> Option<Interrupt> {
+    let msi_x = device
+        .msi_x
+        .filter(|msi_x| handlers.len() <= msi_x.table_size as usize)?;
+    let table = map_msi_x_table(device, &msi_x)?;
+
+    // vector 0 is never in the pool, so it marks a failed allocation
+    let mut vectors = [0; MAX_VECTORS];
+    for vector in &mut vectors[..handlers.len()] {
+        *vector = msi::allocate(1).unwrap_or(0);
+    }
+    if vectors[..handlers.len()].contains(&0) {
+        for vector in vectors.iter().filter(|vector| **vector != 0) {
+            msi::free(*vector);
+        }
+        return None;
+    }
+
+    let address = device.address;
+    let control_offset = msi_x.offset as u16 + 2;
+    let control = config::read::<u16>(address, control_offset);
+    // SAFETY: The function is masked until the table is programmed, and every entry raises one
+    //         of its own vectors.
+    unsafe {
+        config::write(
+            address,
+            control_offset,
+            control | MSI_X_ENABLE | MSI_X_FUNCTION_MASK,
+        )
+    };
+
+    for index in 0..msi_x.table_size as usize {
+        let entry = (table.as_u64() as usize + index * MSI_X_ENTRY_SIZE) as *mut u32;
+        // SAFETY: The table is mapped, and each entry is four dwords.
+        unsafe {
+            match (handlers.get(index), vectors.get(index)) {
+                (Some(handler), Some(vector)) => {
+                    msi::register(*vector, *handler);
+                    let message = msi::message(*vector);
+                    ptr::write_volatile(entry, message.address as u32);
+                    ptr::write_volatile(entry.add(1), (message.address >> 32) as u32);
+                    ptr::write_volatile(entry.add(2), message.data);
+                    ptr::write_volatile(entry.add(3), 0);
+                }
+                _ => ptr::write_volatile(entry.add(3), MSI_X_VECTOR_MASKED),
+            }
+        }
+    }
+
+    // SAFETY: See above.
+    unsafe { config::write(address, control_offset, control | MSI_X_ENABLE) };
+    set_command(device, 0, COMMAND_BUS_MASTER | COMMAND_INTERRUPT_DISABLE);
+
+    Some(Interrupt::MsiX {
+        vectors,
+        count: handlers.len(),
+    })
+}
+
+/// Returns where the function's MSI-X table is mapped, mapping it the first time. Returns `None`
+/// if the table isn't in a memory BAR.
+fn map_msi_x_table(device: &Device, msi_x: &MsiX) -> Option<VirtualAddress> {
+    let mut tables = MSI_X_TABLES.lock();
+    let mapped = tables
+        .iter()
+        .flatten()
+        .find(|(address, _)| *address == device.address);
+    if let Some((_, table)) = mapped {
+        return Some(*table);
+    }
+
+    let table_address = match device.bars.get(msi_x.table_bar as usize) {
+        Some(Some(Bar::Memory { address, .. })) => address + msi_x.table_offset as u64,
+        _ => return None,
+    };
+    // there is a slot for every function in the registry
+    let slot = tables.iter_mut().find(|slot| slot.is_none())?;
+    let table_size = msi_x.table_size as usize * MSI_X_ENTRY_SIZE;
+    let table = map_physical(PhysicalAddress::new(table_address), table_size, MMIO_FLAGS);
+    *slot = Some((device.address, table));
+    Some(table)
+}
+
+/// Programs the MSI capability, or returns `None` if the function doesn't have MSI or there
+/// aren't enough vectors
+fn request_msi(device: &Device, handlers: &[fn()]) -> Option<Interrupt> {
+    let msi = device.msi?;
+    let count = handlers.len().next_power_of_two();
+    if count > msi.max_vectors as usize {
+        return None;
+    }
+    let vector = msi::allocate(count)?;
+    for (index, handler) in handlers.iter().enumerate() {
+        msi::register(vector + index as u8, *handler);
+    }
+
+    let address = device.address;
+    let offset = msi.offset as u16;
+    let message = msi::message(vector);
+    let control = config::read::<u16>(address, offset + 2);
+    // SAFETY: The function only raises the block of vectors we allocated.
+    unsafe {
+        config::write(address, offset + 4, message.address as u32);
+        if msi.is_64_bit {
+            config::write(address, offset + 8, (message.address >> 32) as u32);
+            config::write(address, offset + 0xC, message.data as u16);
+        } else {
+            config::write(address, offset + 8, message.data as u16);
+        }
+        config::write(address, offset + 2, msi_control(control, count));
+    }
+    set_command(device, 0, COMMAND_BUS_MASTER | COMMAND_INTERRUPT_DISABLE);
+
+    Some(Interrupt::Msi { vector, count })
+}
+
+/// The MSI message control register enabling `count` vectors, which must be a power of 2
+fn msi_control(control: u16, count: usize) -> u16 {
+    let multiple_message = count.trailing_zeros() as u16;
+    control & !MSI_MULTIPLE_MESSAGE_ENABLE_MASK
+        | multiple_message << MSI_MULTIPLE_MESSAGE_ENABLE_SHIFT
+        | MSI_ENABLE
+}
+
+/// Registers the handler for the IRQ the function's INTx line is routed to
+fn request_intx(device: &Device, handlers: &[fn()]) -> Result<Interrupt, &'static str> {
+    if handlers.len() != 1 {
+        return Err("INTx only has one interrupt");
+    }
+    if device.interrupt_pin == 0 {
+        return Err("Device doesn't use interrupts");
+    }
+
+    let irq = device.interrupt_line;
+    if irq == NO_IRQ || irq as usize >= irq::NUM_IRQS {
+        return Err("INTx isn't routed to an IRQ");
+    }
+    if irq::is_registered(irq) {
+        return Err("INTx is routed to an IRQ that is already in use");
+    }
+
+    irq::register(irq, handlers[0]);
+    set_command(device, COMMAND_INTERRUPT_DISABLE, 0);
+    Ok(Interrupt::Intx { irq })
+}
+
+/// Clears then sets bits in the function's command register
+fn set_command(device: &Device, clear: u16, set: u16) {
+    let command = config::read::<u16>(device.address, COMMAND);
+    // SAFETY: Only the interrupt and bus master bits are changed, for interrupts the driver
+    //         requested.
+    unsafe { config::write(device.address, COMMAND, command & !clear | set) };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn msi_controls() {
+        // 64 bit, 8 vectors capable
+        assert_eq!(msi_control(0x0086, 1), 0x0087);
+        assert_eq!(msi_control(0x0086, 4), 0x00A7);
+        // the previous number of vectors is replaced
+        assert_eq!(msi_control(0x00B6, 2), 0x0097);
+    }
+}
@@ -0,0 +1,629 @@
+//! PCI enumeration, and a registry of the functions found for drivers to match against.
+//! Every function's configuration space is read through `config`, starting at the host bridges
+//! and following each PCI-to-PCI bridge to the bus behind it.
+
+pub mod capability;
+mod class;
+pub mod config;
+pub mod interrupt;
+
+use core::fmt::{self, Display, Formatter};
+
+use crate::sync::IrqSafeMutex;
+use crate::{debug, info, warn};
+use capability::{Capabilities, Capability, Msi, MsiX};
+use config::LEGACY_CONFIG_SIZE;
+
+/// The most functions kept in the registry, any more are ignored
+const MAX_DEVICES: usize = 64;
+/// The most capabilities kept for each function
+const MAX_CAPABILITIES: usize = 8;
+
+/// Each bus has 32 devices, each with up to 8 functions
+const DEVICES_PER_BUS: u8 = 32;
+const FUNCTIONS_PER_DEVICE: u8 = 8;
+
+/// The vendor ID read from a function that doesn't exist
+const NO_VENDOR: u16 = 0xFFFF;
+
+/// Configuration space registers
+const VENDOR_ID: u16 = 0x00;
+const COMMAND: u16 = 0x04;
+const HEADER_TYPE: u16 = 0x0E;
+const BARS: u16 = 0x10;
+const SECONDARY_BUS: u16 = 0x19;
+const INTERRUPT_LINE: usize = 0x3C;
+const INTERRUPT_PIN: usize = 0x3D;
+
+/// Bits in the command register
+const COMMAND_IO_SPACE: u16 = 1 << 0;
+const COMMAND_MEMORY_SPACE: u16 = 1 << 1;
+
+/// Header types, and the bit set in the header type for multi-function devices
+const HEADER_TYPE_MASK: u8 = 0x7F;
+const HEADER_GENERAL: u8 = 0x00;
+const HEADER_BRIDGE: u8 = 0x01;
+const MULTI_FUNCTION: u8 = 1 << 7;
+
+/// The class of PCI-to-PCI bridges
+const CLASS_BRIDGE: u8 = 0x06;
+const SUBCLASS_PCI_BRIDGE: u8 = 0x04;
+
+/// Bits in the low dword of a BAR
+const BAR_IO: u32 = 1 << 0;
+const BAR_TYPE_MASK: u32 = 0b11 << 1;
+const BAR_TYPE_64: u32 = 0b10 << 1;
+const BAR_PREFETCHABLE: u32 = 1 << 3;
+/// The low bits of a BAR that aren't part of its address
+const BAR_IO_FLAGS: u32 = 0b11;
+const BAR_MEMORY_FLAGS: u32 = 0b1111;
+
+static DEVICES: IrqSafeMutex<[Option<Device>; MAX_DEVICES]> =
+    IrqSafeMutex::new([None; MAX_DEVICES]);
+
+/// Where a function is
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Address {
+    pub segment: u16,
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8,
+}
+
+impl Address {
+    fn new(segment: u16, bus: u8, device: u8, function: u8) -> Address {
+        Address {
+            segment,
+            bus,
+            device,
+            function,
+        }
+    }
+}
+
+/// Formats as `lspci` does, i.e. `00:1f.3`, with the segment only if it isn't 0
+impl Display for Address {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        if self.segment != 0 {
+            write!(f, "{:04x}:", self.segment)?;
+        }
+        write!(
+            f,
+            "{:02x}:{:02x}.{:x}",
+            self.bus, self.device, self.function
+        )
+    }
+}
+
+/// A region of memory or I/O ports the function decodes
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Bar {
+    Memory {
+        address: u64,
+        size: u64,
+        prefetchable: bool,
+        is_64_bit: bool,
+    },
+    Io {
+        port: u32,
+        size: u32,
+    },
+}
+
+impl Bar {
+    /// Decodes a BAR from its value, and what was read back after writing all ones to it (which
+    /// leaves only the address bits the function decodes set). The high dwords are the next BAR,
+    /// and are only used for 64 bit memory BARs. Returns `None` if the BAR isn't implemented.
+    fn decode(value: u64, probed: u64) -> Option<Bar> {
+        let low = value as u32;
+        if low & BAR_IO != 0 {
+            // some functions only decode 16 bits, and read the high bits back as 0
+            let mask = probed as u32 & !BAR_IO_FLAGS & 0xFFFF;
+            if mask == 0 {
+                return None;
+            }
+            return Some(Bar::Io {
+                port: low & !BAR_IO_FLAGS,
+                size: (!mask & 0xFFFF) + 1,
+            });
+        }
+
+        let is_64_bit = low & BAR_TYPE_MASK == BAR_TYPE_64;
+        let (address, mask) = if is_64_bit {
+            (value, probed)
+        } else {
+            (value & 0xFFFF_FFFF, probed | 0xFFFF_FFFF_0000_0000)
+        };
+        let mask = mask & !(BAR_MEMORY_FLAGS as u64);
+        if mask as u32 == 0 && (!is_64_bit || mask == 0) {
+            return None;
+        }
+
+        Some(Bar::Memory {
+            address: address & !(BAR_MEMORY_FLAGS as u64),
+            size: (!mask).wrapping_add(1),
+            prefetchable: low & BAR_PREFETCHABLE != 0,
+            is_64_bit,
+        })
+    }
+
+    pub fn size(&self) -> u64 {
+        match *self {
+            Bar::Memory { size, .. } => size,
+            Bar::Io { size, .. } => size as u64,
+        }
+    }
+}
+
+/// Formats as `lspci -v` does, i.e. `Memory at febf0000 (32-bit, non-prefetchable) [size=4K]`
+impl Display for Bar {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            Bar::Memory {
+                address,
+                size,
+                prefetchable,
+                is_64_bit,
+            } => write!(
+                f,
+                "Memory at {:x} ({}-bit, {}) [size={}]",
+                address,
+                if is_64_bit { 64 } else { 32 },
+                if prefetchable {
+                    "prefetchable"
+                } else {
+                    "non-prefetchable"
+                },
+                Size(size)
+            ),
+            Bar::Io { port, size } => {
+                write!(f, "I/O ports at {:04x} [size={}]", port, Size(size as u64))
+            }
+        }
+    }
+}
+
+/// Formats a size as `lspci` does, i.e. `16M`
+struct Size(u64);
+
+impl Display for Size {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let units = ["", "K", "M", "G", "T"];
+        let mut size = self.0;
+        let mut unit = 0;
+        while size >= 1024 && size % 1024 == 0 && unit + 1 < units.len() {
+            size /= 1024;
+            unit += 1;
+        }
+        write!(f, "{}{}", size, units[unit])
+    }
+}
+
+/// A function found by enumeration
+#[derive(Debug, Copy, Clone)]
+pub struct Device {
+    pub address: Address,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub prog_if: u8,
+    pub revision: u8,
+    /// 0 for most functions, 1 for PCI-to-PCI bridges
+    pub header_type: u8,
+    /// The legacy IRQ the firmware routed INTx to, or 0xFF if it didn't
+    pub interrupt_line: u8,
+    /// Which of INTA# to INTD# the function uses (1 to 4), or 0 if it doesn't use one
+    pub interrupt_pin: u8,
+    /// Each BAR, where the second half of a 64 bit BAR is `None`
+    pub bars: [Option<Bar>; 6],
+    capabilities: [Option<Capability>; MAX_CAPABILITIES],
+    pub msi: Option<Msi>,
+    pub msi_x: Option<MsiX>,
+}
+
+impl Device {
+    /// Reads a function's header, which must exist, sizing its BARs
+    fn read(address: Address) -> Device {
+        let mut config = [0u8; LEGACY_CONFIG_SIZE as usize];
+        for (offset, dword) in (0..LEGACY_CONFIG_SIZE)
+            .step_by(4)
+            .zip(config.chunks_exact_mut(4))
+        {
+            dword.copy_from_slice(&config::read::<u32>(address, offset).to_le_bytes());
+        }
+
+        let header_type = config[HEADER_TYPE as usize] & HEADER_TYPE_MASK;
+        let mut capabilities = [None; MAX_CAPABILITIES];
+        for (slot, capability) in capabilities.iter_mut().zip(Capabilities::new(&config)) {
+            *slot = Some(capability);
+        }
+        let find_capability =
+            |id| Capabilities::new(&config).find(|capability| capability.id == id);
+
+        Device {
+            address,
+            vendor_id: u16::from_le_bytes([config[0x00], config[0x01]]),
+            device_id: u16::from_le_bytes([config[0x02], config[0x03]]),
+            revision: config[0x08],
+            prog_if: config[0x09],
+            subclass: config[0x0A],
+            class: config[0x0B],
+            header_type,
+            interrupt_line: config[INTERRUPT_LINE],
+            interrupt_pin: config[INTERRUPT_PIN],
+            bars: read_bars(address, header_type),
+            capabilities,
+            msi: find_capability(capability::MSI).map(|msi| Msi::parse(&config, msi)),
+            msi_x: find_capability(capability::MSI_X).map(|msi_x| MsiX::parse(&config, msi_x)),
+        }
+    }
+
+    pub fn capabilities(&self) -> impl Iterator<Item = &Capability> {
+        self.capabilities.iter().flatten()
+    }
+
+    /// Returns true if this is a PCI-to-PCI bridge, with another bus behind it
+    fn is_bridge(&self) -> bool {
+        self.header_type == HEADER_BRIDGE
+            && self.class == CLASS_BRIDGE
+            && self.subclass == SUBCLASS_PCI_BRIDGE
+    }
+}
+
+/// Reads and sizes each BAR. Decoding is turned off meanwhile, so the function doesn't claim the
+/// addresses that writing all ones to a BAR would give it.
+fn read_bars(address: Address, header_type: u8) -> [Option<Bar>; 6] {
+    let mut bars = [None; 6];
+    let num_bars = match header_type {
+        HEADER_GENERAL => 6,
+        HEADER_BRIDGE => 2,
+        _ => 0,
+    };
+
+    let command = config::read::<u16>(address, COMMAND);
+    // SAFETY: Turning decoding off only stops the function responding until it is restored.
+    unsafe {
+        config::write(
+            address,
+            COMMAND,
+            command & !(COMMAND_IO_SPACE | COMMAND_MEMORY_SPACE),
+        )
+    };
+
+    let mut index = 0;
+    while index < num_bars {
+        let offset = BARS + index as u16 * 4;
+        let mut value = config::read::<u32>(address, offset) as u64;
+        // SAFETY: Decoding is off.
+        let mut probed = unsafe { probe(address, offset) } as u64;
+
+        let is_64_bit = value as u32 & (BAR_IO | BAR_TYPE_MASK) == BAR_TYPE_64;
+        if is_64_bit && index + 1 < num_bars {
+            value |= (config::read::<u32>(address, offset + 4) as u64) << 32;
+            // SAFETY: Decoding is off.
+            probed |= (unsafe { probe(address, offset + 4) } as u64) << 32;
+        }
+
+        bars[index] = Bar::decode(value, probed);
+        index += if is_64_bit { 2 } else { 1 };
+    }
+
+    // SAFETY: This is the command register the function had.
+    unsafe { config::write(address, COMMAND, command) };
+    bars
+}
+
+/// Writes all ones to a BAR, returning what was read back and restoring it
+///
+/// # Safety
+/// Decoding must be off, or the function may claim any address.
+unsafe fn probe(address: Address, offset: u16) -> u32 {
+    let value = config::read::<u32>(address, offset);
+    config::write::<u32>(address, offset, !0);
+    let probed = config::read::<u32>(address, offset);
+    config::write(address, offset, value);
+    probed
+}
+
+/// The devices a driver supports. Every field that is `Some` must be equal for a device to match.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DeviceMatch {
+    pub vendor_id: Option<u16>,
+    pub device_id: Option<u16>,
+    pub class: Option<u8>,
+    pub subclass: Option<u8>,
+    pub prog_if: Option<u8>,
+}
+
+impl DeviceMatch {
+    /// Matches one specific device
+    pub const fn id(vendor_id: u16, device_id: u16) -> DeviceMatch {
+        DeviceMatch {
+            vendor_id: Some(vendor_id),
+            device_id: Some(device_id),
+            class: None,
+            subclass: None,
+            prog_if: None,
+        }
+    }
+
+    /// Matches any device of a class, i.e. `class(0x01, 0x06)` for SATA controllers
+    pub const fn class(class: u8, subclass: u8) -> DeviceMatch {
+        DeviceMatch {
+            vendor_id: None,
+            device_id: None,
+            class: Some(class),
+            subclass: Some(subclass),
+            prog_if: None,
+        }
+    }
+
+    pub fn matches(&self, device: &Device) -> bool {
+        fn field<T: Eq>(expected: Option<T>, actual: T) -> bool {
+            expected.map_or(true, |expected| expected == actual)
+        }
+
+        field(self.vendor_id, device.vendor_id)
+            && field(self.device_id, device.device_id)
+            && field(self.class, device.class)
+            && field(self.subclass, device.subclass)
+            && field(self.prog_if, device.prog_if)
+    }
+}
+
+/// Returns every function that was found
+pub fn devices() -> impl Iterator<Item = Device> {
+    (0..MAX_DEVICES).filter_map(|index| DEVICES.lock()[index])
+}
+
+/// Returns every function a driver supports
+pub fn find(device_match: DeviceMatch) -> impl Iterator<Item = Device> {
+    devices().filter(move |device| device_match.matches(device))
+}
+
+/// Scans the buses of one segment, following bridges to the buses behind them
+struct Scanner<'a> {
+    segment: u16,
+    /// The buses that have been scanned, so a misconfigured bridge can't make us loop
+    scanned: [u64; 4],
+    devices: &'a mut [Option<Device>; MAX_DEVICES],
+    len: usize,
+}
+
+impl Scanner<'_> {
+    /// Scans from the host bridge at `start_bus`. A multi-function host bridge is several host
+    /// bridges, where each function's bus is `start_bus` plus the function number.
+    fn scan_segment(&mut self, segment: u16, start_bus: u8) {
+        self.segment = segment;
+        self.scanned = [0; 4];
+
+        let host_bridge = Address::new(segment, start_bus, 0, 0);
+        if !is_multi_function(host_bridge) {
+            self.scan_bus(start_bus);
+            return;
+        }
+        for function in 0..FUNCTIONS_PER_DEVICE {
+            if exists(Address::new(segment, start_bus, 0, function)) {
+                self.scan_bus(start_bus.wrapping_add(function));
+            }
+        }
+    }
+
+    fn scan_bus(&mut self, bus: u8) {
+        let (word, bit) = (bus as usize / 64, bus as usize % 64);
+        if self.scanned[word] & 1 << bit != 0 {
+            return;
+        }
+        self.scanned[word] |= 1 << bit;
+
+        for device in 0..DEVICES_PER_BUS {
+            let address = Address::new(self.segment, bus, device, 0);
+            if !exists(address) {
+                continue;
+            }
+
+            let functions = match is_multi_function(address) {
+                true => FUNCTIONS_PER_DEVICE,
+                false => 1,
+            };
+            for function in 0..functions {
+                let address = Address::new(self.segment, bus, device, function);
+                if exists(address) {
+                    self.add(Device::read(address));
+                }
+            }
+        }
+    }
+
+    fn add(&mut self, device: Device) {
+        match self.devices.get_mut(self.len) {
+            Some(slot) => {
+                *slot = Some(device);
+                self.len += 1;
+            }
+            None => warn!("Ignoring PCI device {}, too many devices", device.address),
+        }
+
+        if device.is_bridge() {
+            self.scan_bus(config::read::<u8>(device.address, SECONDARY_BUS));
+        }
+    }
+}
+
+fn exists(address: Address) -> bool {
+    config::read::<u16>(address, VENDOR_ID) != NO_VENDOR
+}
+
+fn is_multi_function(address: Address) -> bool {
+    config::read::<u8>(address, HEADER_TYPE) & MULTI_FUNCTION != 0
+}
+
+/// Logs a function like `lspci -nn`, and its BARs and capabilities like `lspci -v`
+fn log_device(device: &Device) {
+    let vendor = class::vendor(device.vendor_id).unwrap_or("");
+    info!(
+        "{} {} [{:02x}{:02x}]: {}{}Device [{:04x}:{:04x}] (rev {:02x})",
+        device.address,
+        class::name(device.class, device.subclass),
+        device.class,
+        device.subclass,
+        vendor,
+        if vendor.is_empty() { "" } else { " " },
+        device.vendor_id,
+        device.device_id,
+        device.revision
+    );
+
+    if (1..=4).contains(&device.interrupt_pin) {
+        debug!(
+            "    Interrupt: pin {} routed to IRQ {}",
+            (b'A' + device.interrupt_pin - 1) as char,
+            device.interrupt_line
+        );
+    }
+    for bar in device.bars.iter().flatten() {
+        debug!("    {}", bar);
+    }
+    for capability in device.capabilities() {
+        debug!(
+            "    Capabilities: [{:x}] {}",
+            capability.offset,
+            capability.name()
+        );
+    }
+}
+
+/// Finds every function, and logs them like `lspci`. Must be called after `acpi::init`, so the
+/// memory mapped configuration space can be used if there is any.
+pub fn init() {
+    let ecam_regions = config::init();
+    info!(
+        "PCI configuration space through {}",
+        match ecam_regions {
+            0 => "I/O ports",
+            _ => "ECAM",
+        }
+    );
+
+    let mut registry = DEVICES.lock();
+    let mut scanner = Scanner {
+        segment: 0,
+        scanned: [0; 4],
+        devices: &mut registry,
+        len: 0,
+    };
+    if ecam_regions == 0 {
+        scanner.scan_segment(0, 0);
+    }
+    for region in config::ecam_regions() {
+        scanner.scan_segment(region.segment_group, region.start_bus);
+    }
+    let len = scanner.len;
+    drop(registry);
+
+    info!("Found {} PCI functions", len);
+    for device in devices() {
+        log_device(&device);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode_bars() {
+        assert_eq!(
+            Bar::decode(0xFEBF_0000, 0xFFFF_F000),
+            Some(Bar::Memory {
+                address: 0xFEBF_0000,
+                size: 0x1000,
+                prefetchable: false,
+                is_64_bit: false,
+            })
+        );
+        assert_eq!(
+            Bar::decode(0x0000_0001_0000_000C, 0xFFFF_FFFF_FFFF_C00C),
+            Some(Bar::Memory {
+                address: 0x1_0000_0000,
+                size: 0x4000,
+                prefetchable: true,
+                is_64_bit: true,
+            })
+        );
+        // a 64 bit BAR wider than 4 GiB
+        assert_eq!(
+            Bar::decode(0x0000_0080_0000_000C, 0xFFFF_FFC0_0000_000C).map(|bar| bar.size()),
+            Some(0x40_0000_0000)
+        );
+
+        let io = Some(Bar::Io {
+            port: 0xC040,
+            size: 0x20,
+        });
+        assert_eq!(Bar::decode(0xC041, 0xFFFF_FFE1), io);
+        assert_eq!(Bar::decode(0xC041, 0x0000_FFE1), io);
+
+        // unimplemented BARs read back as 0
+        assert_eq!(Bar::decode(0, 0), None);
+        assert_eq!(Bar::decode(0x4, 0x4), None);
+        assert_eq!(Bar::decode(0x1, 0x1), None);
+    }
+
+    #[test]
+    fn format_bars() {
+        let bar = Bar::Memory {
+            address: 0xFD00_0000,
+            size: 16 << 20,
+            prefetchable: true,
+            is_64_bit: false,
+        };
+        assert_eq!(
+            format!("{}", bar),
+            "Memory at fd000000 (32-bit, prefetchable) [size=16M]"
+        );
+        assert_eq!(
+            format!(
+                "{}",
+                Bar::Io {
+                    port: 0x1F0,
+                    size: 8
+                }
+            ),
+            "I/O ports at 01f0 [size=8]"
+        );
+        assert_eq!(format!("{}", Address::new(0, 0, 0x1F, 3)), "00:1f.3");
+        assert_eq!(format!("{}", Address::new(1, 2, 0, 0)), "0001:02:00.0");
+    }
+
+    #[test]
+    fn match_devices() {
+        let device = Device {
+            address: Address::new(0, 0, 0x1F, 2),
+            vendor_id: 0x8086,
+            device_id: 0x2922,
+            class: 0x01,
+            subclass: 0x06,
+            prog_if: 0x01,
+            revision: 0x02,
+            header_type: HEADER_GENERAL,
+            interrupt_line: 10,
+            interrupt_pin: 1,
+            bars: [None; 6],
+            capabilities: [None; MAX_CAPABILITIES],
+            msi: None,
+            msi_x: None,
+        };
+
+        assert!(DeviceMatch::id(0x8086, 0x2922).matches(&device));
+        assert!(!DeviceMatch::id(0x8086, 0x2923).matches(&device));
+        assert!(DeviceMatch::class(0x01, 0x06).matches(&device));
+        assert!(!DeviceMatch::class(0x01, 0x08).matches(&device));
+        assert!(!DeviceMatch {
+            prog_if: Some(0x00),
+            ..DeviceMatch::class(0x01, 0x06)
+        }
+        .matches(&device));
+    }
+}
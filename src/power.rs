@@ -0,0 +1,231 @@
+//! Turning the machine off, or resetting it.
+//!
+//! Shutting down enters the ACPI S5 (soft off) state, which needs the PM1 control registers from
+//! the FADT and the sleep type from the DSDT's `\_S5` object. Rebooting tries the FADT's reset
+//! register, then the 8042 keyboard controller, and finally a triple fault, which always works.
+//! Everything is found by `init`, so a panic can reboot without mapping any tables.
+
+use spin::Once;
+
+use crate::acpi::aml::{self, SleepType};
+use crate::acpi::fadt::Fadt;
+use crate::acpi::{self, GenericAddress};
+use crate::arch::device::i8042;
+use crate::arch::instructions::port::{io_wait, Port};
+use crate::arch::instructions::{self, interrupts};
+use crate::arch::interrupt::idt;
+use crate::arch::paging::{map_physical, PhysicalAddress, VirtualAddress, MMIO_FLAGS};
+use crate::cmdline::Param;
+use crate::time;
+use crate::{info, warn};
+
+/// Bits in the PM1 control registers
+const SCI_ENABLE: u16 = 1 << 0;
+const SLEEP_TYPE_SHIFT: u16 = 10;
+const SLEEP_TYPE_MASK: u16 = 0b111 << SLEEP_TYPE_SHIFT;
+const SLEEP_ENABLE: u16 = 1 << 13;
+
+/// The sleep state that is soft off
+const S5: u8 = 5;
+
+/// Generic Address Structure address spaces
+const SYSTEM_MEMORY: u8 = 0;
+const SYSTEM_IO: u8 = 1;
+
+/// How many times to check that the firmware switched to ACPI mode before giving up
+const MAX_POLLS: usize = 100_000;
+
+/// How long to give each way of resetting before trying the next one
+const RESET_WAIT_MS: u64 = 500;
+
+pub static PANIC_REBOOT_PARAM: Param<u64> = Param::new(
+    "panic",
+    "seconds to wait before rebooting after a panic, or 0 to halt forever",
+    0,
+);
+
+static CONTROL: Once<PowerControl> = Once::new();
+
+/// What ACPI tells us about turning the machine off and resetting it
+#[derive(Debug, Default)]
+struct PowerControl {
+    /// The PM1a control port, and the optional PM1b control port
+    pm1_control: Option<(u16, Option<u16>)>,
+    /// The SLP_TYP values for S5
+    soft_off: Option<SleepType>,
+    /// The SMI command port, and what to write to it to switch to ACPI mode
+    acpi_enable: Option<(u16, u8)>,
+    /// The reset register, and the value to write to it
+    reset: Option<(ResetRegister, u8)>,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum ResetRegister {
+    Port(u16),
+    Memory(VirtualAddress),
+}
+
+impl ResetRegister {
+    /// Maps the register if it is memory mapped. The frame allocator must be initialized.
+    fn new(register: GenericAddress) -> Option<ResetRegister> {
+        match register.address_space {
+            SYSTEM_IO => Some(ResetRegister::Port(register.address as u16)),
+            SYSTEM_MEMORY => {
+                let addr = PhysicalAddress::new(register.address);
+                Some(ResetRegister::Memory(map_physical(addr, 1, MMIO_FLAGS)))
+            }
+            // i.e. PCI configuration space, which we can't write to yet
+            _ => None,
+        }
+    }
+
+    /// # Safety
+    /// This resets the machine (if it works).
+    unsafe fn write(&self, value: u8) {
+        match self {
+            ResetRegister::Port(port) => Port::<u8>::new(*port).write(value),
+            ResetRegister::Memory(addr) => addr.as_ptr_mut::<u8>().write_volatile(value),
+        }
+    }
+}
+
+/// Finds the ACPI registers for powering off and resetting. Must be called after `acpi::init`.
+pub fn init() {
+    let control = CONTROL.call_once(|| {
+        let fadt = match Fadt::get() {
+            Some(fadt) => fadt,
+            None => return PowerControl::default(),
+        };
+
+        let pm1_control = fadt
+            .pm1a_control_block()
+            .map(|pm1a| (pm1a, fadt.pm1b_control_block()));
+        let soft_off = acpi::dsdt().and_then(|dsdt| aml::find_sleep_type(dsdt.data(), S5));
+        let acpi_enable = fadt
+            .smi_command_port()
+            .map(|port| (port, fadt.acpi_enable()));
+        let reset = fadt.reset_register().and_then(|(register, value)| {
+            ResetRegister::new(register).map(|register| (register, value))
+        });
+
+        PowerControl {
+            pm1_control,
+            soft_off,
+            acpi_enable,
+            reset,
+        }
+    });
+
+    if control.pm1_control.is_none() || control.soft_off.is_none() {
+        warn!("ACPI soft off not found, shutting down will only halt");
+    }
+    info!("Power control: {:x?}", control);
+}
+
+/// Turns the machine off. If that doesn't work, this halts instead.
+#[allow(dead_code)] // nothing decides to turn the machine off yet
+pub fn shutdown() -> ! {
+    interrupts::disable();
+    let reason = match CONTROL.r#try() {
+        // SAFETY: We are turning the machine off, so nothing else will run.
+        Some(control) => unsafe { enter_soft_off(control) },
+        None => "power control isn't initialized",
+    };
+
+    warn!(
+        "Couldn't power off ({}), it is now safe to turn off the machine",
+        reason
+    );
+    halt()
+}
+
+/// Enters S5, returning why if the machine is still running.
+///
+/// # Safety
+/// This turns the machine off.
+unsafe fn enter_soft_off(control: &PowerControl) -> &'static str {
+    let (pm1a, pm1b) = match control.pm1_control {
+        Some(ports) => ports,
+        None => return "no PM1 control block",
+    };
+    let sleep_type = match control.soft_off {
+        Some(sleep_type) => sleep_type,
+        None => return "no \\_S5 object in the DSDT",
+    };
+
+    // the firmware may own the power management registers until it is asked to hand them over
+    let mut pm1a_control = Port::<u16>::new(pm1a);
+    if pm1a_control.read() & SCI_ENABLE == 0 {
+        let (smi_command, acpi_enable) = match control.acpi_enable {
+            Some(command) => command,
+            None => return "ACPI mode is off, and can't be turned on",
+        };
+        Port::<u8>::new(smi_command).write(acpi_enable);
+
+        let enabled = (0..MAX_POLLS).any(|_| {
+            io_wait();
+            pm1a_control.read() & SCI_ENABLE != 0
+        });
+        if !enabled {
+            return "the firmware didn't switch to ACPI mode";
+        }
+    }
+
+    enter_sleep_type(pm1a, sleep_type.pm1a);
+    if let Some(pm1b) = pm1b {
+        enter_sleep_type(pm1b, sleep_type.pm1b);
+    }
+
+    wait_ms(RESET_WAIT_MS);
+    "the machine didn't turn off"
+}
+
+/// # Safety
+/// This puts the machine to sleep.
+unsafe fn enter_sleep_type(port: u16, sleep_type: u8) {
+    let mut control = Port::<u16>::new(port);
+    let value = control.read() & !SLEEP_TYPE_MASK;
+    control.write(value | (sleep_type as u16) << SLEEP_TYPE_SHIFT | SLEEP_ENABLE);
+}
+
+/// Resets the machine
+pub fn reboot() -> ! {
+    interrupts::disable();
+
+    // SAFETY: We are resetting the machine, so nothing else will run.
+    unsafe {
+        if let Some((register, value)) = CONTROL.r#try().and_then(|control| control.reset) {
+            register.write(value);
+            wait_ms(RESET_WAIT_MS);
+        }
+
+        i8042::pulse_reset();
+        wait_ms(RESET_WAIT_MS);
+
+        idt::triple_fault()
+    }
+}
+
+/// Stops the CPU for good
+pub fn halt() -> ! {
+    interrupts::disable();
+    loop {
+        instructions::halt();
+    }
+}
+
+/// Busy-waits for about `ms` milliseconds, even with interrupts disabled (so the system timer
+/// doesn't advance). This is only accurate once the TSC is calibrated.
+pub fn wait_ms(ms: u64) {
+    match time::tsc_frequency() {
+        Some(frequency) => {
+            let start = instructions::rdtsc();
+            let cycles = ms * frequency / 1000;
+            while instructions::rdtsc() - start < cycles {
+                core::hint::spin_loop();
+            }
+        }
+        // each io_wait takes at least a microsecond
+        None => (0..ms * 1000).for_each(|_| io_wait()),
+    }
+}
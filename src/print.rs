@@ -1,11 +1,26 @@
 use core::fmt::{Arguments, Write};
 
+use crate::arch::device::serial::COM1;
+use crate::framebuffer;
 use crate::vga::VGA_WRITER;
 
+/// Prints to both the screen and the serial port. The screen is the framebuffer console once it
+/// is started, and the VGA text buffer before then.
 #[cfg(not(test))]
 #[doc(hidden)]
 pub fn _print(args: Arguments) {
-    VGA_WRITER.lock().write_fmt(args).unwrap();
+    match framebuffer::CONSOLE.lock().as_mut() {
+        Some(console) => console.write_fmt(args).unwrap(),
+        None => VGA_WRITER.lock().write_fmt(args).unwrap(),
+    }
+    COM1.lock().write_fmt(args).unwrap();
+}
+
+/// Prints only to the serial port
+#[cfg(not(test))]
+#[doc(hidden)]
+pub fn _serial_print(args: Arguments) {
+    COM1.lock().write_fmt(args).unwrap();
 }
 
 // Allows us to print in the kernel during testing.
@@ -16,6 +31,12 @@ pub fn _print(args: Arguments) {
     std::print!("{}", args)
 }
 
+#[cfg(test)]
+#[doc(hidden)]
+pub fn _serial_print(args: Arguments) {
+    std::print!("{}", args)
+}
+
 #[macro_export]
 macro_rules! print {
     ($($arg:tt)*) => {$crate::print::_print(format_args!($($arg)*))};
@@ -27,3 +48,14 @@ macro_rules! println {
     () => {$crate::print::_print(format_args!("\n"))};
     ($($arg:tt)*) => {$crate::print::_print(format_args!("{}\n", format_args!($($arg)*)))};
 }
+
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => {$crate::print::_serial_print(format_args!($($arg)*))};
+}
+
+#[macro_export]
+macro_rules! serial_println {
+    () => {$crate::print::_serial_print(format_args!("\n"))};
+    ($($arg:tt)*) => {$crate::print::_serial_print(format_args!("{}\n", format_args!($($arg)*)))};
+}
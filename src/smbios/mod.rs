@@ -0,0 +1,278 @@
+//! SMBIOS, the firmware's inventory of the hardware.
+//! An entry point gives the physical address of the structure table, which describes the
+//! firmware, the machine, and each CPU socket and memory slot. The bootloader may pass a copy of
+//! the entry point, otherwise we search the BIOS area for it.
+
+pub mod structure;
+
+use core::slice;
+use spin::Once;
+
+use crate::arch::paging::{map_physical, EntryFlags, PhysicalAddress};
+use crate::cmdline::Param;
+use crate::{info, warn};
+use structure::{Record, Structures};
+
+/// The anchor of the SMBIOS 2.1+ (32 bit) entry point, and of the table it contains
+const ANCHOR_32: &[u8] = b"_SM_";
+const INTERMEDIATE_ANCHOR: &[u8] = b"_DMI_";
+/// The anchor of the SMBIOS 3.0+ (64 bit) entry point
+const ANCHOR_64: &[u8] = b"_SM3_";
+
+/// The smallest entry points can be
+const ENTRY_POINT_32_SIZE: usize = 0x1F;
+const ENTRY_POINT_64_SIZE: usize = 0x18;
+
+/// Where the entry point is in the BIOS area, on a 16 byte boundary
+const BIOS_AREA_START: u64 = 0xF_0000;
+const BIOS_AREA_END: u64 = 0x10_0000;
+
+pub static HWINFO_PARAM: Param<bool> =
+    Param::new("hwinfo", "log the SMBIOS hardware inventory at boot", false);
+
+static TABLE: Once<Option<Table>> = Once::new();
+
+/// Where the structure table is, from an entry point
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct EntryPoint {
+    major: u8,
+    minor: u8,
+    table_address: u64,
+    /// The exact length for 32 bit entry points, but only the maximum for 64 bit ones
+    table_length: usize,
+}
+
+impl EntryPoint {
+    /// Parses a 32 or 64 bit entry point at the start of `bytes`, if its checksums are valid
+    pub fn parse(bytes: &[u8]) -> Option<EntryPoint> {
+        if bytes.starts_with(ANCHOR_64) {
+            let length = *bytes.get(6)? as usize;
+            let entry_point = bytes.get(..length)?;
+            if length < ENTRY_POINT_64_SIZE || checksum(entry_point) != 0 {
+                return None;
+            }
+
+            Some(EntryPoint {
+                major: entry_point[7],
+                minor: entry_point[8],
+                table_address: read_le(entry_point, 0x10, 8),
+                table_length: read_le(entry_point, 0x0C, 4) as usize,
+            })
+        } else if bytes.starts_with(ANCHOR_32) {
+            let length = *bytes.get(5)? as usize;
+            let entry_point = bytes.get(..length)?;
+            if length < ENTRY_POINT_32_SIZE
+                || checksum(entry_point) != 0
+                || !entry_point[0x10..].starts_with(INTERMEDIATE_ANCHOR)
+                || checksum(&entry_point[0x10..ENTRY_POINT_32_SIZE]) != 0
+            {
+                return None;
+            }
+
+            Some(EntryPoint {
+                major: entry_point[6],
+                minor: entry_point[7],
+                table_address: read_le(entry_point, 0x18, 4),
+                table_length: read_le(entry_point, 0x16, 2) as usize,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// The SMBIOS major and minor version
+    pub fn version(&self) -> (u8, u8) {
+        (self.major, self.minor)
+    }
+}
+
+/// The structure table, once it is mapped
+struct Table {
+    version: (u8, u8),
+    structures: &'static [u8],
+}
+
+/// Sums all bytes, which must be 0 for a valid entry point
+fn checksum(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(0, |sum: u8, byte| sum.wrapping_add(*byte))
+}
+
+/// Reads a little endian field out of an entry point
+fn read_le(bytes: &[u8], offset: usize, size: usize) -> u64 {
+    let mut value = [0u8; 8];
+    value[..size].copy_from_slice(&bytes[offset..offset + size]);
+    u64::from_le_bytes(value)
+}
+
+/// Searches `bytes` for an entry point, which is always on a 16 byte boundary. The 64 bit one is
+/// preferred, as the structure table may be above 4 GiB.
+fn scan(bytes: &[u8]) -> Option<EntryPoint> {
+    let entry_points = || {
+        (0..bytes.len())
+            .step_by(16)
+            .filter_map(move |offset| EntryPoint::parse(&bytes[offset..]))
+    };
+    entry_points()
+        .find(|entry_point| entry_point.major >= 3)
+        .or_else(|| entry_points().next())
+}
+
+/// Searches the BIOS area for the entry point
+fn scan_bios_area() -> Option<EntryPoint> {
+    let length = (BIOS_AREA_END - BIOS_AREA_START) as usize;
+    let area = map_physical(
+        PhysicalAddress::new(BIOS_AREA_START),
+        length,
+        EntryFlags::PRESENT,
+    );
+    // SAFETY: We just mapped the entire area.
+    scan(unsafe { slice::from_raw_parts(area.as_ptr::<u8>(), length) })
+}
+
+fn find_table(entry_point: Option<&EntryPoint>) -> Option<Table> {
+    let entry_point = entry_point.copied().or_else(scan_bios_area)?;
+    let table = map_physical(
+        PhysicalAddress::new(entry_point.table_address),
+        entry_point.table_length,
+        EntryFlags::PRESENT,
+    );
+
+    Some(Table {
+        version: entry_point.version(),
+        // SAFETY: The entry point says the table is this long, and we just mapped all of it.
+        structures: unsafe {
+            slice::from_raw_parts(table.as_ptr::<u8>(), entry_point.table_length)
+        },
+    })
+}
+
+/// Finds the structure table, from the copy of the entry point the bootloader passed if there is
+/// one, and logs the inventory if `hwinfo` is given. Must be called after the frame allocator is
+/// initialized, as this maps the table.
+pub fn init(entry_point: Option<&EntryPoint>) {
+    match TABLE.call_once(|| find_table(entry_point)) {
+        Some(table) => info!(
+            "SMBIOS {}.{}, {} structures",
+            table.version.0,
+            table.version.1,
+            structures().count()
+        ),
+        None => warn!("No SMBIOS tables found"),
+    }
+
+    if HWINFO_PARAM.get() {
+        report();
+    }
+}
+
+/// Returns every structure in the table, or none if there isn't one
+pub fn structures() -> Structures<'static> {
+    match TABLE.r#try() {
+        Some(Some(table)) => Structures::new(table.structures),
+        _ => Structures::new(&[]),
+    }
+}
+
+/// Returns every structure we know the type of
+pub fn records() -> impl Iterator<Item = Record<'static>> {
+    structures()
+        .map(|structure| structure.record())
+        .filter(|record| !matches!(record, Record::Unknown(_)))
+}
+
+/// Logs the firmware, machine, CPUs and memory
+pub fn report() {
+    for record in records() {
+        match record {
+            Record::Bios(bios) => info!(
+                "BIOS: {} {} ({})",
+                bios.vendor, bios.version, bios.release_date
+            ),
+            Record::System(system) => info!(
+                "System: {} {} {}, serial {:?}",
+                system.manufacturer, system.product_name, system.version, system.serial_number
+            ),
+            Record::Processor(processor) if processor.populated => info!(
+                "CPU {}: {} {}, {} cores, {} threads, {} MHz",
+                processor.socket,
+                processor.manufacturer,
+                processor.version,
+                processor.core_count.unwrap_or(1),
+                processor.thread_count.unwrap_or(1),
+                processor.current_speed_mhz
+            ),
+            Record::MemoryDevice(device) => {
+                if let Some(size) = device.size {
+                    info!(
+                        "Memory {}: {} MiB, {} MT/s, {} {}",
+                        device.device_locator,
+                        size >> 20,
+                        device.speed_mts.unwrap_or(0),
+                        device.manufacturer,
+                        device.part_number
+                    );
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Builds a 32 bit entry point with correct checksums
+    fn entry_point_32(table_address: u32, table_length: u16) -> [u8; 0x1F] {
+        let mut bytes = [0u8; 0x1F];
+        bytes[..4].copy_from_slice(ANCHOR_32);
+        bytes[5] = 0x1F;
+        bytes[6] = 2;
+        bytes[7] = 8;
+        bytes[0x10..0x15].copy_from_slice(INTERMEDIATE_ANCHOR);
+        bytes[0x16..0x18].copy_from_slice(&table_length.to_le_bytes());
+        bytes[0x18..0x1C].copy_from_slice(&table_address.to_le_bytes());
+        bytes[0x15] = 0u8.wrapping_sub(checksum(&bytes[0x10..]));
+        bytes[4] = 0u8.wrapping_sub(checksum(&bytes));
+        bytes
+    }
+
+    /// Builds a 64 bit entry point with a correct checksum
+    fn entry_point_64(table_address: u64, table_length: u32) -> [u8; 0x18] {
+        let mut bytes = [0u8; 0x18];
+        bytes[..5].copy_from_slice(ANCHOR_64);
+        bytes[6] = 0x18;
+        bytes[7] = 3;
+        bytes[8] = 2;
+        bytes[0x0C..0x10].copy_from_slice(&table_length.to_le_bytes());
+        bytes[0x10..0x18].copy_from_slice(&table_address.to_le_bytes());
+        bytes[5] = 0u8.wrapping_sub(checksum(&bytes));
+        bytes
+    }
+
+    #[test]
+    fn scan_for_entry_point() {
+        let mut area = [0u8; 128];
+        area[32..63].copy_from_slice(&entry_point_32(0xF_1000, 0x200));
+        let entry_point = scan(&area).unwrap();
+        assert_eq!(entry_point.version(), (2, 8));
+        assert_eq!(entry_point.table_address, 0xF_1000);
+        assert_eq!(entry_point.table_length, 0x200);
+
+        // the 64 bit entry point wins, even if it comes later
+        area[80..104].copy_from_slice(&entry_point_64(0x1_0000_0000, 0x1000));
+        let entry_point = scan(&area).unwrap();
+        assert_eq!(entry_point.version(), (3, 2));
+        assert_eq!(entry_point.table_address, 0x1_0000_0000);
+
+        // a bad checksum, or not being on a 16 byte boundary, is skipped
+        area[80 + 0x10] ^= 1;
+        area[32 + 0x1A] ^= 1;
+        assert_eq!(scan(&area), None);
+        let mut area = [0u8; 128];
+        area[40..71].copy_from_slice(&entry_point_32(0xF_1000, 0x200));
+        assert_eq!(scan(&area), None);
+    }
+}
@@ -0,0 +1,307 @@
+//! The structures in the SMBIOS structure table.
+//!
+//! Each structure is a header and formatted area, followed by a set of strings that the formatted
+//! area refers to by their index (starting at 1). The string set always ends with two null bytes.
+
+use core::str;
+
+/// Structure types
+const BIOS_INFO: u8 = 0;
+const SYSTEM_INFO: u8 = 1;
+const PROCESSOR: u8 = 4;
+const MEMORY_DEVICE: u8 = 17;
+const END_OF_TABLE: u8 = 127;
+
+/// The header every structure starts with
+const HEADER_SIZE: usize = 4;
+
+/// The processor status bit set when its socket has a CPU in it
+const SOCKET_POPULATED: u8 = 1 << 6;
+
+/// Memory device sizes that don't fit in the size field
+const SIZE_UNKNOWN: u16 = 0xFFFF;
+const SIZE_EXTENDED: u16 = 0x7FFF;
+/// The size is in KiB rather than MiB
+const SIZE_KIB: u16 = 1 << 15;
+
+/// A structure, without its type decoded
+#[derive(Debug, Copy, Clone)]
+pub struct Structure<'a> {
+    formatted: &'a [u8],
+    strings: &'a [u8],
+}
+
+impl<'a> Structure<'a> {
+    pub fn structure_type(&self) -> u8 {
+        self.formatted[0]
+    }
+
+    /// The handle other structures refer to this one by
+    pub fn handle(&self) -> u16 {
+        self.field(2, 2) as u16
+    }
+
+    /// The formatted area, including the header
+    pub fn formatted(&self) -> &'a [u8] {
+        self.formatted
+    }
+
+    /// Returns every string in the string set, in order
+    pub fn strings(&self) -> impl Iterator<Item = &'a str> {
+        self.strings
+            .split(|byte| *byte == b'\0')
+            .filter(|string| !string.is_empty())
+            .map(|string| str::from_utf8(string).unwrap_or(""))
+    }
+
+    /// Reads a little endian field out of the formatted area, or 0 if this structure is too short
+    /// to have it (i.e. it is from an older SMBIOS version)
+    fn field(&self, offset: usize, size: usize) -> u64 {
+        let mut value = [0u8; 8];
+        if let Some(field) = self.formatted.get(offset..offset + size) {
+            value[..size].copy_from_slice(field);
+        }
+        u64::from_le_bytes(value)
+    }
+
+    /// Returns true if the formatted area has the `size` byte field at `offset`
+    fn has_field(&self, offset: usize, size: usize) -> bool {
+        self.formatted.len() >= offset + size
+    }
+
+    /// Returns the string the byte at `offset` refers to, or an empty string if it is 0 (or out
+    /// of range)
+    fn string(&self, offset: usize) -> &'a str {
+        match self.field(offset, 1) as usize {
+            0 => "",
+            index => self.strings().nth(index - 1).unwrap_or(""),
+        }
+    }
+
+    /// Decodes the structure, if it is a type we know
+    pub fn record(&self) -> Record<'a> {
+        match self.structure_type() {
+            BIOS_INFO if self.has_field(0x09, 1) => Record::Bios(BiosInfo {
+                vendor: self.string(0x04),
+                version: self.string(0x05),
+                release_date: self.string(0x08),
+                rom_size_kib: (self.field(0x09, 1) as u32 + 1) * 64,
+            }),
+            SYSTEM_INFO if self.has_field(0x07, 1) => Record::System(SystemInfo {
+                manufacturer: self.string(0x04),
+                product_name: self.string(0x05),
+                version: self.string(0x06),
+                serial_number: self.string(0x07),
+                uuid: Some(0x08)
+                    .filter(|offset| self.has_field(*offset, 16))
+                    .map(|offset| {
+                        let mut uuid = [0; 16];
+                        uuid.copy_from_slice(&self.formatted[offset..offset + 16]);
+                        uuid
+                    }),
+            }),
+            PROCESSOR if self.has_field(0x18, 1) => Record::Processor(Processor {
+                socket: self.string(0x04),
+                manufacturer: self.string(0x07),
+                version: self.string(0x10),
+                max_speed_mhz: self.field(0x14, 2) as u16,
+                current_speed_mhz: self.field(0x16, 2) as u16,
+                populated: self.field(0x18, 1) as u8 & SOCKET_POPULATED != 0,
+                core_count: Some(self.field(0x23, 1) as u8).filter(|_| self.has_field(0x23, 1)),
+                thread_count: Some(self.field(0x25, 1) as u8).filter(|_| self.has_field(0x25, 1)),
+            }),
+            MEMORY_DEVICE if self.has_field(0x12, 1) => Record::MemoryDevice(MemoryDevice {
+                size: self.memory_device_size(),
+                device_locator: self.string(0x10),
+                bank_locator: self.string(0x11),
+                memory_type: self.field(0x12, 1) as u8,
+                speed_mts: Some(self.field(0x15, 2) as u16).filter(|speed| *speed != 0),
+                manufacturer: self.string(0x17),
+                part_number: self.string(0x1A),
+            }),
+            structure_type => Record::Unknown(structure_type),
+        }
+    }
+
+    /// The size of a memory device in bytes, or `None` if the slot is empty or the size unknown
+    fn memory_device_size(&self) -> Option<u64> {
+        match self.field(0x0C, 2) as u16 {
+            0 | SIZE_UNKNOWN => None,
+            SIZE_EXTENDED => Some(self.field(0x1C, 4) << 20).filter(|size| *size != 0),
+            size if size & SIZE_KIB != 0 => Some(((size & !SIZE_KIB) as u64) << 10),
+            size => Some((size as u64) << 20),
+        }
+    }
+}
+
+/// A structure we know the type of
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Record<'a> {
+    Bios(BiosInfo<'a>),
+    System(SystemInfo<'a>),
+    Processor(Processor<'a>),
+    MemoryDevice(MemoryDevice<'a>),
+    /// A structure type we don't parse (or one too short for its type)
+    Unknown(u8),
+}
+
+/// The firmware's vendor and version. Any string the firmware leaves out is empty.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct BiosInfo<'a> {
+    pub vendor: &'a str,
+    pub version: &'a str,
+    pub release_date: &'a str,
+    pub rom_size_kib: u32,
+}
+
+/// What the machine is. Any string the firmware leaves out is empty.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct SystemInfo<'a> {
+    pub manufacturer: &'a str,
+    pub product_name: &'a str,
+    pub version: &'a str,
+    pub serial_number: &'a str,
+    /// SMBIOS 2.1+
+    pub uuid: Option<[u8; 16]>,
+}
+
+/// A CPU socket. Any string the firmware leaves out is empty.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Processor<'a> {
+    pub socket: &'a str,
+    pub manufacturer: &'a str,
+    pub version: &'a str,
+    /// 0 if unknown
+    pub max_speed_mhz: u16,
+    /// 0 if unknown
+    pub current_speed_mhz: u16,
+    /// Whether there is a CPU in the socket
+    pub populated: bool,
+    /// SMBIOS 2.5+
+    pub core_count: Option<u8>,
+    /// SMBIOS 2.5+
+    pub thread_count: Option<u8>,
+}
+
+/// A memory slot, i.e. a DIMM. Any string the firmware leaves out is empty.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct MemoryDevice<'a> {
+    /// The size in bytes, or `None` if the slot is empty
+    pub size: Option<u64>,
+    /// The slot, i.e. `DIMM 0`
+    pub device_locator: &'a str,
+    pub bank_locator: &'a str,
+    /// i.e. 0x1A for DDR4
+    pub memory_type: u8,
+    /// SMBIOS 2.3+, in megatransfers per second
+    pub speed_mts: Option<u16>,
+    /// SMBIOS 2.3+
+    pub manufacturer: &'a str,
+    /// SMBIOS 2.3+
+    pub part_number: &'a str,
+}
+
+/// An iterator over the structures in a structure table. It stops at the end of table structure,
+/// or the first malformed one.
+#[derive(Clone)]
+pub struct Structures<'a> {
+    rest: &'a [u8],
+}
+
+impl<'a> Structures<'a> {
+    pub fn new(table: &'a [u8]) -> Structures<'a> {
+        Structures { rest: table }
+    }
+}
+
+impl<'a> Iterator for Structures<'a> {
+    type Item = Structure<'a>;
+
+    fn next(&mut self) -> Option<Structure<'a>> {
+        let rest = self.rest;
+        self.rest = &[];
+
+        let length = *rest.get(1)? as usize;
+        if length < HEADER_SIZE || length > rest.len() || rest[0] == END_OF_TABLE {
+            return None;
+        }
+
+        let (formatted, after) = rest.split_at(length);
+        let strings_len = after.windows(2).position(|bytes| bytes == [0, 0])?;
+        self.rest = &after[strings_len + 2..];
+        Some(Structure {
+            formatted,
+            strings: &after[..strings_len],
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_structures() {
+        #[rustfmt::skip]
+        let table = [
+            // BIOS information, as QEMU sets it up
+            BIOS_INFO, 0x18, 0x00, 0x00, 0x01, 0x02, 0x00, 0xE8, 0x03, 0x00,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            b'S', b'e', b'a', b'B', b'I', b'O', b'S', 0, b'1', b'.', b'0', 0, 0,
+            // a processor from SMBIOS 2.0, with no strings
+            PROCESSOR, 0x1A, 0x00, 0x04, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0xD0, 0x07, 0xD0, 0x07, 0x41, 0,
+            0, 0,
+            // a memory device of 16 GiB, with an extended size
+            MEMORY_DEVICE, 0x22, 0x00, 0x11, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0x7F, 0, 0,
+            0x01, 0x00, 0x1A, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x00, 0x40, 0x00, 0x00, 0, 0,
+            b'D', b'I', b'M', b'M', b' ', b'0', 0, 0,
+            END_OF_TABLE, 0x04, 0xFF, 0xFF, 0, 0,
+            // nothing is read past the end of table
+            SYSTEM_INFO, 0x08, 0x00, 0x00, 0, 0, 0, 0, 0, 0,
+        ];
+
+        let structures: Vec<Structure> = Structures::new(&table).collect();
+        assert_eq!(structures.len(), 3);
+        assert_eq!(structures[1].handle(), 0x0400);
+        assert_eq!(
+            structures[0].strings().collect::<Vec<_>>(),
+            ["SeaBIOS", "1.0"]
+        );
+        assert_eq!(
+            structures[0].record(),
+            Record::Bios(BiosInfo {
+                vendor: "SeaBIOS",
+                version: "1.0",
+                release_date: "",
+                rom_size_kib: 64,
+            })
+        );
+        assert_eq!(
+            structures[1].record(),
+            Record::Processor(Processor {
+                socket: "",
+                manufacturer: "",
+                version: "",
+                max_speed_mhz: 2000,
+                current_speed_mhz: 2000,
+                populated: true,
+                core_count: None,
+                thread_count: None,
+            })
+        );
+        match structures[2].record() {
+            Record::MemoryDevice(device) => {
+                assert_eq!(device.size, Some(16 << 30));
+                assert_eq!(device.device_locator, "DIMM 0");
+                assert_eq!(device.memory_type, 0x1A);
+                assert_eq!(device.speed_mts, None);
+            }
+            record => panic!("{:?} isn't a memory device", record),
+        }
+
+        // a structure with no string set terminator, or a bad length, ends the table
+        assert_eq!(Structures::new(&table[..20]).count(), 0);
+        assert_eq!(Structures::new(&[SYSTEM_INFO, 0x02, 0, 0, 0, 0]).count(), 0);
+    }
+}
@@ -0,0 +1,198 @@
+//! Synchronization primitives that are safe to use from both normal kernel code and interrupt
+//! handlers.
+
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+use spin::{Mutex, MutexGuard};
+
+use crate::arch::instructions::interrupts;
+
+/// A spinlock that disables interrupts while it is held.
+///
+/// A plain `spin::Mutex` will deadlock if an interrupt handler tries to take a lock that the
+/// interrupted code is holding, as the interrupted code can never run to release it. Disabling
+/// interrupts for as long as the lock is held prevents this. The previous state of RFLAGS.IF is
+/// saved when locking, and restored once the guard is dropped, so these can be safely nested.
+pub struct IrqSafeMutex<T: ?Sized> {
+    inner: Mutex<T>,
+}
+
+impl<T> IrqSafeMutex<T> {
+    pub const fn new(value: T) -> IrqSafeMutex<T> {
+        IrqSafeMutex {
+            inner: Mutex::new(value),
+        }
+    }
+}
+
+impl<T: ?Sized> IrqSafeMutex<T> {
+    /// Disables interrupts and locks the mutex, spinning until it is available.
+    ///
+    /// In debug builds, this will panic if the lock is already held. As we only run on a single
+    /// core and interrupts are now disabled, nobody could ever release it, so we would otherwise
+    /// spin forever.
+    pub fn lock(&self) -> IrqSafeMutexGuard<T> {
+        let interrupts_were_enabled = interrupts::are_enabled();
+        interrupts::disable();
+
+        #[cfg(debug_assertions)]
+        let guard = match self.inner.try_lock() {
+            Some(guard) => guard,
+            None => panic!("Deadlock detected: IrqSafeMutex is already held!"),
+        };
+
+        #[cfg(not(debug_assertions))]
+        let guard = self.inner.lock();
+
+        IrqSafeMutexGuard {
+            guard: ManuallyDrop::new(guard),
+            interrupts_were_enabled,
+        }
+    }
+
+    /// Attempts to lock the mutex without spinning. Interrupts are left untouched if the lock
+    /// could not be taken.
+    pub fn try_lock(&self) -> Option<IrqSafeMutexGuard<T>> {
+        let interrupts_were_enabled = interrupts::are_enabled();
+        interrupts::disable();
+
+        match self.inner.try_lock() {
+            Some(guard) => Some(IrqSafeMutexGuard {
+                guard: ManuallyDrop::new(guard),
+                interrupts_were_enabled,
+            }),
+            None => {
+                if interrupts_were_enabled {
+                    interrupts::enable();
+                }
+                None
+            }
+        }
+    }
+
+    /// Forcibly unlocks the mutex. This does not touch the interrupt flag.
+    ///
+    /// # Safety
+    /// This is only safe if the current holder of the lock will never use it again, i.e. when
+    /// we are panicking and need to print through a lock that may have been held.
+    pub unsafe fn force_unlock(&self) {
+        self.inner.force_unlock()
+    }
+}
+
+// SAFETY: These mirror the implementations for `spin::Mutex`.
+unsafe impl<T: ?Sized + Send> Sync for IrqSafeMutex<T> {}
+unsafe impl<T: ?Sized + Send> Send for IrqSafeMutex<T> {}
+
+/// A guard for an `IrqSafeMutex`. Releases the lock and restores the previous interrupt state
+/// when dropped.
+pub struct IrqSafeMutexGuard<'a, T: ?Sized + 'a> {
+    guard: ManuallyDrop<MutexGuard<'a, T>>,
+    interrupts_were_enabled: bool,
+}
+
+impl<'a, T: ?Sized> Deref for IrqSafeMutexGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &*self.guard
+    }
+}
+
+impl<'a, T: ?Sized> DerefMut for IrqSafeMutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut *self.guard
+    }
+}
+
+impl<'a, T: ?Sized> Drop for IrqSafeMutexGuard<'a, T> {
+    fn drop(&mut self) {
+        // The lock must be released before interrupts are enabled again, otherwise a pending
+        // interrupt could try to take it and deadlock.
+        // SAFETY: The guard is never used again after this.
+        unsafe { ManuallyDrop::drop(&mut self.guard) };
+
+        if self.interrupts_were_enabled {
+            interrupts::enable();
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lock_and_restore() {
+        let mutex = IrqSafeMutex::new(5);
+        interrupts::enable();
+
+        {
+            let mut guard = mutex.lock();
+            assert!(!interrupts::are_enabled());
+            *guard += 1;
+        }
+
+        assert!(interrupts::are_enabled());
+        assert_eq!(*mutex.lock(), 6);
+    }
+
+    #[test]
+    fn nested_locks() {
+        let first = IrqSafeMutex::new(());
+        let second = IrqSafeMutex::new(());
+        interrupts::enable();
+
+        {
+            let _first = first.lock();
+            {
+                let _second = second.lock();
+            }
+            // the inner guard must not re-enable interrupts while the outer is still held
+            assert!(!interrupts::are_enabled());
+        }
+
+        assert!(interrupts::are_enabled());
+    }
+
+    #[test]
+    fn try_lock_held() {
+        let mutex = IrqSafeMutex::new(());
+        let _guard = mutex.lock();
+
+        assert!(mutex.try_lock().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Deadlock detected: IrqSafeMutex is already held!")]
+    fn deadlock() {
+        let mutex = IrqSafeMutex::new(());
+        let _guard = mutex.lock();
+        let _deadlock = mutex.lock();
+    }
+}
+
+#[cfg(feature = "kernel-test")]
+mod kernel_test {
+    use super::*;
+    use juntos_macros::kernel_test;
+
+    #[kernel_test]
+    fn lock_disables_interrupts() {
+        let mutex = IrqSafeMutex::new(0);
+        interrupts::enable();
+
+        {
+            let _guard = mutex.lock();
+            assert!(!interrupts::are_enabled());
+        }
+        assert!(interrupts::are_enabled());
+    }
+
+    #[kernel_test(should_panic)]
+    fn deadlock_is_detected() {
+        let mutex = IrqSafeMutex::new(0);
+        let _first = mutex.lock();
+        let _second = mutex.lock();
+    }
+}
@@ -0,0 +1,182 @@
+//! Abstractions over the hardware timers.
+//!
+//! A `ClockSource` is a free-running counter that is read to tell the time, while a `ClockEvent`
+//! is a device that can raise an interrupt at some point in the future. Drivers register the
+//! devices they find, and `select` picks the best of each by their rating.
+
+use bitflags::bitflags;
+
+use super::NANOS_PER_SEC;
+use crate::info;
+use crate::sync::IrqSafeMutex;
+
+/// The maximum number of sources and events that can be registered
+const MAX_DEVICES: usize = 4;
+
+static REGISTRY: IrqSafeMutex<Registry> = IrqSafeMutex::new(Registry {
+    sources: [None; MAX_DEVICES],
+    events: [None; MAX_DEVICES],
+});
+
+static CURRENT_SOURCE: IrqSafeMutex<Option<CurrentSource>> = IrqSafeMutex::new(None);
+static CURRENT_EVENT: IrqSafeMutex<Option<&'static dyn ClockEvent>> = IrqSafeMutex::new(None);
+
+bitflags! {
+    pub struct EventFeatures: u8 {
+        /// Can fire an event at a fixed interval
+        const PERIODIC = 1 << 0;
+        /// Can fire a single event at an arbitrary time in the future
+        const ONE_SHOT = 1 << 1;
+    }
+}
+
+/// A free-running counter that can be used to tell the time
+pub trait ClockSource: Sync {
+    fn name(&self) -> &'static str;
+
+    /// How good this source is (i.e. resolution, cost to read, stability). Higher is better.
+    fn rating(&self) -> u32;
+
+    /// The rate the counter increments at, in Hz
+    fn frequency(&self) -> u64;
+
+    /// Reads the current value of the counter
+    fn read(&self) -> u64;
+
+    /// Called when this source is selected
+    fn enable(&self) {}
+}
+
+/// A device that can raise an interrupt in the future. Drivers should call
+/// `time::timer::handle_event` whenever the device fires.
+pub trait ClockEvent: Sync {
+    fn name(&self) -> &'static str;
+
+    /// How good this device is (i.e. resolution, cost to program). Higher is better.
+    fn rating(&self) -> u32;
+
+    fn features(&self) -> EventFeatures;
+
+    /// Fires an event every `period_ns` nanoseconds
+    fn set_periodic(&self, period_ns: u64);
+
+    /// Fires a single event `delta_ns` nanoseconds from now. Devices without `ONE_SHOT` return an
+    /// error.
+    fn set_oneshot(&self, delta_ns: u64) -> Result<(), &'static str>;
+
+    /// Called when this device is selected
+    fn enable(&self) {}
+
+    /// Stops the device from firing any more events
+    fn stop(&self);
+}
+
+struct Registry {
+    sources: [Option<&'static dyn ClockSource>; MAX_DEVICES],
+    events: [Option<&'static dyn ClockEvent>; MAX_DEVICES],
+}
+
+struct CurrentSource {
+    source: &'static dyn ClockSource,
+
+    /// The value of the counter when this source was selected
+    base_count: u64,
+
+    /// The monotonic time when this source was selected
+    base_ns: u64,
+}
+
+/// Registers a clock source to be considered by `select`
+pub fn register_source(source: &'static dyn ClockSource) {
+    let mut registry = REGISTRY.lock();
+    let slot = registry
+        .sources
+        .iter_mut()
+        .find(|slot| slot.is_none())
+        .expect("Too many clock sources registered!");
+    *slot = Some(source);
+}
+
+/// Registers a clock event device to be considered by `select`
+pub fn register_event(event: &'static dyn ClockEvent) {
+    let mut registry = REGISTRY.lock();
+    let slot = registry
+        .events
+        .iter_mut()
+        .find(|slot| slot.is_none())
+        .expect("Too many clock event devices registered!");
+    *slot = Some(event);
+}
+
+/// Switches to the best registered clock source and event device, stopping every other event
+/// device.
+pub fn select() {
+    let (source, event) = {
+        let registry = REGISTRY.lock();
+        let source = registry
+            .sources
+            .iter()
+            .flatten()
+            .max_by_key(|source| source.rating())
+            .copied();
+        let event = registry
+            .events
+            .iter()
+            .flatten()
+            .max_by_key(|event| event.rating())
+            .copied();
+
+        (source, event)
+    };
+
+    if let Some(source) = source {
+        let base_ns = super::monotonic();
+        source.enable();
+        *CURRENT_SOURCE.lock() = Some(CurrentSource {
+            source,
+            base_count: source.read(),
+            base_ns,
+        });
+        info!(
+            "using clock source {} ({} Hz)",
+            source.name(),
+            source.frequency()
+        );
+    }
+
+    // the source must be enabled first, as some devices are both a source and an event device
+    let registry = REGISTRY.lock();
+    for unused in registry.events.iter().flatten() {
+        if event.map_or(true, |event| !same_device(event, *unused)) {
+            unused.stop();
+        }
+    }
+    drop(registry);
+
+    if let Some(event) = event {
+        event.enable();
+        *CURRENT_EVENT.lock() = Some(event);
+        info!("using clock event device {}", event.name());
+    }
+}
+
+fn same_device(a: &dyn ClockEvent, b: &dyn ClockEvent) -> bool {
+    a as *const dyn ClockEvent as *const u8 == b as *const dyn ClockEvent as *const u8
+}
+
+/// Returns the number of nanoseconds since boot according to the current clock source, or None
+/// if no source has been selected yet.
+pub(super) fn current_ns() -> Option<u64> {
+    let current = CURRENT_SOURCE.lock();
+    let current = current.as_ref()?;
+
+    let elapsed = current.source.read().wrapping_sub(current.base_count) as u128;
+    let elapsed_ns = elapsed * NANOS_PER_SEC as u128 / current.source.frequency() as u128;
+
+    Some(current.base_ns + elapsed_ns as u64)
+}
+
+/// Returns the currently selected clock event device
+pub fn current_event() -> Option<&'static dyn ClockEvent> {
+    *CURRENT_EVENT.lock()
+}
@@ -0,0 +1,180 @@
+use core::fmt;
+
+const SECS_PER_MINUTE: u64 = 60;
+const SECS_PER_HOUR: u64 = 60 * SECS_PER_MINUTE;
+const SECS_PER_DAY: u64 = 24 * SECS_PER_HOUR;
+
+/// A UTC calendar date and time, with a resolution of one second
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl DateTime {
+    /// Converts seconds since the Unix epoch into a date and time
+    pub fn from_unix(timestamp: u64) -> DateTime {
+        let days = timestamp / SECS_PER_DAY;
+        let secs = timestamp % SECS_PER_DAY;
+        let (year, month, day) = civil_from_days(days);
+
+        DateTime {
+            year: year as u16,
+            month: month as u8,
+            day: day as u8,
+            hour: (secs / SECS_PER_HOUR) as u8,
+            minute: (secs % SECS_PER_HOUR / SECS_PER_MINUTE) as u8,
+            second: (secs % SECS_PER_MINUTE) as u8,
+        }
+    }
+
+    /// Converts this date and time into seconds since the Unix epoch, or `None` if it isn't
+    /// valid (i.e. read from an RTC that was never set)
+    pub fn to_unix(&self) -> Option<u64> {
+        if !self.is_valid() {
+            return None;
+        }
+        let days = days_from_civil(self.year as u64, self.month as u64, self.day as u64);
+
+        Some(
+            days * SECS_PER_DAY
+                + self.hour as u64 * SECS_PER_HOUR
+                + self.minute as u64 * SECS_PER_MINUTE
+                + self.second as u64,
+        )
+    }
+
+    /// Returns true if every field is in range, and the date isn't before the Unix epoch
+    pub fn is_valid(&self) -> bool {
+        self.year >= 1970
+            && (1..=12).contains(&self.month)
+            && (1..=days_in_month(self.year, self.month)).contains(&self.day)
+            && self.hour < 24
+            && self.minute < 60
+            && self.second < 60
+    }
+}
+
+impl fmt::Display for DateTime {
+    /// Formats as ISO 8601, i.e. `2020-07-04T13:37:00Z`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+            self.year, self.month, self.day, self.hour, self.minute, self.second
+        )
+    }
+}
+
+// The two conversions below are Howard Hinnant's `days_from_civil` and `civil_from_days`
+// algorithms, restricted to dates after the Unix epoch. Years are shifted to start in March,
+// so the leap day is always the last day of the year.
+
+fn days_in_month(year: u16, month: u8) -> u8 {
+    let is_leap_year = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    match month {
+        2 if is_leap_year => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Returns the number of days between the Unix epoch and the given date, which must be valid
+fn days_from_civil(year: u64, month: u64, day: u64) -> u64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let month_from_march = (month + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    // 719468 is the number of days from 0000-03-01 to 1970-01-01
+    era * 146097 + day_of_era - 719468
+}
+
+/// Returns the (year, month, day) that is `days` days after the Unix epoch
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn datetime(year: u16, month: u8, day: u8, hour: u8, minute: u8, second: u8) -> DateTime {
+        DateTime {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        }
+    }
+
+    #[test]
+    fn epoch() {
+        assert_eq!(DateTime::from_unix(0), datetime(1970, 1, 1, 0, 0, 0));
+        assert_eq!(datetime(1970, 1, 1, 0, 0, 0).to_unix(), Some(0));
+    }
+
+    #[test]
+    fn known_timestamps() {
+        assert_eq!(
+            DateTime::from_unix(951_782_400),
+            datetime(2000, 2, 29, 0, 0, 0)
+        );
+        assert_eq!(
+            DateTime::from_unix(1_700_000_000),
+            datetime(2023, 11, 14, 22, 13, 20)
+        );
+        assert_eq!(datetime(2038, 1, 19, 3, 14, 8).to_unix(), Some(1 << 31));
+    }
+
+    #[test]
+    fn round_trip() {
+        for timestamp in (0..4_102_444_800).step_by(86_399 * 7) {
+            assert_eq!(DateTime::from_unix(timestamp).to_unix(), Some(timestamp));
+        }
+    }
+
+    #[test]
+    fn invalid_dates() {
+        // what an RTC that was never set might read
+        assert_eq!(datetime(2000, 0, 0, 0, 0, 0).to_unix(), None);
+        assert_eq!(datetime(2021, 2, 29, 0, 0, 0).to_unix(), None);
+        assert_eq!(datetime(2020, 4, 31, 0, 0, 0).to_unix(), None);
+        assert_eq!(datetime(2020, 1, 1, 24, 0, 0).to_unix(), None);
+        assert_eq!(datetime(2020, 13, 1, 0, 0, 0).to_unix(), None);
+        assert_eq!(datetime(1969, 12, 31, 23, 59, 59).to_unix(), None);
+        assert!(datetime(2020, 2, 29, 23, 59, 59).to_unix().is_some());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(
+            datetime(2020, 7, 4, 13, 37, 0).to_string(),
+            "2020-07-04T13:37:00Z"
+        );
+    }
+}
@@ -0,0 +1,154 @@
+//! Kernel timekeeping
+
+pub mod clock;
+pub mod datetime;
+pub mod timer;
+
+pub use datetime::DateTime;
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use crate::arch::device::{hpet, pit, rtc, tsc};
+use crate::arch::instructions::interrupts;
+use crate::arch::interrupt::lapic;
+use crate::warn;
+
+pub const NANOS_PER_SEC: u64 = 1_000_000_000;
+pub const NANOS_PER_MILLI: u64 = 1_000_000;
+
+/// How often the system timer ticks, in Hz
+pub const TICK_FREQUENCY: u32 = 1000;
+
+/// How long to spend calibrating the TSC against the PIT
+const TSC_CALIBRATION_MS: u64 = 50;
+
+/// The measured frequency of the TSC in Hz, or 0 if it has not been calibrated
+static TSC_FREQUENCY: AtomicU64 = AtomicU64::new(0);
+
+/// Set once the system timer is running, and so `monotonic` is meaningful
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+/// The wall-clock time read from the RTC during `init` (as a Unix timestamp), and the value of
+/// the monotonic clock when it was read. The RTC only has a resolution of one second, so after
+/// boot, wall-clock time is kept by the monotonic clock instead.
+static BOOT_UNIX_TIME: AtomicU64 = AtomicU64::new(0);
+static BOOT_MONOTONIC_NS: AtomicU64 = AtomicU64::new(0);
+
+/// Set once `BOOT_UNIX_TIME` holds a valid time read from the RTC
+static HAS_WALL_CLOCK: AtomicBool = AtomicBool::new(false);
+
+/// Starts the system timer, calibrates the TSC, and then picks the best available clock source
+/// and clock event device. The local APIC and frame allocator must already be initialized.
+pub fn init() {
+    pit::init(TICK_FREQUENCY);
+    STARTED.store(true, Ordering::SeqCst);
+
+    let tsc_frequency = pit::calibrate_tsc(TSC_CALIBRATION_MS);
+    TSC_FREQUENCY.store(tsc_frequency, Ordering::SeqCst);
+
+    clock::register_source(&pit::PIT);
+    clock::register_event(&pit::PIT);
+    clock::register_source(&tsc::TSC);
+    clock::register_event(&rtc::RTC);
+
+    if hpet::init() {
+        clock::register_source(&hpet::HPET);
+        if hpet::can_raise_events() {
+            clock::register_event(&hpet::HPET);
+        }
+    }
+
+    if lapic::init_timer() {
+        clock::register_event(&lapic::LOCAL_APIC_TIMER);
+    }
+
+    clock::select();
+
+    // one-shot devices are programmed on demand by the timer subsystem
+    if let Some(event) = clock::current_event() {
+        if !event.features().contains(clock::EventFeatures::ONE_SHOT) {
+            event.set_periodic(NANOS_PER_SEC / TICK_FREQUENCY as u64);
+        }
+    }
+
+    BOOT_MONOTONIC_NS.store(monotonic(), Ordering::SeqCst);
+    let time = rtc::read_time();
+    match time.to_unix() {
+        Some(timestamp) => {
+            BOOT_UNIX_TIME.store(timestamp, Ordering::SeqCst);
+            HAS_WALL_CLOCK.store(true, Ordering::SeqCst);
+        }
+        None => warn!("The RTC time {} isn't valid, using the Unix epoch", time),
+    }
+}
+
+/// Returns the number of nanoseconds since the system timer was started.
+pub fn monotonic() -> u64 {
+    // until a clock source is selected, all we have is the PIT tick
+    clock::current_ns().unwrap_or_else(pit::elapsed_ns)
+}
+
+/// Returns true once `init` has started the system timer
+pub fn has_started() -> bool {
+    STARTED.load(Ordering::Relaxed)
+}
+
+/// Returns the current UTC date and time. Before `init`, this is the Unix epoch.
+pub fn wall_clock() -> DateTime {
+    DateTime::from_unix(unix_ns().unwrap_or(0) / NANOS_PER_SEC)
+}
+
+/// Returns the number of nanoseconds since the Unix epoch, or `None` until `init` has read a
+/// valid time from the RTC.
+pub fn unix_ns() -> Option<u64> {
+    if !HAS_WALL_CLOCK.load(Ordering::Relaxed) {
+        return None;
+    }
+
+    let elapsed = monotonic() - BOOT_MONOTONIC_NS.load(Ordering::Relaxed);
+    Some(BOOT_UNIX_TIME.load(Ordering::Relaxed) * NANOS_PER_SEC + elapsed)
+}
+
+/// Busy-waits for at least `ms` milliseconds. Interrupts must be enabled, or the timer may
+/// never advance.
+pub fn sleep_ms(ms: u64) {
+    debug_assert!(
+        interrupts::are_enabled(),
+        "sleep_ms called with interrupts disabled!"
+    );
+
+    let deadline = monotonic() + ms * NANOS_PER_MILLI;
+    while monotonic() < deadline {
+        core::hint::spin_loop();
+    }
+}
+
+/// Returns the frequency of the TSC in Hz, if it has been calibrated
+pub fn tsc_frequency() -> Option<u64> {
+    match TSC_FREQUENCY.load(Ordering::Relaxed) {
+        0 => None,
+        frequency => Some(frequency),
+    }
+}
+
+/// Converts a number of TSC cycles into nanoseconds, if the TSC has been calibrated
+pub fn tsc_to_ns(cycles: u64) -> Option<u64> {
+    let frequency = tsc_frequency()? as u128;
+    Some((cycles as u128 * NANOS_PER_SEC as u128 / frequency) as u64)
+}
+
+#[cfg(feature = "kernel-test")]
+mod kernel_test {
+    use super::*;
+    use juntos_macros::kernel_test;
+
+    #[kernel_test]
+    fn monotonic_advances() {
+        let start = monotonic();
+        sleep_ms(10);
+        let elapsed = monotonic() - start;
+
+        assert!(elapsed >= 10 * NANOS_PER_MILLI);
+        assert!(elapsed < NANOS_PER_SEC);
+    }
+}
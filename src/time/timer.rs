@@ -0,0 +1,167 @@
+//! One-shot timer callbacks, driven by the current clock event device.
+
+use super::clock::{self, EventFeatures};
+use crate::sync::IrqSafeMutex;
+use crate::warn;
+
+/// The maximum number of timers that can be pending at once
+const MAX_TIMERS: usize = 32;
+
+static TIMERS: IrqSafeMutex<Timers> = IrqSafeMutex::new(Timers {
+    slots: [Slot {
+        generation: 0,
+        timer: None,
+    }; MAX_TIMERS],
+});
+
+#[derive(Debug, Copy, Clone)]
+struct Timer {
+    /// The monotonic time (in nanoseconds) to fire at
+    deadline: u64,
+    callback: fn(),
+}
+
+/// A slot for a pending timer. The generation changes every time the slot is reused, so a stale
+/// `TimerId` can't cancel the slot's next timer.
+#[derive(Debug, Copy, Clone)]
+struct Slot {
+    generation: u32,
+    timer: Option<Timer>,
+}
+
+struct Timers {
+    slots: [Slot; MAX_TIMERS],
+}
+
+impl Timers {
+    fn insert(&mut self, timer: Timer) -> Option<TimerId> {
+        let index = self.slots.iter().position(|slot| slot.timer.is_none())?;
+        let slot = &mut self.slots[index];
+        slot.generation = slot.generation.wrapping_add(1);
+        slot.timer = Some(timer);
+        Some(TimerId {
+            index,
+            generation: slot.generation,
+        })
+    }
+
+    fn cancel(&mut self, id: TimerId) {
+        let slot = &mut self.slots[id.index];
+        if slot.generation == id.generation {
+            slot.timer = None;
+        }
+    }
+
+    /// Takes the timer at `index` out of its slot, if it has expired by `now`
+    fn take_expired(&mut self, index: usize, now: u64) -> Option<Timer> {
+        let slot = &mut self.slots[index];
+        match slot.timer {
+            Some(timer) if timer.deadline <= now => slot.timer.take(),
+            _ => None,
+        }
+    }
+
+    fn next_deadline(&self) -> Option<u64> {
+        self.slots
+            .iter()
+            .filter_map(|slot| slot.timer)
+            .map(|timer| timer.deadline)
+            .min()
+    }
+}
+
+/// A handle to a pending timer, which can be used to cancel it
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct TimerId {
+    index: usize,
+    generation: u32,
+}
+
+/// Calls `callback` (from interrupt context) once at least `delay_ns` nanoseconds have passed.
+/// Returns None if too many timers are already pending.
+pub fn schedule(delay_ns: u64, callback: fn()) -> Option<TimerId> {
+    let deadline = super::monotonic() + delay_ns;
+    let id = TIMERS.lock().insert(Timer { deadline, callback })?;
+
+    program_next();
+    Some(id)
+}
+
+/// Cancels a pending timer. Does nothing if it has already fired, even if its slot has been
+/// reused by another timer since.
+pub fn cancel(id: TimerId) {
+    TIMERS.lock().cancel(id);
+}
+
+/// Runs every expired timer, and reprograms the clock event device for the next one.
+/// Called by clock event device drivers whenever their interrupt fires.
+pub fn handle_event() {
+    let now = super::monotonic();
+
+    for index in 0..MAX_TIMERS {
+        // take each timer out before calling it, so callbacks are free to schedule new timers
+        let expired = TIMERS.lock().take_expired(index, now);
+
+        if let Some(timer) = expired {
+            (timer.callback)();
+        }
+    }
+
+    program_next();
+}
+
+/// If the current device supports one-shot events, programs it for the earliest pending timer.
+/// Periodic devices will simply check for expired timers on every tick.
+fn program_next() {
+    let event = match clock::current_event() {
+        Some(event) if event.features().contains(EventFeatures::ONE_SHOT) => event,
+        _ => return,
+    };
+
+    let next_deadline = TIMERS.lock().next_deadline();
+
+    if let Some(deadline) = next_deadline {
+        let delta = deadline.saturating_sub(super::monotonic()).max(1);
+        if let Err(error) = event.set_oneshot(delta) {
+            warn!("Couldn't program {}: {}", event.name(), error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn timers() -> Timers {
+        Timers {
+            slots: [Slot {
+                generation: 0,
+                timer: None,
+            }; MAX_TIMERS],
+        }
+    }
+
+    fn timer(deadline: u64) -> Timer {
+        Timer {
+            deadline,
+            callback: || {},
+        }
+    }
+
+    #[test]
+    fn stale_id_does_not_cancel_reused_slot() {
+        let mut timers = timers();
+        let stale = timers.insert(timer(10)).unwrap();
+        assert!(timers.take_expired(stale.index, 10).is_some());
+
+        // the slot is reused by the next timer
+        let current = timers.insert(timer(20)).unwrap();
+        assert_eq!(current.index, stale.index);
+        assert_ne!(current, stale);
+
+        timers.cancel(stale);
+        assert_eq!(timers.next_deadline(), Some(20));
+        timers.cancel(current);
+        assert_eq!(timers.next_deadline(), None);
+    }
+}
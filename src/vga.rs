@@ -2,14 +2,16 @@ use core::fmt::{Error, Write};
 use core::ptr;
 use core::slice;
 use lazy_static::lazy_static;
-use spin::Mutex;
+
+use crate::klog::Sink;
+use crate::sync::IrqSafeMutex;
 
 const SCREEN_WIDTH: usize = 80;
 const SCREEN_HEIGHT: usize = 25;
 
 lazy_static! {
-    pub static ref VGA_WRITER: Mutex<VgaWriter<'static>> = {
-        let vga = Mutex::new(VgaWriter::new(unsafe {
+    pub static ref VGA_WRITER: IrqSafeMutex<VgaWriter<'static>> = {
+        let vga = IrqSafeMutex::new(VgaWriter::new(unsafe {
             slice::from_raw_parts_mut(
                 0xFFFF_8000_000b_8000 as *mut VgaChar,
                 SCREEN_WIDTH * SCREEN_HEIGHT,
@@ -177,6 +179,21 @@ impl<'a> Write for VgaWriter<'a> {
     }
 }
 
+/// The screen, as a log sink
+pub static VGA_SINK: VgaSink = VgaSink;
+
+pub struct VgaSink;
+
+impl Sink for VgaSink {
+    fn name(&self) -> &'static str {
+        "vga"
+    }
+
+    fn write_str(&self, s: &str) {
+        VGA_WRITER.lock().write_str(s).unwrap();
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;